@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::alerting::{notifiers, rules, silences};
+use crate::resources::collector::{Collector, Sample, Topic};
+use crate::squire;
+
+/// Time (in seconds) a resolved alert remains visible, before it is dropped.
+const RESOLVED_RETENTION: i64 = 900;
+
+/// Enum for the state of an alert.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Pending,
+    Firing,
+    Resolved,
+}
+
+/// Struct for an alert raised by a rule.
+///
+/// ## Fields
+///
+/// * `rule` - Rule expression that raised the alert.
+/// * `state` - Current state of the alert.
+/// * `value` - Latest value of the subject, or `None` if it is missing.
+/// * `since` - Epoch timestamp (in seconds) at which the condition started to hold.
+/// * `fired_at` - Epoch timestamp (in seconds) at which the alert started firing.
+/// * `resolved_at` - Epoch timestamp (in seconds) at which the alert was resolved.
+/// * `silenced` - Whether the alert is muted by a silence or a maintenance window.
+/// * `clearing_since` - Epoch timestamp (in seconds) at which a firing alert's condition stopped holding.
+#[derive(Serialize, Clone, Debug)]
+pub struct Alert {
+    pub rule: String,
+    pub state: State,
+    pub value: Option<f64>,
+    pub since: i64,
+    pub fired_at: Option<i64>,
+    pub resolved_at: Option<i64>,
+    pub silenced: bool,
+    #[serde(skip)]
+    clearing_since: Option<i64>,
+}
+
+impl Alert {
+    /// Creates an alert whose condition started to hold, which is pending until it holds for the rule's duration.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - Rule expression that raised the alert.
+    /// * `value` - Value of the subject, or `None` if it is missing.
+    /// * `now` - Epoch timestamp (in seconds) of the evaluation.
+    pub fn pending(rule: &str, value: Option<f64>, now: i64) -> Alert {
+        Alert {
+            rule: rule.to_string(),
+            state: State::Pending,
+            value,
+            since: now,
+            fired_at: None,
+            resolved_at: None,
+            silenced: false,
+            clearing_since: None,
+        }
+    }
+}
+
+/// Struct for the alerting engine, that evaluates the rules against every frame it receives from the collector.
+///
+/// ## Fields
+///
+/// * `rules` - Rules declared in the configuration.
+/// * `alerts` - Alerts that are pending, firing or recently resolved, keyed by the index of their rule.
+/// * `dispatcher` - Dispatcher that sends the transitions to the notifiers.
+/// * `silences` - Silences and maintenance windows that mute the alerts.
+pub struct Engine {
+    rules: Vec<rules::Rule>,
+    alerts: Mutex<HashMap<usize, Alert>>,
+    dispatcher: notifiers::Dispatcher,
+    silences: Arc<silences::Silences>,
+}
+
+impl Engine {
+    /// Evaluates all the rules against a sample and updates the state of the alerts.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - Sample taken by the collector.
+    /// * `now` - Epoch timestamp (in seconds) of the evaluation.
+    ///
+    /// # Returns
+    ///
+    /// Returns the alerts that transitioned to firing or resolved in this evaluation.
+    fn evaluate(&self, sample: &Sample, now: i64) -> Vec<Alert> {
+        let muted: Vec<bool> = self.rules.iter().map(|rule| self.silences.mutes(rule, now)).collect();
+        let mut alerts = self.alerts.lock().unwrap();
+        let mut transitions = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let value = rule.value(sample);
+            let firing = alerts.get(&index).is_some_and(|alert| alert.state == State::Firing);
+            let holds = rule.holds(value, firing);
+            match alerts.get_mut(&index) {
+                Some(alert) if alert.state == State::Firing => {
+                    alert.value = value;
+                    if holds {
+                        alert.clearing_since = None;
+                        continue;
+                    }
+                    // Hysteresis: the condition must stay clear for the same duration, before the alert resolves
+                    let clearing_since = *alert.clearing_since.get_or_insert(now);
+                    if now - clearing_since >= rule.duration {
+                        alert.state = State::Resolved;
+                        alert.resolved_at = Some(now);
+                        log::info!("Alert resolved: {}", rule.expression);
+                        transitions.push(index);
+                    }
+                }
+                Some(alert) if alert.state == State::Pending => {
+                    alert.value = value;
+                    if !holds {
+                        alerts.remove(&index);
+                    } else if now - alert.since >= rule.duration {
+                        alert.state = State::Firing;
+                        alert.fired_at = Some(now);
+                        log::warn!("Alert firing: {}", rule.expression);
+                        transitions.push(index);
+                    }
+                }
+                _ if holds => {
+                    let mut alert = Alert::pending(&rule.expression, value, now);
+                    if rule.duration == 0 {
+                        alert.state = State::Firing;
+                        alert.fired_at = Some(now);
+                        log::warn!("Alert firing: {}", rule.expression);
+                        transitions.push(index);
+                    }
+                    alerts.insert(index, alert);
+                }
+                Some(alert) => {
+                    alert.value = value;
+                }
+                None => (),
+            }
+        }
+        alerts.retain(|_, alert| {
+            alert.resolved_at.map(|resolved_at| now - resolved_at < RESOLVED_RETENTION).unwrap_or(true)
+        });
+        for (index, alert) in alerts.iter_mut() {
+            alert.silenced = muted[*index];
+        }
+        transitions.iter().filter_map(|index| alerts.get(index).cloned()).collect()
+    }
+
+    /// Lists the alerts that are pending, firing or recently resolved.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec` of alerts, with the firing alerts first and the most recent ones first within a state.
+    pub fn alerts(&self) -> Vec<Alert> {
+        let mut alerts: Vec<Alert> = self.alerts.lock().unwrap().values().cloned().collect();
+        let rank = |state: State| match state {
+            State::Firing => 0,
+            State::Pending => 1,
+            State::Resolved => 2,
+        };
+        alerts.sort_by(|a, b| rank(a.state).cmp(&rank(b.state)).then(b.since.cmp(&a.since)));
+        alerts
+    }
+
+    /// Receives frames from the collector and evaluates the rules, until the collector goes away.
+    ///
+    /// # Arguments
+    ///
+    /// * `collector` - Shared background collector that fans out the sampled frames.
+    /// * `interval` - Interval between two evaluations.
+    fn run(&self, collector: Arc<Collector>, interval: Duration) {
+        let (subscriber, mut receiver) = collector.subscribe();
+        let topics = self.rules.iter().flat_map(|rule| rule.topics()).collect::<Vec<Topic>>();
+        let unused: Vec<Topic> = Topic::all().into_iter().filter(|topic| !topics.contains(topic)).collect();
+        subscriber.remove_topics(&unused);
+        subscriber.set_interval(interval);
+        while let Some(frame) = receiver.blocking_recv() {
+            let sample: Sample = match serde_json::from_str(&frame.payload) {
+                Ok(sample) => sample,
+                Err(err) => {
+                    log::error!("Failed to parse the frame for alerting: {}", err);
+                    continue;
+                }
+            };
+            let (silenced, transitions): (Vec<Alert>, Vec<Alert>) = self.evaluate(&sample, Utc::now().timestamp())
+                .into_iter()
+                .partition(|alert| alert.silenced);
+            for alert in silenced {
+                log::info!("Alert silenced, notification suppressed: {}", alert.rule);
+            }
+            self.dispatcher.dispatch(&transitions);
+        }
+    }
+}
+
+/// Parses the rules and spawns the alerting engine in a dedicated thread, when there are any rules.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+/// * `silences` - Silences and maintenance windows that mute the alerts.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Engine` struct.
+pub fn start(config: &squire::settings::Config,
+             collector: Arc<Collector>,
+             silences: Arc<silences::Silences>) -> Arc<Engine> {
+    // Rules are validated during startup
+    let rules: Vec<rules::Rule> = config.alert_rules.iter().filter_map(|rule| rules::parse(rule).ok()).collect();
+    // Notifiers are only started when there are rules to notify
+    let enabled: &[squire::settings::Notifier] = if rules.is_empty() { &[] } else { &config.notifiers };
+    let dispatcher = notifiers::start(enabled);
+    let engine = Arc::new(Engine {
+        rules,
+        alerts: Mutex::new(HashMap::new()),
+        dispatcher,
+        silences,
+    });
+    if engine.rules.is_empty() {
+        return engine;
+    }
+    log::info!("Evaluating {} alert rules every {}s", engine.rules.len(), config.alert_interval);
+    let worker = engine.clone();
+    let interval = Duration::from_secs(config.alert_interval as u64);
+    thread::Builder::new()
+        .name("alerting".to_string())
+        .spawn(move || worker.run(collector, interval))
+        .expect("Failed to spawn the alerting thread");
+    engine
+}
@@ -0,0 +1,8 @@
+/// This module contains the parser for the alert rules, and the functions to evaluate them against a sample.
+pub mod rules;
+/// This module contains the engine that evaluates the alert rules and tracks the state of the alerts.
+pub mod engine;
+/// This module contains the notifiers that send the alerts through webhooks, email and syslog.
+pub mod notifiers;
+/// This module contains the silences and maintenance windows that mute the alerts.
+pub mod silences;
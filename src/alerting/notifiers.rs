@@ -0,0 +1,736 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use regex::Regex;
+
+use crate::alerting::engine::{Alert, State};
+use crate::squire::settings::{Channel, Notifier};
+
+/// Maximum time to wait on a network operation, while sending a notification.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum length (in bytes) of a line in an SMTP reply, which is well beyond the 512 bytes required by RFC 5321.
+const MAX_REPLY_LENGTH: usize = 4096;
+
+/// Maximum number of lines in a multi-line SMTP reply, which is plenty for the extensions listed after `EHLO`.
+const MAX_REPLY_LINES: usize = 128;
+
+/// Validates the settings of a notifier.
+///
+/// # Arguments
+///
+/// * `notifier` - Notifier as declared in the configuration.
+///
+/// # Returns
+///
+/// Returns a `Result` with the reason the notifier is invalid, if any.
+pub fn validate(notifier: &Notifier) -> Result<(), String> {
+    for pattern in &notifier.rules {
+        Regex::new(pattern).map_err(|err| format!("invalid rule pattern '{}': {}", pattern, err))?;
+    }
+    for state in &notifier.states {
+        if state != "firing" && state != "resolved" {
+            return Err(format!("state '{}' should be either 'firing' or 'resolved'", state));
+        }
+    }
+    if notifier.rate_period <= 0 {
+        return Err("'rate_period' should be greater than zero".to_string());
+    }
+    match &notifier.channel {
+        Channel::Webhook { url, .. } | Channel::Slack { url, .. } => {
+            url::Url::parse(url).map_err(|err| format!("invalid url '{}': {}", url, err))?;
+        }
+        Channel::Email { tls, to, .. } => {
+            if !["starttls", "tls", "none"].contains(&tls.as_str()) {
+                return Err(format!("'tls' should be one of 'starttls', 'tls' or 'none', received '{}'", tls));
+            }
+            if to.is_empty() {
+                return Err("'to' should contain at least one recipient".to_string());
+            }
+        }
+        Channel::Syslog { protocol, facility, .. } => {
+            if protocol != "udp" && protocol != "tcp" {
+                return Err(format!("'protocol' should be either 'udp' or 'tcp', received '{}'", protocol));
+            }
+            if *facility > 23 {
+                return Err(format!("'facility' should be between 0 and 23, received '{}'", facility));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name of the state, as used in the configuration.
+fn state_name(state: State) -> &'static str {
+    match state {
+        State::Pending => "pending",
+        State::Firing => "firing",
+        State::Resolved => "resolved",
+    }
+}
+
+/// Formats an epoch timestamp (in seconds) as an RFC 3339 string.
+fn timestamp(epoch: i64) -> String {
+    Utc.timestamp_opt(epoch, 0)
+        .single()
+        .map(|datetime| datetime.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// Builds the subject line of a notification.
+fn subject(alert: &Alert, host: &str) -> String {
+    format!("[SysMonk] {} on {}: {}", state_name(alert.state).to_uppercase(), host, alert.rule)
+}
+
+/// Builds the plain text body of a notification.
+fn body(alert: &Alert, host: &str) -> String {
+    let value = alert.value.map(|value| format!("{:.2}", value)).unwrap_or("missing".to_string());
+    let mut lines = vec![
+        format!("Host: {}", host),
+        format!("Rule: {}", alert.rule),
+        format!("State: {}", state_name(alert.state)),
+        format!("Value: {}", value),
+        format!("Since: {}", timestamp(alert.since)),
+    ];
+    if let Some(fired_at) = alert.fired_at {
+        lines.push(format!("Fired at: {}", timestamp(fired_at)));
+    }
+    if let Some(resolved_at) = alert.resolved_at {
+        lines.push(format!("Resolved at: {}", timestamp(resolved_at)));
+    }
+    lines.join("\n")
+}
+
+/// Posts a JSON payload to a webhook, retrying with an exponential backoff on failures.
+///
+/// # Arguments
+///
+/// * `client` - Blocking HTTP client.
+/// * `url` - URL of the webhook.
+/// * `payload` - JSON payload to post.
+/// * `retries` - Number of retries after the first attempt.
+/// * `backoff` - Time (in seconds) to wait before the first retry, which doubles on every retry.
+fn post(client: &reqwest::blocking::Client,
+        url: &str,
+        payload: &serde_json::Value,
+        retries: u32,
+        backoff: u64) -> Result<(), String> {
+    let mut attempt = 0;
+    loop {
+        let error = match client.post(url).json(payload).send() {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => format!("webhook responded with {}", response.status()),
+            Err(err) => err.to_string(),
+        };
+        if attempt >= retries {
+            return Err(error);
+        }
+        let delay = backoff.saturating_mul(1 << attempt.min(16));
+        log::warn!("Webhook attempt {} failed: {}, retrying in {}s", attempt + 1, error, delay);
+        thread::sleep(Duration::from_secs(delay));
+        attempt += 1;
+    }
+}
+
+/// Enum for an SMTP connection, which may be upgraded to TLS.
+enum Connection {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Connection {
+    /// Opens a TCP connection to the server.
+    fn open(host: &str, port: u16) -> Result<Connection, String> {
+        let address = (host, port).to_socket_addrs()
+            .map_err(|err| err.to_string())?
+            .next()
+            .ok_or(format!("unable to resolve '{}'", host))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(|err| err.to_string())?;
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+        stream.set_write_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+        Ok(Connection::Plain(stream))
+    }
+
+    /// Performs the TLS handshake over the connection, verifying the certificate of the server.
+    fn upgrade(self, host: &str) -> Result<Connection, String> {
+        let stream = match self {
+            Connection::Plain(stream) => stream,
+            tls => return Ok(tls),
+        };
+        let connector = SslConnector::builder(SslMethod::tls()).map_err(|err| err.to_string())?.build();
+        let stream = connector.connect(host, stream).map_err(|err| err.to_string())?;
+        Ok(Connection::Tls(Box::new(stream)))
+    }
+
+    /// Reads a reply from the server, which may span multiple lines.
+    ///
+    /// # See Also
+    ///
+    /// Both the length of each line and the number of lines are capped, so a misbehaving server can't exhaust the
+    /// memory by never ending a line or a reply.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the reply code and the text of the reply.
+    fn reply(&mut self) -> Result<(u16, String), String> {
+        let mut text = String::new();
+        for _ in 0..MAX_REPLY_LINES {
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            while !line.ends_with(b"\r\n") {
+                if line.len() >= MAX_REPLY_LENGTH {
+                    return Err(format!("reply line exceeds {} bytes", MAX_REPLY_LENGTH));
+                }
+                match self.read(&mut byte) {
+                    Ok(0) => return Err("connection closed by the server".to_string()),
+                    Ok(_) => line.push(byte[0]),
+                    Err(err) => return Err(err.to_string()),
+                }
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            let code = line.get(..3)
+                .and_then(|code| code.parse::<u16>().ok())
+                .ok_or(format!("invalid reply '{}'", line))?;
+            text.push_str(line.get(4..).unwrap_or_default());
+            // A hyphen after the code indicates that the reply continues in the next line
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+            text.push('\n');
+        }
+        Err(format!("reply exceeds {} lines", MAX_REPLY_LINES))
+    }
+
+    /// Sends a command to the server and checks the reply code.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Command to send, without the line ending.
+    /// * `expected` - Reply codes that indicate success.
+    fn command(&mut self, command: &str, expected: &[u16]) -> Result<String, String> {
+        self.write_all(format!("{}\r\n", command).as_bytes()).map_err(|err| err.to_string())?;
+        let (code, text) = self.reply()?;
+        if expected.contains(&code) {
+            Ok(text)
+        } else {
+            // Credentials are not included in the error
+            let verb = command.split_whitespace().next().unwrap_or_default();
+            Err(format!("'{}' failed with {} {}", verb, code, text))
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            Connection::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            Connection::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            Connection::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Sends an email via SMTP.
+///
+/// # Arguments
+///
+/// * `channel` - Email channel with the server settings, sender and recipients.
+/// * `host` - Hostname of this server, used as the client name in the greeting.
+/// * `subject` - Subject of the email.
+/// * `body` - Plain text body of the email.
+///
+/// # References
+///
+/// * [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321)
+/// * [RFC 3207](https://datatracker.ietf.org/doc/html/rfc3207)
+fn send_email(channel: &Channel, host: &str, subject: &str, body: &str) -> Result<(), String> {
+    let (server, port, tls, username, password, from, to) = match channel {
+        Channel::Email { host, port, tls, username, password, from, to } => {
+            (host, *port, tls.as_str(), username, password, from, to)
+        }
+        _ => return Err("not an email channel".to_string()),
+    };
+    let mut connection = Connection::open(server, port)?;
+    if tls == "tls" {
+        connection = connection.upgrade(server)?;
+    }
+    let (code, greeting) = connection.reply()?;
+    if code != 220 {
+        return Err(format!("server greeted with {} {}", code, greeting));
+    }
+    let ehlo = format!("EHLO {}", host);
+    let mut extensions = connection.command(&ehlo, &[250])?;
+    if tls == "starttls" {
+        if !extensions.lines().any(|line| line.eq_ignore_ascii_case("STARTTLS")) {
+            return Err("server doesn't support STARTTLS".to_string());
+        }
+        connection.command("STARTTLS", &[220])?;
+        connection = connection.upgrade(server)?;
+        extensions = connection.command(&ehlo, &[250])?;
+    }
+    if let Some(username) = username {
+        if !extensions.lines().any(|line| line.to_uppercase().starts_with("AUTH")) {
+            return Err("server doesn't support authentication".to_string());
+        }
+        let password = password.as_deref().unwrap_or_default();
+        let credentials = STANDARD.encode(format!("\0{}\0{}", username, password));
+        connection.command(&format!("AUTH PLAIN {}", credentials), &[235])?;
+    }
+    connection.command(&format!("MAIL FROM:<{}>", from), &[250])?;
+    for recipient in to {
+        connection.command(&format!("RCPT TO:<{}>", recipient), &[250, 251])?;
+    }
+    connection.command("DATA", &[354])?;
+    let headers = [
+        format!("From: {}", from),
+        format!("To: {}", to.join(", ")),
+        format!("Subject: {}", subject),
+        format!("Date: {}", Utc::now().to_rfc2822()),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+    ];
+    // Lines starting with a period are escaped, since a lone period terminates the message
+    let body: Vec<String> = body.lines()
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() })
+        .collect();
+    let message = format!("{}\r\n\r\n{}\r\n.", headers.join("\r\n"), body.join("\r\n"));
+    connection.command(&message, &[250])?;
+    let _ = connection.command("QUIT", &[221]);
+    Ok(())
+}
+
+/// Sends a syslog message over UDP or TCP.
+///
+/// # Arguments
+///
+/// * `channel` - Syslog channel with the server settings.
+/// * `alert` - Alert to send.
+/// * `host` - Hostname of this server.
+/// * `message` - Text of the message.
+///
+/// # References
+///
+/// * [RFC 5424](https://datatracker.ietf.org/doc/html/rfc5424)
+/// * [RFC 6587](https://datatracker.ietf.org/doc/html/rfc6587#section-3.4.1)
+fn send_syslog(channel: &Channel, alert: &Alert, host: &str, message: &str) -> Result<(), String> {
+    let (server, port, protocol, facility) = match channel {
+        Channel::Syslog { host, port, protocol, facility } => (host, *port, protocol.as_str(), *facility),
+        _ => return Err("not a syslog channel".to_string()),
+    };
+    // Firing alerts are logged as warnings (4), and resolved alerts as notices (5)
+    let severity = if alert.state == State::Firing { 4 } else { 5 };
+    let line = format!(
+        "<{}>1 {} {} sysmonk {} alert - {}",
+        facility as u16 * 8 + severity,
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        if host.is_empty() { "-" } else { host },
+        std::process::id(),
+        message,
+    );
+    let address = (server.as_str(), port).to_socket_addrs()
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or(format!("unable to resolve '{}'", server))?;
+    if protocol == "tcp" {
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(|err| err.to_string())?;
+        stream.set_write_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+        // Octet counting framing, so that the message may contain line breaks
+        stream.write_all(format!("{} {}", line.len(), line).as_bytes()).map_err(|err| err.to_string())?;
+    } else {
+        let bind = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind).map_err(|err| err.to_string())?;
+        socket.send_to(line.as_bytes(), address).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Struct for a worker that routes and sends the notifications for a single notifier.
+///
+/// ## Fields
+///
+/// * `notifier` - Notifier as declared in the configuration.
+/// * `rules` - Compiled patterns of the rules to notify.
+/// * `sent` - Epoch timestamps (in seconds) of the notifications sent within the rate period.
+/// * `host` - Hostname of this server.
+/// * `client` - Blocking HTTP client for the webhooks.
+struct Worker {
+    notifier: Notifier,
+    rules: Vec<Regex>,
+    sent: VecDeque<i64>,
+    host: String,
+    client: reqwest::blocking::Client,
+}
+
+impl Worker {
+    /// Checks whether the alert is routed to this notifier.
+    fn routes(&self, alert: &Alert) -> bool {
+        self.notifier.states.iter().any(|state| state == state_name(alert.state))
+            && (self.rules.is_empty() || self.rules.iter().any(|rule| rule.is_match(&alert.rule)))
+    }
+
+    /// Checks the rate limit and records the notification if it is allowed.
+    fn allow(&mut self, now: i64) -> bool {
+        if self.notifier.rate_limit == 0 {
+            return true;
+        }
+        while self.sent.front().is_some_and(|sent| now - sent >= self.notifier.rate_period) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.notifier.rate_limit {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+
+    /// Sends the notification for an alert through the channel.
+    fn send(&self, alert: &Alert) -> Result<(), String> {
+        let subject = subject(alert, &self.host);
+        match &self.notifier.channel {
+            Channel::Webhook { url, retries, backoff } => {
+                let payload = serde_json::json!({"host": self.host, "alert": alert});
+                post(&self.client, url, &payload, *retries, *backoff)
+            }
+            Channel::Slack { url, retries, backoff } => {
+                let payload = serde_json::json!({"text": format!("*{}*\n{}", subject, body(alert, &self.host))});
+                post(&self.client, url, &payload, *retries, *backoff)
+            }
+            Channel::Email { .. } => send_email(&self.notifier.channel, &self.host, &subject, &body(alert, &self.host)),
+            Channel::Syslog { .. } => send_syslog(&self.notifier.channel, alert, &self.host, &subject),
+        }
+    }
+
+    /// Receives the alerts and sends the notifications, until the dispatcher goes away.
+    fn run(&mut self, receiver: mpsc::Receiver<Alert>) {
+        while let Ok(alert) = receiver.recv() {
+            if !self.routes(&alert) {
+                continue;
+            }
+            if !self.allow(Utc::now().timestamp()) {
+                log::warn!("Rate limit reached, dropped the notification for '{}'", alert.rule);
+                continue;
+            }
+            if let Err(err) = self.send(&alert) {
+                log::error!("Failed to send the notification for '{}': {}", alert.rule, err);
+            }
+        }
+    }
+}
+
+/// Struct for the dispatcher that fans out the alert transitions to the notifiers.
+///
+/// ## Fields
+///
+/// * `senders` - Channels to the workers, one per notifier.
+pub struct Dispatcher {
+    senders: Vec<mpsc::Sender<Alert>>,
+}
+
+impl Dispatcher {
+    /// Hands the alerts over to every notifier, without waiting for them to be sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `alerts` - Alerts that transitioned to firing or resolved.
+    pub fn dispatch(&self, alerts: &[Alert]) {
+        for alert in alerts {
+            for sender in &self.senders {
+                let _ = sender.send(alert.clone());
+            }
+        }
+    }
+}
+
+/// Spawns a worker thread for each notifier, so that a slow or unreachable channel doesn't hold back the others.
+///
+/// # Arguments
+///
+/// * `notifiers` - Notifiers declared in the configuration.
+///
+/// # Returns
+///
+/// Returns the `Dispatcher` struct that hands the alerts over to the workers.
+pub fn start(notifiers: &[Notifier]) -> Dispatcher {
+    let host = sysinfo::System::host_name().unwrap_or_default();
+    let mut senders = Vec::new();
+    for (index, notifier) in notifiers.iter().enumerate() {
+        let (sender, receiver) = mpsc::channel();
+        let notifier = notifier.clone();
+        let host = host.clone();
+        // The blocking HTTP client is created within the thread, since it can't be dropped in an async context
+        let spawned = thread::Builder::new()
+            .name(format!("notifier-{}", index))
+            .spawn(move || {
+                let client = match reqwest::blocking::Client::builder().timeout(TIMEOUT).build() {
+                    Ok(client) => client,
+                    Err(err) => {
+                        log::error!("Failed to create the HTTP client for notifiers: {}", err);
+                        return;
+                    }
+                };
+                let mut worker = Worker {
+                    // Patterns are validated during startup
+                    rules: notifier.rules.iter().filter_map(|rule| Regex::new(rule).ok()).collect(),
+                    notifier,
+                    sent: VecDeque::new(),
+                    host,
+                    client,
+                };
+                worker.run(receiver);
+            });
+        match spawned {
+            Ok(_) => senders.push(sender),
+            Err(err) => log::error!("Failed to spawn the notifier thread: {}", err),
+        }
+    }
+    Dispatcher { senders }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Serves a single SMTP session, replying to every command and returning the lines received.
+    fn smtp_server(listener: TcpListener) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut received = Vec::new();
+            writer.write_all(b"220-smtp.test ESMTP\r\n220 ready\r\n").unwrap();
+            let mut data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end_matches("\r\n").to_string();
+                received.push(line.clone());
+                let reply: &[u8] = if data {
+                    if line != "." {
+                        continue;
+                    }
+                    data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("EHLO") {
+                    b"250-smtp.test\r\n250-SIZE 1048576\r\n250 AUTH PLAIN LOGIN\r\n"
+                } else if line.starts_with("AUTH") {
+                    b"235 authenticated\r\n"
+                } else if line == "DATA" {
+                    data = true;
+                    b"354 end with <CRLF>.<CRLF>\r\n"
+                } else if line == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+            }
+            received
+        })
+    }
+
+    /// Serves HTTP requests with the status codes in order, returning the number of requests received.
+    fn http_server(listener: TcpListener, statuses: Vec<u16>) -> thread::JoinHandle<usize> {
+        thread::spawn(move || {
+            for status in &statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                writer.write_all(response.as_bytes()).unwrap();
+            }
+            statuses.len()
+        })
+    }
+
+    fn alert() -> Alert {
+        let mut alert = Alert::pending("cpu_avg > 90%", Some(95.0), 1_700_000_000);
+        alert.state = State::Firing;
+        alert.fired_at = Some(1_700_000_000);
+        alert
+    }
+
+    #[test]
+    fn test_send_email() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let channel = Channel::Email {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            tls: "none".to_string(),
+            username: Some("monitor".to_string()),
+            password: Some("secret".to_string()),
+            from: "sysmonk@example.com".to_string(),
+            to: vec!["ops@example.com".to_string(), "oncall@example.com".to_string()],
+        };
+        let server = smtp_server(listener);
+        send_email(&channel, "web-1", "CPU is high", "first line\n.hidden\n.\nlast line").unwrap();
+        let received = server.join().unwrap();
+        let credentials = STANDARD.encode("\0monitor\0secret");
+        let data = received.iter().position(|line| line == "DATA").unwrap();
+        assert_eq!(received[..data], [
+            "EHLO web-1".to_string(),
+            format!("AUTH PLAIN {}", credentials),
+            "MAIL FROM:<sysmonk@example.com>".to_string(),
+            "RCPT TO:<ops@example.com>".to_string(),
+            "RCPT TO:<oncall@example.com>".to_string(),
+        ]);
+        assert!(received.contains(&"Subject: CPU is high".to_string()));
+        assert!(received.contains(&"To: ops@example.com, oncall@example.com".to_string()));
+        let body = received.iter().position(|line| line.is_empty()).unwrap();
+        assert_eq!(received[body + 1..], ["first line", "..hidden", "..", "last line", ".", "QUIT"]);
+    }
+
+    #[test]
+    fn test_send_email_without_auth_support() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"220 ready\r\n250-smtp.test\r\n250 SIZE 1048576\r\n").unwrap();
+        });
+        let channel = Channel::Email {
+            host: "127.0.0.1".to_string(),
+            port,
+            tls: "none".to_string(),
+            username: Some("monitor".to_string()),
+            password: None,
+            from: "sysmonk@example.com".to_string(),
+            to: vec!["ops@example.com".to_string()],
+        };
+        let result = send_email(&channel, "web-1", "CPU is high", "body");
+        server.join().unwrap();
+        assert_eq!(result, Err("server doesn't support authentication".to_string()));
+    }
+
+    #[test]
+    fn test_reply_is_capped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[b'2'; MAX_REPLY_LENGTH + 1]).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"250-line\r\n".repeat(MAX_REPLY_LINES + 1).as_slice()).unwrap();
+        });
+        let mut connection = Connection::open("127.0.0.1", port).unwrap();
+        assert_eq!(connection.reply(), Err(format!("reply line exceeds {} bytes", MAX_REPLY_LENGTH)));
+        let mut connection = Connection::open("127.0.0.1", port).unwrap();
+        assert_eq!(connection.reply(), Err(format!("reply exceeds {} lines", MAX_REPLY_LINES)));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_webhook_retries_on_server_errors() {
+        let client = reqwest::blocking::Client::builder().timeout(TIMEOUT).build().unwrap();
+        let payload = serde_json::json!({"alert": "cpu_avg > 90%"});
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = http_server(listener, vec![500, 503, 200]);
+        assert_eq!(post(&client, &url, &payload, 2, 0), Ok(()));
+        assert_eq!(server.join().unwrap(), 3);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = http_server(listener, vec![500, 502]);
+        let started = std::time::Instant::now();
+        let result = post(&client, &url, &payload, 1, 1);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.join().unwrap(), 2);
+        assert_eq!(result, Err("webhook responded with 502 Bad Gateway".to_string()));
+    }
+
+    #[test]
+    fn test_send_syslog() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let channel = Channel::Syslog {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            protocol: "tcp".to_string(),
+            facility: 16,
+        };
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = String::new();
+            stream.read_to_string(&mut received).unwrap();
+            received
+        });
+        send_syslog(&channel, &alert(), "web-1", "CPU is high").unwrap();
+        let received = server.join().unwrap();
+        let (length, line) = received.split_once(' ').unwrap();
+        assert_eq!(length.parse::<usize>().unwrap(), line.len());
+        let fields: Vec<&str> = line.splitn(8, ' ').collect();
+        // Facility local0 (16) with the warning severity (4)
+        assert_eq!(fields[0], "<132>1");
+        assert!(chrono::DateTime::parse_from_rfc3339(fields[1]).is_ok());
+        assert!(fields[1].ends_with('Z'));
+        assert_eq!(fields[2..], [
+            "web-1", "sysmonk", &std::process::id().to_string(), "alert", "-", "CPU is high"
+        ]);
+    }
+
+    #[test]
+    fn test_send_syslog_over_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let channel = Channel::Syslog {
+            host: "127.0.0.1".to_string(),
+            port: socket.local_addr().unwrap().port(),
+            protocol: "udp".to_string(),
+            facility: 1,
+        };
+        let mut alert = alert();
+        alert.state = State::Resolved;
+        send_syslog(&channel, &alert, "", "CPU is back to normal").unwrap();
+        let mut buffer = [0u8; 1024];
+        let size = socket.recv(&mut buffer).unwrap();
+        let line = String::from_utf8_lossy(&buffer[..size]).to_string();
+        // Facility user (1) with the notice severity (5), and the nil value for an unknown hostname
+        assert!(line.starts_with("<13>1 "));
+        assert!(line.contains(" - sysmonk "));
+        assert!(line.ends_with(" alert - CPU is back to normal"));
+    }
+}
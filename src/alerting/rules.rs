@@ -0,0 +1,431 @@
+use std::collections::HashSet;
+
+use crate::resources::collector::{number, percent, Sample, Topic};
+
+/// Enum for the fields of a service, process or container that a rule can compare.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Pid,
+    Cpu,
+    Memory,
+}
+
+/// Enum for the subject of a rule, which resolves to a single value in a sample.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Subject {
+    /// Average CPU usage across all cores, in percent.
+    CpuAvg,
+    /// Memory usage in percent.
+    Memory,
+    /// Swap usage in percent.
+    Swap,
+    /// Aggregate disk usage in percent.
+    Disk,
+    /// Load average for the period, as the key in `load_averages`.
+    Load(&'static str),
+    /// Disk usage of a mount point, in percent.
+    Mount(String),
+    /// Field of a configured service.
+    Service(String, Field),
+    /// Field of a configured process.
+    Process(String, Field),
+    /// Field of a docker container.
+    Docker(String, Field),
+}
+
+/// Enum for the comparison operators supported in a rule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Operator {
+    /// Compares the value against the threshold.
+    fn compare(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Operator::Gt => value > threshold,
+            Operator::Ge => value >= threshold,
+            Operator::Lt => value < threshold,
+            Operator::Le => value <= threshold,
+            Operator::Eq => value == threshold,
+            Operator::Ne => value != threshold,
+        }
+    }
+}
+
+/// Enum for the condition of a rule.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// The value of the subject is compared against the threshold.
+    Compare(Operator, f64),
+    /// The subject is not present in the sample.
+    Missing,
+}
+
+/// Struct for an alert rule declared in the configuration.
+///
+/// ## Fields
+///
+/// * `expression` - Rule as declared in the configuration, which also identifies the alert.
+/// * `subject` - Subject of the rule.
+/// * `condition` - Condition that triggers the alert.
+/// * `duration` - Time (in seconds) the condition must hold before the alert fires, and must clear before it resolves.
+/// * `clear` - Threshold the value must cross back before a firing alert resolves, defaults to the threshold.
+///
+/// # Examples
+///
+/// ```text
+/// cpu_avg > 90% for 5m clear 80%
+/// mount:/ > 85%
+/// load1 >= 4 for 30s
+/// service:nginx pid == 0
+/// process:postgres memory > 2147483648
+/// docker:redis missing for 1m
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub expression: String,
+    pub subject: Subject,
+    pub condition: Condition,
+    pub duration: i64,
+    pub clear: Option<f64>,
+}
+
+/// Parses a duration like `30s`, `5m` or `1h` into seconds.
+fn parse_duration(text: &str) -> Result<i64, String> {
+    let (value, multiplier) = match text.chars().last() {
+        Some('s') => (&text[..text.len() - 1], 1),
+        Some('m') => (&text[..text.len() - 1], 60),
+        Some('h') => (&text[..text.len() - 1], 3600),
+        _ => (text, 1),
+    };
+    value.parse::<i64>()
+        .ok()
+        .filter(|value| *value >= 0)
+        .map(|value| value * multiplier)
+        .ok_or(format!("invalid duration '{}'", text))
+}
+
+/// Parses a threshold like `90%` or `0.5` into a number.
+fn parse_threshold(text: &str) -> Result<f64, String> {
+    text.trim_end_matches('%')
+        .parse::<f64>()
+        .map_err(|_| format!("invalid threshold '{}'", text))
+}
+
+/// Parses the field of a service, process or container.
+fn parse_field(text: &str) -> Option<Field> {
+    match text {
+        "pid" => Some(Field::Pid),
+        "cpu" => Some(Field::Cpu),
+        "memory" => Some(Field::Memory),
+        _ => None,
+    }
+}
+
+/// Parses a rule expression.
+///
+/// # Arguments
+///
+/// * `expression` - Rule as declared in the configuration.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `Rule` struct, or the reason the expression is invalid.
+pub fn parse(expression: &str) -> Result<Rule, String> {
+    let mut tokens = expression.split_whitespace().peekable();
+    let subject = tokens.next().ok_or("rule is empty".to_string())?;
+    let subject = match subject.split_once(':') {
+        None => match subject {
+            "cpu_avg" => Subject::CpuAvg,
+            "memory" => Subject::Memory,
+            "swap" => Subject::Swap,
+            "disk" => Subject::Disk,
+            "load1" => Subject::Load("m1"),
+            "load5" => Subject::Load("m5"),
+            "load15" => Subject::Load("m15"),
+            _ => return Err(format!("unknown subject '{}'", subject)),
+        },
+        Some(("mount", path)) if !path.is_empty() => Subject::Mount(path.to_string()),
+        Some((kind, name)) if !name.is_empty() => {
+            // Field defaults to the PID, since it is irrelevant when checking if the subject is missing
+            let field = match tokens.peek().and_then(|token| parse_field(token)) {
+                Some(field) => {
+                    tokens.next();
+                    field
+                }
+                None => Field::Pid,
+            };
+            match kind {
+                "service" => Subject::Service(name.to_string(), field),
+                "process" => Subject::Process(name.to_string(), field),
+                "docker" => Subject::Docker(name.to_string(), field),
+                _ => return Err(format!("unknown subject '{}'", subject)),
+            }
+        }
+        _ => return Err(format!("subject '{}' is missing a name", subject)),
+    };
+    let operator = tokens.next().ok_or("rule is missing a condition".to_string())?;
+    let condition = match operator {
+        "missing" => Condition::Missing,
+        _ => {
+            let operator = match operator {
+                ">" => Operator::Gt,
+                ">=" => Operator::Ge,
+                "<" => Operator::Lt,
+                "<=" => Operator::Le,
+                "==" => Operator::Eq,
+                "!=" => Operator::Ne,
+                _ => return Err(format!("unknown operator '{}'", operator)),
+            };
+            let threshold = tokens.next().ok_or("rule is missing a threshold".to_string())?;
+            Condition::Compare(operator, parse_threshold(threshold)?)
+        }
+    };
+    let mut duration = 0;
+    let mut clear = None;
+    while let Some(keyword) = tokens.next() {
+        let value = tokens.next().ok_or(format!("'{}' is missing a value", keyword))?;
+        match keyword {
+            "for" => duration = parse_duration(value)?,
+            "clear" if condition != Condition::Missing => clear = Some(parse_threshold(value)?),
+            _ => return Err(format!("unexpected '{}'", keyword)),
+        }
+    }
+    Ok(Rule {
+        expression: expression.trim().to_string(),
+        subject,
+        condition,
+        duration,
+        clear,
+    })
+}
+
+/// Finds the row in a list within the sample, whose `key` matches the name.
+fn find<'a>(sample: &'a Sample, list: &str, key: &str, name: &str) -> Option<&'a serde_json::Value> {
+    sample.get(list)?
+        .as_array()?
+        .iter()
+        .find(|row| row.get(key).and_then(|value| value.as_str()) == Some(name))
+}
+
+/// Finds the rows in a list within the sample, whose `name` contains the name.
+///
+/// # See Also
+///
+/// Processes are selected by a substring of their name when they are monitored, so the same match is used here,
+/// to cover every row that a process name like `postgres` was configured to select.
+fn matching<'a>(sample: &'a Sample, list: &str, name: &str) -> Vec<&'a serde_json::Value> {
+    sample.get(list)
+        .and_then(|value| value.as_array())
+        .map(|rows| {
+            rows.iter()
+                .filter(|row| row["name"].as_str().is_some_and(|value| value.contains(name)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Rule {
+    /// Returns the topics that have to be sampled to evaluate the rule.
+    pub fn topics(&self) -> HashSet<Topic> {
+        let topic = match self.subject {
+            Subject::CpuAvg | Subject::Load(_) => Topic::Cpu,
+            Subject::Memory | Subject::Swap => Topic::Memory,
+            Subject::Disk | Subject::Mount(_) => Topic::Disks,
+            Subject::Service(..) => Topic::Services,
+            Subject::Process(..) => Topic::Processes,
+            Subject::Docker(..) => Topic::Docker,
+        };
+        HashSet::from([topic])
+    }
+
+    /// Resolves the value of the subject in a sample.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` containing the value, or `None` if the subject is not present in the sample.
+    pub fn value(&self, sample: &Sample) -> Option<f64> {
+        match &self.subject {
+            Subject::CpuAvg => {
+                let cores: Vec<f64> = sample.get("cpu_usage")?.as_array()?.iter().filter_map(number).collect();
+                (!cores.is_empty()).then(|| cores.iter().sum::<f64>() / cores.len() as f64)
+            }
+            Subject::Memory => percent(sample.get("memory_info")?),
+            Subject::Swap => percent(sample.get("swap_info")?),
+            Subject::Disk => percent(sample.get("disk_info")?),
+            Subject::Load(key) => number(sample.get("load_averages")?.get(key)?),
+            Subject::Mount(path) => percent(find(sample, "disk_usage", "mount_point", path)?),
+            Subject::Service(name, field) | Subject::Process(name, field) => {
+                let list = match self.subject {
+                    Subject::Service(..) => "service_stats",
+                    _ => "process_stats",
+                };
+                let rows = matching(sample, list, name);
+                if rows.is_empty() {
+                    return None;
+                }
+                // Services that aren't running have no values, which are treated as zero usage
+                let total = |key: &str| rows.iter().filter_map(|row| number(&row["values"][key])).sum::<f64>();
+                match field {
+                    // Running as long as any of the matching rows has a PID
+                    Field::Pid => Some(
+                        rows.iter().filter_map(|row| number(&row["pid"])).find(|pid| *pid != 0.0).unwrap_or_default()
+                    ),
+                    Field::Cpu => Some(total("cpu_percent")),
+                    Field::Memory => Some(total("memory_bytes")),
+                }
+            }
+            Subject::Docker(name, field) => {
+                let row = find(sample, "docker_stats", "Name", name)?;
+                match field {
+                    Field::Pid => number(&row["PIDs"]),
+                    Field::Cpu => number(&row["CPUPerc"]),
+                    Field::Memory => number(&row["MemPerc"]),
+                }
+            }
+        }
+    }
+
+    /// Checks whether the condition holds for the value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value of the subject, or `None` if it is missing.
+    /// * `firing` - Whether the alert is firing, in which case the clear threshold is used for hysteresis.
+    pub fn holds(&self, value: Option<f64>, firing: bool) -> bool {
+        match (&self.condition, value) {
+            (Condition::Missing, value) => value.is_none(),
+            (Condition::Compare(operator, threshold), Some(value)) => {
+                let threshold = if firing { self.clear.unwrap_or(*threshold) } else { *threshold };
+                operator.compare(value, threshold)
+            }
+            (Condition::Compare(..), None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    fn sample() -> Sample {
+        Sample::from([
+            ("cpu_usage".to_string(), json!(["95.5", "92.5"])),
+            ("disk_usage".to_string(), json!([
+                {"mount_point": "/", "total": 100, "used": 90},
+                {"mount_point": "/boot", "total": 100, "used": 10}
+            ])),
+            ("service_stats".to_string(), json!([
+                {"name": "nginx", "pid": 0, "values": null}
+            ])),
+            ("process_stats".to_string(), json!([
+                {"name": "postgres", "pid": 10, "values": {"cpu_percent": 1.5, "memory_bytes": 100}},
+                {"name": "postgres: walwriter", "pid": 11, "values": {"cpu_percent": 0.5, "memory_bytes": 50}}
+            ])),
+            ("docker_stats".to_string(), json!([
+                {"Name": "redis", "PIDs": "4", "CPUPerc": "0.25%", "MemPerc": "1.50%"}
+            ])),
+        ])
+    }
+
+    #[test]
+    fn test_cpu_avg_with_duration() {
+        let rule = parse("cpu_avg > 90% for 5m").unwrap();
+        assert_eq!(rule.subject, Subject::CpuAvg);
+        assert_eq!(rule.condition, Condition::Compare(Operator::Gt, 90.0));
+        assert_eq!(rule.duration, 300);
+        assert_eq!(rule.value(&sample()), Some(94.0));
+        assert!(rule.holds(rule.value(&sample()), false));
+    }
+
+    #[test]
+    fn test_mount() {
+        let rule = parse("mount:/ > 85%").unwrap();
+        assert_eq!(rule.subject, Subject::Mount("/".to_string()));
+        assert_eq!(rule.value(&sample()), Some(90.0));
+        assert!(rule.holds(rule.value(&sample()), false));
+        let rule = parse("mount:/boot > 85%").unwrap();
+        assert!(!rule.holds(rule.value(&sample()), false));
+    }
+
+    #[test]
+    fn test_service_pid() {
+        let rule = parse("service:nginx pid == 0").unwrap();
+        assert_eq!(rule.subject, Subject::Service("nginx".to_string(), Field::Pid));
+        assert_eq!(rule.value(&sample()), Some(0.0));
+        assert!(rule.holds(rule.value(&sample()), false));
+        let rule = parse("service:nginx cpu > 0").unwrap();
+        assert_eq!(rule.value(&sample()), Some(0.0));
+    }
+
+    #[test]
+    fn test_process_aggregates_matching_rows() {
+        let rule = parse("process:postgres memory > 120").unwrap();
+        assert_eq!(rule.value(&sample()), Some(150.0));
+        assert!(rule.holds(rule.value(&sample()), false));
+        let rule = parse("process:postgres cpu >= 2").unwrap();
+        assert_eq!(rule.value(&sample()), Some(2.0));
+        let rule = parse("process:walwriter pid == 0").unwrap();
+        assert_eq!(rule.value(&sample()), Some(11.0));
+        let rule = parse("process:mysql missing").unwrap();
+        assert_eq!(rule.value(&sample()), None);
+        assert!(rule.holds(None, false));
+    }
+
+    #[test]
+    fn test_docker_missing() {
+        let rule = parse("docker:redis missing for 1m").unwrap();
+        assert_eq!(rule.subject, Subject::Docker("redis".to_string(), Field::Pid));
+        assert_eq!(rule.condition, Condition::Missing);
+        assert_eq!(rule.duration, 60);
+        assert!(!rule.holds(rule.value(&sample()), false));
+        let rule = parse("docker:nginx missing").unwrap();
+        assert!(rule.holds(rule.value(&sample()), false));
+        let rule = parse("docker:redis cpu > 0.2").unwrap();
+        assert_eq!(rule.value(&sample()), Some(0.25));
+    }
+
+    #[test]
+    fn test_clear_hysteresis() {
+        let rule = parse("cpu_avg > 90% for 5m clear 80%").unwrap();
+        assert_eq!(rule.clear, Some(80.0));
+        // Below the threshold doesn't fire, but keeps a firing alert until it drops below the clear threshold
+        assert!(!rule.holds(Some(85.0), false));
+        assert!(rule.holds(Some(85.0), true));
+        assert!(!rule.holds(Some(75.0), true));
+        let rule = parse("cpu_avg > 90%").unwrap();
+        assert!(!rule.holds(Some(85.0), true));
+    }
+
+    #[test]
+    fn test_rejected_expressions() {
+        let rejected = [
+            "",
+            "cpu_max > 90%",
+            "mount: > 85%",
+            "service: pid == 0",
+            "queue:jobs > 1",
+            "memory",
+            "memory => 90",
+            "memory > ",
+            "memory > ninety",
+            "memory > 90 for 5d",
+            "memory > 90 for -5m",
+            "memory > 90 for",
+            "memory > 90 until 5m",
+            "docker:redis missing clear 5",
+        ];
+        for expression in rejected {
+            assert!(parse(expression).is_err(), "'{}' should be rejected", expression);
+        }
+    }
+}
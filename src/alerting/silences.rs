@@ -0,0 +1,432 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone, Weekday};
+use rand::{thread_rng, Rng};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::alerting::rules::{Rule, Subject};
+use crate::squire;
+
+/// Name of the file within `storage_dir`, that the silences are persisted to.
+const SILENCES_FILE: &str = "silences.json";
+
+/// Enum for the alerts that a silence or a maintenance window mutes.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// All the alerts on the host, or only on the named host.
+    Host(Option<String>),
+    /// Alerts whose rule expression matches the pattern.
+    Rule(Regex),
+    /// Alerts for the named service.
+    Service(String),
+    /// Alerts for the named process.
+    Process(String),
+    /// Alerts for the named docker container.
+    Docker(String),
+}
+
+/// Parses a matcher like `host`, `rule:^cpu_avg`, `service:nginx`, `process:postgres` or `docker:redis`.
+///
+/// # Arguments
+///
+/// * `text` - Matcher as declared by the user.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `Matcher` enum, or the reason the matcher is invalid.
+pub fn parse_matcher(text: &str) -> Result<Matcher, String> {
+    let text = text.trim();
+    match text.split_once(':') {
+        None if text == "host" => Ok(Matcher::Host(None)),
+        Some((kind, name)) if !name.is_empty() => match kind {
+            "host" => Ok(Matcher::Host(Some(name.to_string()))),
+            "rule" => Regex::new(name)
+                .map(Matcher::Rule)
+                .map_err(|err| format!("invalid rule pattern '{}': {}", name, err)),
+            "service" => Ok(Matcher::Service(name.to_string())),
+            "process" => Ok(Matcher::Process(name.to_string())),
+            "docker" => Ok(Matcher::Docker(name.to_string())),
+            _ => Err(format!("unknown matcher '{}'", text)),
+        },
+        _ => Err(format!("matcher '{}' should be 'host', or one of 'host', 'rule', 'service', 'process' or \
+                          'docker' followed by ':' and a name", text)),
+    }
+}
+
+impl Matcher {
+    /// Checks whether the matcher mutes the alerts raised by a rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - Rule that raises the alert.
+    /// * `host` - Hostname of this server.
+    fn matches(&self, rule: &Rule, host: &str) -> bool {
+        match (self, &rule.subject) {
+            (Matcher::Host(None), _) => true,
+            (Matcher::Host(Some(name)), _) => name.eq_ignore_ascii_case(host),
+            (Matcher::Rule(pattern), _) => pattern.is_match(&rule.expression),
+            (Matcher::Service(name), Subject::Service(subject, _)) => name == subject,
+            (Matcher::Process(name), Subject::Process(subject, _)) => name == subject,
+            (Matcher::Docker(name), Subject::Docker(subject, _)) => name == subject,
+            _ => false,
+        }
+    }
+}
+
+/// Struct for a recurring maintenance window declared in the configuration.
+///
+/// ## Fields
+///
+/// * `expression` - Window as declared in the configuration.
+/// * `days` - Days of the week on which the window starts.
+/// * `start` - Local time at which the window starts.
+/// * `end` - Local time at which the window ends, which is on the next day when it is before the start.
+/// * `matcher` - Alerts muted during the window.
+///
+/// # Examples
+///
+/// ```text
+/// sun 02:00-04:00
+/// mon,thu 23:30-00:30 service:nginx
+/// daily 03:00-03:15 rule:^process:
+/// ```
+#[derive(Clone, Debug)]
+pub struct Window {
+    pub expression: String,
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub matcher: Matcher,
+}
+
+/// Parses a maintenance window expression.
+///
+/// # Arguments
+///
+/// * `expression` - Window as declared in the configuration.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `Window` struct, or the reason the expression is invalid.
+pub fn parse_window(expression: &str) -> Result<Window, String> {
+    let mut tokens = expression.split_whitespace();
+    let days = tokens.next().ok_or("window is empty".to_string())?;
+    let days = if days == "daily" {
+        vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun]
+    } else {
+        days.split(',')
+            .map(|day| day.parse::<Weekday>().map_err(|_| format!("invalid day '{}'", day)))
+            .collect::<Result<Vec<Weekday>, String>>()?
+    };
+    let range = tokens.next().ok_or("window is missing a time range".to_string())?;
+    let (start, end) = range.split_once('-').ok_or(format!("invalid time range '{}'", range))?;
+    let parse_time = |text: &str| NaiveTime::parse_from_str(text, "%H:%M").map_err(|_| format!("invalid time '{}'", text));
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    if start == end {
+        return Err(format!("time range '{}' is empty", range));
+    }
+    let matcher = match tokens.next() {
+        Some(matcher) => parse_matcher(matcher)?,
+        None => Matcher::Host(None),
+    };
+    if let Some(token) = tokens.next() {
+        return Err(format!("unexpected '{}'", token));
+    }
+    Ok(Window {
+        expression: expression.trim().to_string(),
+        days,
+        start,
+        end,
+        matcher,
+    })
+}
+
+impl Window {
+    /// Checks whether the window is active at the given local time.
+    pub fn active(&self, now: DateTime<Local>) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        if self.start < self.end {
+            return self.days.contains(&today) && time >= self.start && time < self.end;
+        }
+        // Windows that wrap around midnight belong to the day they start on
+        (self.days.contains(&today) && time >= self.start) || (self.days.contains(&today.pred()) && time < self.end)
+    }
+}
+
+/// Struct for a silence created through the API.
+///
+/// ## Fields
+///
+/// * `id` - Unique identifier of the silence.
+/// * `matcher` - Matcher as declared by the user.
+/// * `starts_at` - Epoch timestamp (in seconds) at which the silence starts.
+/// * `ends_at` - Epoch timestamp (in seconds) at which the silence expires.
+/// * `created_by` - Username of the user who created the silence.
+/// * `created_at` - Epoch timestamp (in seconds) at which the silence was created.
+/// * `comment` - Reason for the silence.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Silence {
+    pub id: String,
+    pub matcher: String,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub created_by: String,
+    pub created_at: i64,
+    pub comment: String,
+}
+
+/// Struct for the status of a maintenance window.
+///
+/// ## Fields
+///
+/// * `expression` - Window as declared in the configuration.
+/// * `active` - Whether the window is currently active.
+#[derive(Serialize, Clone, Debug)]
+pub struct WindowStatus {
+    pub expression: String,
+    pub active: bool,
+}
+
+/// Struct for the silences and maintenance windows that mute the alerts.
+///
+/// ## Fields
+///
+/// * `entries` - Silences that haven't expired, along with their parsed matchers.
+/// * `windows` - Maintenance windows declared in the configuration.
+/// * `path` - Path of the file that the silences are persisted to, if `storage_dir` is set.
+/// * `host` - Hostname of this server.
+pub struct Silences {
+    entries: Mutex<Vec<(Silence, Matcher)>>,
+    windows: Vec<Window>,
+    path: Option<PathBuf>,
+    host: String,
+}
+
+impl Silences {
+    /// Writes the silences to the file, replacing it atomically.
+    fn save(&self, entries: &[(Silence, Matcher)]) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let silences: Vec<&Silence> = entries.iter().map(|(silence, _)| silence).collect();
+        let temporary = path.with_extension("json.tmp");
+        let result = serde_json::to_vec_pretty(&silences)
+            .map_err(|err| err.to_string())
+            .and_then(|content| fs::write(&temporary, content).map_err(|err| err.to_string()))
+            .and_then(|_| fs::rename(&temporary, path).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            log::error!("Failed to persist the silences to '{}': {}", path.display(), err);
+        }
+    }
+
+    /// Drops the silences that have expired, and persists the change if any.
+    fn prune(&self, entries: &mut Vec<(Silence, Matcher)>, now: i64) {
+        let count = entries.len();
+        entries.retain(|(silence, _)| silence.ends_at > now);
+        if entries.len() != count {
+            self.save(entries);
+        }
+    }
+
+    /// Creates a silence.
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - Matcher for the alerts to mute.
+    /// * `starts_at` - Epoch timestamp (in seconds) at which the silence starts.
+    /// * `ends_at` - Epoch timestamp (in seconds) at which the silence expires.
+    /// * `created_by` - Username of the user creating the silence.
+    /// * `comment` - Reason for the silence.
+    /// * `now` - Current epoch timestamp (in seconds).
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the created `Silence`, or the reason it is invalid.
+    pub fn create(&self,
+                  matcher: &str,
+                  starts_at: i64,
+                  ends_at: i64,
+                  created_by: &str,
+                  comment: &str,
+                  now: i64) -> Result<Silence, String> {
+        let parsed = parse_matcher(matcher)?;
+        if ends_at <= starts_at || ends_at <= now {
+            return Err("silence should end after it starts, and in the future".to_string());
+        }
+        if comment.trim().is_empty() {
+            return Err("comment is required".to_string());
+        }
+        let silence = Silence {
+            id: format!("{:016x}", thread_rng().gen::<u64>()),
+            matcher: matcher.trim().to_string(),
+            starts_at,
+            ends_at,
+            created_by: created_by.to_string(),
+            created_at: now,
+            comment: comment.trim().to_string(),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.push((silence.clone(), parsed));
+        self.prune(&mut entries, now);
+        self.save(&entries);
+        log::info!("Silence '{}' created by '{}' for '{}'", silence.id, created_by, silence.matcher);
+        Ok(silence)
+    }
+
+    /// Removes a silence.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean value to indicate whether the silence existed.
+    pub fn remove(&self, id: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.retain(|(silence, _)| silence.id != id);
+        if entries.len() == count {
+            return false;
+        }
+        self.save(&entries);
+        true
+    }
+
+    /// Lists the silences that are active or upcoming, ordered by the time they start.
+    pub fn silences(&self, now: i64) -> Vec<Silence> {
+        let mut entries = self.entries.lock().unwrap();
+        self.prune(&mut entries, now);
+        let mut silences: Vec<Silence> = entries.iter().map(|(silence, _)| silence.clone()).collect();
+        silences.sort_by_key(|silence| silence.starts_at);
+        silences
+    }
+
+    /// Lists the maintenance windows along with whether they are currently active.
+    pub fn windows(&self, now: i64) -> Vec<WindowStatus> {
+        let local = Local.timestamp_opt(now, 0).single().unwrap_or_else(Local::now);
+        self.windows.iter()
+            .map(|window| WindowStatus { expression: window.expression.clone(), active: window.active(local) })
+            .collect()
+    }
+
+    /// Checks whether the alerts raised by a rule are muted by an active silence or maintenance window.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - Rule that raises the alert.
+    /// * `now` - Current epoch timestamp (in seconds).
+    pub fn mutes(&self, rule: &Rule, now: i64) -> bool {
+        let local = Local.timestamp_opt(now, 0).single().unwrap_or_else(Local::now);
+        if self.windows.iter().any(|window| window.active(local) && window.matcher.matches(rule, &self.host)) {
+            return true;
+        }
+        self.entries.lock().unwrap().iter().any(|(silence, matcher)| {
+            silence.starts_at <= now && now < silence.ends_at && matcher.matches(rule, &self.host)
+        })
+    }
+}
+
+/// Loads the silences persisted in `storage_dir`, along with the maintenance windows declared in the configuration.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Silences` struct.
+pub fn load(config: &squire::settings::Config) -> Arc<Silences> {
+    let path = config.storage_dir.as_ref().map(|directory| PathBuf::from(directory).join(SILENCES_FILE));
+    let mut entries = Vec::new();
+    if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+        match fs::read(path).map_err(|err| err.to_string())
+            .and_then(|content| serde_json::from_slice::<Vec<Silence>>(&content).map_err(|err| err.to_string())) {
+            Ok(silences) => {
+                for silence in silences {
+                    match parse_matcher(&silence.matcher) {
+                        Ok(matcher) => entries.push((silence, matcher)),
+                        Err(err) => log::warn!("Dropped the silence '{}': {}", silence.id, err),
+                    }
+                }
+                log::info!("Loaded {} silences from '{}'", entries.len(), path.display());
+            }
+            Err(err) => log::error!("Failed to load the silences from '{}': {}", path.display(), err),
+        }
+    }
+    Arc::new(Silences {
+        entries: Mutex::new(entries),
+        // Windows are validated during startup
+        windows: config.maintenance_windows.iter().filter_map(|window| parse_window(window).ok()).collect(),
+        path,
+        host: sysinfo::System::host_name().unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Returns the local time on the given day of January 2024, which starts on a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 1, day, hour, minute, 0).single().unwrap()
+    }
+
+    #[test]
+    fn test_window_within_a_day() {
+        let window = parse_window("Sun 02:00-04:00").unwrap();
+        assert_eq!(window.days, [Weekday::Sun]);
+        assert!(matches!(window.matcher, Matcher::Host(None)));
+        assert_eq!(at(7, 2, 0).weekday(), Weekday::Sun);
+        assert!(window.active(at(7, 2, 0)));
+        assert!(window.active(at(7, 3, 59)));
+        assert!(!window.active(at(7, 4, 0)));
+        assert!(!window.active(at(7, 1, 59)));
+        assert!(!window.active(at(6, 3, 0)));
+        assert!(!window.active(at(8, 3, 0)));
+    }
+
+    #[test]
+    fn test_window_across_midnight() {
+        let window = parse_window("Sat 22:00-02:00 service:nginx").unwrap();
+        assert!(matches!(window.matcher, Matcher::Service(ref name) if name == "nginx"));
+        assert!(window.active(at(6, 23, 0)));
+        // Belongs to Saturday, when it started, rather than to Sunday
+        assert!(window.active(at(7, 1, 0)));
+        assert!(!window.active(at(7, 2, 0)));
+        assert!(!window.active(at(7, 23, 0)));
+        assert!(!window.active(at(6, 1, 0)));
+    }
+
+    #[test]
+    fn test_daily_window() {
+        let window = parse_window("daily 03:00-03:15 rule:^process:").unwrap();
+        assert_eq!(window.days.len(), 7);
+        for day in 1..=7 {
+            assert!(window.active(at(day, 3, 5)));
+            assert!(!window.active(at(day, 3, 15)));
+        }
+    }
+
+    #[test]
+    fn test_rejected_windows() {
+        let rejected = [
+            "",
+            "sun",
+            "sun 02:00-02:00",
+            "sun 02:00",
+            "sun 02:00-25:00",
+            "sun 2am-4am",
+            "someday 02:00-04:00",
+            "sun,,mon 02:00-04:00",
+            "sun 02:00-04:00 queue:jobs",
+            "sun 02:00-04:00 host extra",
+        ];
+        for expression in rejected {
+            assert!(parse_window(expression).is_err(), "'{}' should be rejected", expression);
+        }
+    }
+}
@@ -1,7 +1,25 @@
-use fernet::Fernet;
+use crate::squire;
+use chrono::Utc;
+use fernet::{Fernet, MultiFernet};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::collections::HashMap;
-use std::env;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use std::{env, fs, io, thread};
+
+/// Interval between two sweeps of the expired sessions.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of the file within `storage_dir`, that the generated key to encrypt the session tokens is persisted to.
+const SECRET_KEY_FILE: &str = "secret.key";
+
+/// Name of the file within `storage_dir`, that the sessions are persisted to.
+const SESSIONS_FILE: &str = "sessions.json";
+
+/// Name of the file within `storage_dir`, that the sessions revoked before they expired are persisted to.
+const REVOKED_FILE: &str = "revoked.json";
 
 /// Struct to store the cargo information gathered at compile time using the `env!` macro.
 #[allow(dead_code)]
@@ -45,42 +63,640 @@ pub fn build_info() -> Arc<MetaData> {
     Arc::new(metadata)
 }
 
+/// Struct for a session created on a successful login.
+///
+/// ## Fields
+///
+/// * `id` - Identifier of the session, that is safe to share with the user and the admins.
+/// * `hash` - Hash of the secret stored in the `session_token` cookie, so the secret itself is never stored.
+/// * `username` - Username of the authenticated user.
+/// * `created_at` - Epoch timestamp (in seconds) at which the session was created.
+/// * `last_seen` - Epoch timestamp (in seconds) at which the session was last used.
+/// * `expires_at` - Epoch timestamp (in seconds) at which the session expires.
+/// * `ip` - IP address from which the user logged in.
+/// * `user_agent` - User agent of the browser used to log in.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionRecord {
+    pub id: String,
+    #[serde(skip)]
+    pub hash: String,
+    pub username: String,
+    pub created_at: i64,
+    pub last_seen: i64,
+    pub expires_at: i64,
+    pub ip: String,
+    pub user_agent: String,
+}
+
+/// Struct for a session as it is persisted, since the hash is left out when a session is listed.
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    #[serde(flatten)]
+    record: SessionRecord,
+    hash: String,
+}
+
+/// Struct for the claims of a `session_token` cookie, which can only be encrypted with the secret keys.
+///
+/// ## Fields
+///
+/// * `id` - Identifier of the session.
+/// * `key` - Secret that proves the ownership of the session.
+/// * `username` - Username of the authenticated user.
+/// * `issued_at` - Epoch timestamp (in seconds) at which the session was created.
+pub struct Claims {
+    pub id: String,
+    pub key: String,
+    pub username: String,
+    pub issued_at: i64,
+}
+
+/// Struct for the sessions that were revoked before they expired.
+///
+/// ## Fields
+///
+/// * `sessions` - Epoch timestamp (in seconds) at which the revoked sessions expire, keyed by their identifier.
+/// * `users` - Epoch timestamp (in seconds) at which all the sessions of a user were revoked, keyed by the username.
+/// * `modified` - Modified time of the file when it was last read, so that it is only read again once it changes.
+#[derive(Serialize, Deserialize, Default)]
+struct Revoked {
+    sessions: HashMap<String, i64>,
+    users: HashMap<String, i64>,
+    #[serde(skip)]
+    modified: Option<SystemTime>,
+}
+
+impl Revoked {
+    /// Checks whether a session was revoked, either by itself or along with all the sessions of the user.
+    fn contains(&self, id: &str, username: &str, created_at: i64) -> bool {
+        self.sessions.contains_key(id) || self.users.get(username).is_some_and(|revoked_at| created_at <= *revoked_at)
+    }
+
+    /// Adds the revocations made by another instance.
+    fn merge(&mut self, other: Revoked) {
+        self.sessions.extend(other.sessions);
+        for (username, revoked_at) in other.users {
+            let latest = self.users.entry(username).or_insert(revoked_at);
+            *latest = revoked_at.max(*latest);
+        }
+    }
+
+    /// Drops the revocations that outlived every session they could apply to.
+    fn prune(&mut self, now: i64, duration: i64) {
+        self.sessions.retain(|_, expires_at| now < *expires_at);
+        self.users.retain(|_, revoked_at| now < *revoked_at + duration);
+    }
+}
+
 /// Struct to store the session information.
 ///
 /// ## Fields
 ///
-/// * `mapping` - Used to store username and session token's payload as key value pairs.
+/// * `mapping` - Used to store the sessions keyed by their identifier, so a user can have many sessions at once.
+/// * `revoked` - Sessions that were revoked before they expired, so that their cookies are rejected.
+/// * `duration` - Time (in seconds) each session lasts from the time it was created.
+/// * `path` - Path of the file that the sessions are persisted to, if `storage_dir` is set.
+/// * `revoked_path` - Path of the file that the revoked sessions are persisted to, if `storage_dir` is set.
 ///
 /// ## See Also:
 ///
-/// These fields are updated and used only for authenticated sessions.
+/// - These fields are updated and used only for authenticated sessions.
+/// - A cookie that is decrypted with the secret keys is accepted even without a session in the `mapping`,
+///   so the instances behind a load balancer that share the keys accept each other's sessions.
+/// - Revocations are read from the file again whenever it changes, so the instances that share `storage_dir`
+///   reject the sessions that were revoked on any of them.
 pub struct Session {
-    pub mapping: Mutex<HashMap<String, String>>,
+    pub mapping: Mutex<HashMap<String, SessionRecord>>,
+    revoked: Mutex<Revoked>,
+    duration: i64,
+    path: Option<PathBuf>,
+    revoked_path: Option<PathBuf>,
+}
+
+/// Reads the revoked sessions persisted in a file, along with its modified time.
+fn read_revoked(path: &Path) -> Result<Revoked, String> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).map_err(|err| err.to_string())?;
+    let content = fs::read(path).map_err(|err| err.to_string())?;
+    let mut revoked: Revoked = serde_json::from_slice(&content).map_err(|err| err.to_string())?;
+    revoked.modified = Some(modified);
+    Ok(revoked)
 }
 
+impl Session {
+    /// Writes the sessions to the file, so that they survive a restart.
+    ///
+    /// # Arguments
+    ///
+    /// * `mapping` - Sessions keyed by their identifier.
+    ///
+    /// # See Also
+    ///
+    /// The time a session was last seen is only persisted along with the other changes, to avoid a write per request.
+    fn save(&self, mapping: &HashMap<String, SessionRecord>) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let sessions: Vec<StoredSession> = mapping.values()
+            .map(|record| StoredSession { record: record.clone(), hash: record.hash.clone() })
+            .collect();
+        let result = serde_json::to_vec(&sessions)
+            .map_err(io::Error::other)
+            .and_then(|content| squire::util::write_private(path, &content));
+        if let Err(err) = result {
+            log::error!("Failed to persist the sessions to '{}': {}", path.display(), err);
+        }
+    }
+
+    /// Reads the revocations made by the other instances, when the file has changed since it was last read.
+    fn refresh(&self, revoked: &mut Revoked) {
+        let path = match &self.revoked_path {
+            Some(path) => path,
+            None => return,
+        };
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_none() || modified == revoked.modified {
+            return;
+        }
+        match read_revoked(path) {
+            Ok(loaded) => {
+                revoked.modified = loaded.modified;
+                revoked.merge(loaded);
+            }
+            Err(err) => log::error!("Failed to read the revoked sessions from '{}': {}", path.display(), err),
+        }
+    }
+
+    /// Writes the revoked sessions to the file, along with the ones revoked by the other instances.
+    fn save_revoked(&self, revoked: &mut Revoked, now: i64) {
+        self.refresh(revoked);
+        revoked.prune(now, self.duration);
+        let path = match &self.revoked_path {
+            Some(path) => path,
+            None => return,
+        };
+        let result = serde_json::to_vec(revoked)
+            .map_err(io::Error::other)
+            .and_then(|content| squire::util::write_private(path, &content))
+            .and_then(|_| fs::metadata(path).and_then(|metadata| metadata.modified()));
+        match result {
+            Ok(modified) => revoked.modified = Some(modified),
+            Err(err) => log::error!("Failed to persist the revoked sessions to '{}': {}", path.display(), err),
+        }
+    }
+
+    /// Checks whether a session was revoked, on this or any other instance that shares `storage_dir`.
+    fn is_revoked(&self, id: &str, username: &str, created_at: i64) -> bool {
+        let mut revoked = self.revoked.lock().unwrap();
+        self.refresh(&mut revoked);
+        revoked.contains(id, username, created_at)
+    }
+
+    /// Creates a new session for a user, that has just logged in.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the authenticated user.
+    /// * `ip` - IP address from which the user logged in.
+    /// * `user_agent` - User agent of the browser used to log in.
+    /// * `now` - Epoch timestamp (in seconds) of the login.
+    /// * `duration` - Time (in seconds) the session should last.
+    ///
+    /// # Returns
+    ///
+    /// Returns the secret to store in the `session_token` cookie, which is only ever returned once,
+    /// along with the created `SessionRecord`.
+    pub fn create(&self,
+                  username: &str,
+                  ip: &str,
+                  user_agent: &str,
+                  now: i64,
+                  duration: i64) -> (String, SessionRecord) {
+        let key = squire::secure::keygen();
+        let record = SessionRecord {
+            id: squire::secure::keygen()[..16].to_string(),
+            hash: squire::secure::calculate_hash(key.clone()),
+            username: username.to_string(),
+            created_at: now,
+            last_seen: now,
+            expires_at: now + duration,
+            ip: ip.to_string(),
+            user_agent: user_agent.to_string(),
+        };
+        let mut mapping = self.mapping.lock().unwrap();
+        mapping.insert(record.id.clone(), record.clone());
+        self.save(&mapping);
+        (key, record)
+    }
+
+    /// Looks up a session with the claims of the cookie, and marks it as seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - Claims of the `session_token` cookie, which was decrypted with the secret keys.
+    /// * `ip` - IP address of the request, which is recorded for a session created by another instance.
+    /// * `user_agent` - User agent of the request, which is recorded for a session created by another instance.
+    /// * `now` - Epoch timestamp (in seconds) of the request.
+    ///
+    /// # See Also
+    ///
+    /// Only the secret keys can encrypt the claims, so a session that is missing from the `mapping` was created
+    /// by another instance that shares the keys. It is added to the `mapping`, unless it has expired or was revoked.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the `SessionRecord`, or the reason why the session is not valid.
+    pub fn touch(&self, claims: &Claims, ip: &str, user_agent: &str, now: i64) -> Result<SessionRecord, &'static str> {
+        if self.is_revoked(&claims.id, &claims.username, claims.issued_at) {
+            let mut mapping = self.mapping.lock().unwrap();
+            if mapping.remove(&claims.id).is_some() {
+                self.save(&mapping);
+            }
+            return Err("Session has been revoked");
+        }
+        let mut mapping = self.mapping.lock().unwrap();
+        if let Some(record) = mapping.get_mut(&claims.id) {
+            if record.hash != squire::secure::calculate_hash(claims.key.clone()) || record.username != claims.username {
+                return Err("Invalid session token");
+            }
+            if now >= record.expires_at {
+                mapping.remove(&claims.id);
+                self.save(&mapping);
+                return Err("Session Expired");
+            }
+            record.last_seen = now;
+            return Ok(record.clone());
+        }
+        if now >= claims.issued_at + self.duration {
+            return Err("Session Expired");
+        }
+        let record = SessionRecord {
+            id: claims.id.clone(),
+            hash: squire::secure::calculate_hash(claims.key.clone()),
+            username: claims.username.clone(),
+            created_at: claims.issued_at,
+            last_seen: now,
+            expires_at: claims.issued_at + self.duration,
+            ip: ip.to_string(),
+            user_agent: user_agent.to_string(),
+        };
+        log::info!("Accepted the session '{}' of '{}', created by another instance", record.id, record.username);
+        mapping.insert(record.id.clone(), record.clone());
+        self.save(&mapping);
+        Ok(record)
+    }
+
+    /// Lists the sessions, with the most recently used first.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username whose sessions should be listed, or `None` to list the sessions of all users.
+    ///
+    /// # See Also
+    ///
+    /// Sessions created by another instance are only listed once they have been used with this one.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec` of `SessionRecord`.
+    pub fn list(&self, username: Option<&str>) -> Vec<SessionRecord> {
+        let mut records: Vec<SessionRecord> = self.mapping.lock().unwrap().values()
+            .filter(|record| username.map_or(true, |username| record.username == username))
+            .cloned()
+            .collect();
+        records.sort_by_key(|record| Reverse(record.last_seen));
+        records
+    }
+
+    /// Revokes a session, so the cookie that holds it is no longer accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the session.
+    ///
+    /// # Returns
+    ///
+    /// Returns the revoked `SessionRecord`, or `None` if the session doesn't exist.
+    pub fn revoke(&self, id: &str) -> Option<SessionRecord> {
+        let record = {
+            let mut mapping = self.mapping.lock().unwrap();
+            let record = mapping.remove(id)?;
+            self.save(&mapping);
+            record
+        };
+        // The cookie would be accepted again without the revocation, like a session created by another instance
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.sessions.insert(record.id.clone(), record.expires_at);
+        self.save_revoked(&mut revoked, Utc::now().timestamp());
+        Some(record)
+    }
+
+    /// Revokes all the sessions of a user, to log them out everywhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username whose sessions should be revoked.
+    ///
+    /// # See Also
+    ///
+    /// Every session of the user created until now is revoked, including the ones created by another instance.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of sessions that were revoked on this instance.
+    pub fn revoke_user(&self, username: &str) -> usize {
+        let revoked_count = {
+            let mut mapping = self.mapping.lock().unwrap();
+            let before = mapping.len();
+            mapping.retain(|_, record| record.username != username);
+            self.save(&mapping);
+            before - mapping.len()
+        };
+        let now = Utc::now().timestamp();
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.users.insert(username.to_string(), now);
+        self.save_revoked(&mut revoked, now);
+        revoked_count
+    }
 
-/// Instantiates the `Session` struct with empty `HashMap` for `mapping` fields.
+    /// Checks whether a session still exists and has not expired, for connections that outlive a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the session.
+    /// * `now` - Epoch timestamp (in seconds) of the check.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the session is still active.
+    pub fn is_active(&self, id: &str, now: i64) -> bool {
+        let record = match self.mapping.lock().unwrap().get(id) {
+            Some(record) if now < record.expires_at => record.clone(),
+            _ => return false,
+        };
+        !self.is_revoked(&record.id, &record.username, record.created_at)
+    }
+
+    /// Drops the sessions that have expired, along with the revocations that no longer apply.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Epoch timestamp (in seconds) of the sweep.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of sessions that were dropped.
+    pub fn sweep(&self, now: i64) -> usize {
+        self.revoked.lock().unwrap().prune(now, self.duration);
+        let mut mapping = self.mapping.lock().unwrap();
+        let before = mapping.len();
+        mapping.retain(|_, record| now < record.expires_at);
+        if mapping.len() != before {
+            self.save(&mapping);
+        }
+        before - mapping.len()
+    }
+}
+
+/// Instantiates the `Session` struct with the sessions persisted in `storage_dir`,
+/// and spawns a thread that sweeps the expired sessions.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
 ///
 /// ## See Also
 ///
-/// Creates new `Mutex` in an unlocked state for each of the fields.
+/// Sessions can only be decrypted after a restart, if the keys to encrypt the session tokens are persisted as well.
 ///
 /// # Returns
 ///
 /// Returns the constructed `Arc` for the `Session` struct.
-pub fn session_info() -> Arc<Session> {
-    Arc::new(Session {
-        mapping: Mutex::new(HashMap::new()),
-    })
+pub fn session_info(config: &squire::settings::Config) -> Arc<Session> {
+    let session = Arc::new(load_sessions(config.storage_dir.as_ref().map(PathBuf::from), config.session_duration));
+    let sweeper = session.clone();
+    thread::Builder::new()
+        .name("sessions".to_string())
+        .spawn(move || loop {
+            thread::sleep(SWEEP_INTERVAL);
+            let swept = sweeper.sweep(Utc::now().timestamp());
+            if swept > 0 {
+                log::debug!("Swept {} expired sessions", swept);
+            }
+        })
+        .expect("Failed to spawn the session sweeper thread");
+    session
+}
+
+/// Loads the sessions and the revoked sessions persisted in a directory.
+///
+/// # Arguments
+///
+/// * `directory` - Directory that the sessions are persisted to, if any.
+/// * `duration` - Time (in seconds) each session lasts from the time it was created.
+///
+/// # Returns
+///
+/// Returns the `Session` struct.
+fn load_sessions(directory: Option<PathBuf>, duration: i64) -> Session {
+    let path = directory.as_ref().map(|directory| directory.join(SESSIONS_FILE));
+    let revoked_path = directory.as_ref().map(|directory| directory.join(REVOKED_FILE));
+    let now = Utc::now().timestamp();
+    let mut mapping = HashMap::new();
+    if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+        match fs::read(path).map_err(|err| err.to_string())
+            .and_then(|content| serde_json::from_slice::<Vec<StoredSession>>(&content).map_err(|err| err.to_string())) {
+            Ok(sessions) => {
+                for StoredSession { mut record, hash } in sessions {
+                    if now < record.expires_at {
+                        record.hash = hash;
+                        mapping.insert(record.id.clone(), record);
+                    }
+                }
+                log::info!("Loaded {} sessions from '{}'", mapping.len(), path.display());
+            }
+            Err(err) => log::error!("Failed to load the sessions from '{}': {}", path.display(), err),
+        }
+    }
+    let mut revoked = Revoked::default();
+    if let Some(path) = revoked_path.as_ref().filter(|path| path.exists()) {
+        // Logging in with a revoked session is worse than not starting at all
+        match read_revoked(path) {
+            Ok(loaded) => revoked = loaded,
+            Err(err) => panic!("\nFailed to load the revoked sessions from '{}': {}\n", path.display(), err),
+        }
+        revoked.prune(now, duration);
+    }
+    Session {
+        mapping: Mutex::new(mapping),
+        revoked: Mutex::new(revoked),
+        duration,
+        path,
+        revoked_path,
+    }
+}
+
+/// Loads the keys to encrypt the session tokens, which are also the secret that the server is unique by.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # See Also
+///
+/// - The keys set in `secret_keys` or `secret_key_file` are used when available, where the first key encrypts
+///   the session tokens, and all the keys can decrypt them. So a key can be rotated by adding a new one first.
+/// - Otherwise, a key is generated and persisted in `storage_dir` on the first run, and re-used afterwards.
+/// - Without a `storage_dir`, a random key is generated on every start, which ends all the sessions on a restart.
+///
+/// # Returns
+///
+/// Returns a `Vec` of the configured or generated keys, with the key that encrypts the session tokens first.
+///
+/// # Panics
+///
+/// If the key file in `storage_dir` exists, but can't be read or holds an invalid key.
+pub fn secret_keys(config: &squire::settings::Config) -> Vec<String> {
+    // Configured keys are validated during startup
+    if !config.secret_keys.is_empty() {
+        return config.secret_keys.clone();
+    }
+    let directory = match &config.storage_dir {
+        Some(directory) => directory,
+        None => {
+            log::warn!("Using a random secret key, sessions won't survive a restart");
+            return vec![Fernet::generate_key()];
+        }
+    };
+    let path = PathBuf::from(directory).join(SECRET_KEY_FILE);
+    if path.exists() {
+        // Replacing the key would silently end every session, and change the salts of the login challenges
+        let keys = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .map(|content| squire::secure::parse_keys(&content))
+            .and_then(|keys| match keys.iter().position(|key| Fernet::new(key).is_none()) {
+                Some(index) => Err(format!("key #{} is not a valid Fernet key", index + 1)),
+                None if keys.is_empty() => Err("no key was found".to_string()),
+                None => Ok(keys),
+            });
+        return match keys {
+            Ok(keys) => keys,
+            Err(err) => panic!("\nFailed to load the secret key from '{}': {}\n", path.display(), err),
+        };
+    }
+    let key = Fernet::generate_key();
+    match squire::util::write_private(&path, format!("{}\n", key).as_bytes()) {
+        Ok(_) => log::info!("Generated a secret key at '{}'", path.display()),
+        Err(err) => log::error!("Failed to persist the secret key to '{}': {}", path.display(), err),
+    }
+    vec![key]
 }
 
-/// Create a [Fernet](https://docs.rs/fernet/latest/fernet/) object to encrypt and decrypt session token.
+/// Create a [MultiFernet](https://docs.rs/fernet/latest/fernet/struct.MultiFernet.html) object to encrypt and
+/// decrypt session token.
 ///
-/// Generates a random key, that can be safely passed to `Fernet::new()`
+/// # Arguments
+///
+/// * `keys` - Keys loaded with `secret_keys`, where the first key encrypts the session tokens.
 ///
 /// # Returns
 ///
-/// Returns the constructed `Arc` for the `Fernet` instance, with the generated key.
-pub fn fernet_object() -> Arc<Fernet> {
-    Arc::new(Fernet::new(&Fernet::generate_key()).unwrap())
+/// Returns the constructed `Arc` for the `MultiFernet` instance.
+pub fn fernet_object(keys: &[String]) -> Arc<MultiFernet> {
+    // Keys are validated when they are loaded
+    let fernets: Vec<Fernet> = keys.iter().filter_map(|key| Fernet::new(key)).collect();
+    Arc::new(MultiFernet::new(fernets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    const DURATION: i64 = 3600;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("sysmonk-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn claims(key: &str, record: &SessionRecord) -> Claims {
+        Claims {
+            id: record.id.clone(),
+            key: key.to_string(),
+            username: record.username.clone(),
+            issued_at: record.created_at,
+        }
+    }
+
+    #[test]
+    fn test_session_from_another_instance() {
+        let issuer = load_sessions(None, DURATION);
+        let other = load_sessions(None, DURATION);
+        let (key, record) = issuer.create("alice", "10.0.0.1", "firefox", 100, DURATION);
+        let adopted = other.touch(&claims(&key, &record), "10.0.0.2", "chrome", 200).unwrap();
+        assert_eq!((adopted.expires_at, adopted.last_seen), (100 + DURATION, 200));
+        assert_eq!(adopted.ip, "10.0.0.2");
+        assert!(other.is_active(&record.id, 200));
+        // Once adopted, the key is checked against the recorded hash
+        assert_eq!(other.touch(&claims("forged", &record), "", "", 300).err(), Some("Invalid session token"));
+        assert_eq!(other.touch(&claims(&key, &record), "", "", 100 + DURATION).err(), Some("Session Expired"));
+        let (key, record) = issuer.create("bob", "10.0.0.1", "firefox", 0, DURATION);
+        assert_eq!(other.touch(&claims(&key, &record), "", "", DURATION).err(), Some("Session Expired"));
+        assert!(other.list(Some("bob")).is_empty());
+    }
+
+    #[test]
+    fn test_revoked_across_instances() {
+        let directory = temp_dir("revoked");
+        let first = load_sessions(Some(directory.clone()), DURATION);
+        let second = load_sessions(Some(directory.clone()), DURATION);
+        let now = Utc::now().timestamp();
+        let (key, record) = first.create("alice", "10.0.0.1", "firefox", now, DURATION);
+        assert!(second.touch(&claims(&key, &record), "", "", now).is_ok());
+        assert!(first.revoke(&record.id).is_some());
+        assert_eq!(first.touch(&claims(&key, &record), "", "", now).err(), Some("Session has been revoked"));
+        assert!(!second.is_active(&record.id, now));
+        assert_eq!(second.touch(&claims(&key, &record), "", "", now).err(), Some("Session has been revoked"));
+        assert!(second.list(None).is_empty());
+        // Revocations survive a restart
+        let restarted = load_sessions(Some(directory.clone()), DURATION);
+        assert!(restarted.touch(&claims(&key, &record), "", "", now).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_revoke_user() {
+        let directory = temp_dir("revoke-user");
+        let first = load_sessions(Some(directory.clone()), DURATION);
+        let second = load_sessions(Some(directory.clone()), DURATION);
+        let now = Utc::now().timestamp();
+        let (old_key, old) = second.create("alice", "10.0.0.1", "firefox", now - 10, DURATION);
+        let (other_key, other) = second.create("bob", "10.0.0.1", "firefox", now - 10, DURATION);
+        // The session was never seen by the instance that logs the user out
+        assert_eq!(first.revoke_user("alice"), 0);
+        assert!(second.touch(&claims(&old_key, &old), "", "", now).is_err());
+        assert!(second.touch(&claims(&other_key, &other), "", "", now).is_ok());
+        // Logging in again after the logout is not affected
+        let (new_key, new) = first.create("alice", "10.0.0.1", "firefox", now + 5, DURATION);
+        assert!(second.touch(&claims(&new_key, &new), "", "", now + 5).is_ok());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_sweep_prunes_revocations() {
+        let session = load_sessions(None, DURATION);
+        let now = Utc::now().timestamp();
+        let (_, record) = session.create("alice", "10.0.0.1", "firefox", now, DURATION);
+        session.revoke(&record.id);
+        session.revoked.lock().unwrap().users.insert("bob".to_string(), now);
+        session.sweep(now + DURATION - 1);
+        assert_eq!(session.revoked.lock().unwrap().sessions.len(), 1);
+        session.sweep(now + DURATION + 1);
+        let revoked = session.revoked.lock().unwrap();
+        assert!(revoked.sessions.is_empty() && revoked.users.is_empty());
+    }
 }
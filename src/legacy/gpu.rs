@@ -108,7 +108,7 @@ fn get_gpu_info_linux(lib_path: &str) -> Vec<HashMap<String, String>> {
     let mut gpu_info = Vec::new();
     for line in output.lines() {
         if line.contains("VGA") {
-            let gpu = line.split(':').last().unwrap().trim();
+            let gpu = line.split(':').next_back().unwrap().trim();
             let mut info = HashMap::new();
             info.insert("model".to_string(), gpu.to_string());
             gpu_info.push(info);
@@ -20,6 +20,8 @@ mod templates;
 mod resources;
 /// Module for legacy (but still useful for reference) functions
 mod legacy;
+/// Module for the alerting engine that evaluates rules against the sampled metrics.
+mod alerting;
 
 /// Contains entrypoint and initializer settings to trigger the asynchronous `HTTPServer`
 ///
@@ -49,11 +51,24 @@ pub async fn start() -> io::Result<()> {
     // Create a dedicated clone, since it will be used within closure
     let config_clone = config.clone();
     let host = format!("{}:{}", config.host, config.port);
-    log::info!("{} [workers:{}] running on http://{} (Press CTRL+C to quit)",
-        &metadata.pkg_name, &config.workers, &host);
+    let certificates = squire::tls::load(&config);
+    let scheme = if certificates.is_some() { "https" } else { "http" };
+    log::info!("{} [workers:{}] running on {}://{} (Press CTRL+C to quit)",
+        &metadata.pkg_name, &config.workers, scheme, &host);
     let jinja = templates::environment();
-    let fernet = constant::fernet_object();
-    let session = constant::session_info();
+    let keys = constant::secret_keys(&config);
+    let fernet = constant::fernet_object(&keys);
+    let credentials = squire::credentials::load(&config, &keys);
+    let session = constant::session_info(&config);
+    let lockout = squire::lockout::tracker(&config);
+    let nonces = squire::nonces::store();
+    let access = squire::access::rules(&config);
+    let mfa = squire::mfa::load(&config, fernet.clone());
+    let tokens = squire::tokens::load(&config);
+    let history = resources::history::store(&config);
+    let collector = resources::collector::start(config.clone(), history.clone());
+    let silences = alerting::silences::load(&config);
+    let alerts = alerting::engine::start(&config, collector.clone(), silences.clone());
     /*
         || syntax is creating a closure that serves as the argument to the HttpServer::new() method.
         The closure is defining the configuration for the Actix web server.
@@ -65,26 +80,95 @@ pub async fn start() -> io::Result<()> {
             .app_data(web::Data::new(jinja.clone()))
             .app_data(web::Data::new(fernet.clone()))
             .app_data(web::Data::new(session.clone()))
+            .app_data(web::Data::new(lockout.clone()))
+            .app_data(web::Data::new(credentials.clone()))
+            .app_data(web::Data::new(nonces.clone()))
+            .app_data(web::Data::new(mfa.clone()))
+            .app_data(web::Data::new(tokens.clone()))
+            .app_data(web::Data::new(access.clone()))
             .app_data(web::Data::new(metadata.clone()))
+            .app_data(web::Data::new(collector.clone()))
+            .app_data(web::Data::new(history.clone()))
+            .app_data(web::Data::new(alerts.clone()))
+            .app_data(web::Data::new(silences.clone()))
             .wrap(squire::middleware::get_cors(config_clone.websites.clone()))
+            .wrap(middleware::from_fn(squire::middleware::security_headers))
+            .wrap(middleware::from_fn(squire::middleware::access_control))
             .wrap(middleware::Logger::default())  // Adds a default logger middleware to the application
             .service(routes::basics::health)  // Registers a service for handling requests
             .service(routes::basics::root)
+            .service(routes::basics::argon2)
             .service(routes::basics::health)  // Registers a service for handling requests
+            .service(routes::auth::challenge)
             .service(routes::auth::login)
+            .service(routes::mfa::login)
             .service(routes::monitor::monitor)
             .service(routes::auth::logout)
             .service(routes::auth::error)
+            .service(routes::sse::system)
+            .service(routes::history::history)
+            .service(routes::metrics::metrics)
+            .service(routes::alerts::alerts)
+            .service(routes::silences::list)
+            .service(routes::silences::create)
+            .service(routes::silences::remove)
+            .service(routes::sessions::list)
+            .service(routes::sessions::revoke)
+            .service(routes::lockouts::list)
+            .service(routes::lockouts::unlock)
+            .service(routes::mfa::status)
+            .service(routes::mfa::enroll)
+            .service(routes::mfa::confirm)
+            .service(routes::mfa::disable)
+            .service(routes::mfa::reset)
+            .service(routes::tokens::list)
+            .service(routes::tokens::create)
+            .service(routes::tokens::revoke)
             .configure(routes::configure_websocket)
+            .configure(routes::configure_api)
     };
     let server = HttpServer::new(application)
         .workers(config.workers)
         .max_connections(config.max_connections);
-    match server.bind(host) {
-        Ok(bound_server) => bound_server.run().await,
+    let bound = match &certificates {
+        Some(certificates) => {
+            let acceptor = certificates.acceptor()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            squire::tls::reload_on_hangup(certificates.clone());
+            server.bind_openssl(host, acceptor)
+        }
+        None => server.bind(host)
+    };
+    let bound_server = match bound {
+        Ok(bound_server) => bound_server,
         Err(err) => {
             log::error!("Failed to bind server: {}", err);
-            Err(err)
+            return Err(err);
+        }
+    };
+    if let Some(redirect_port) = config.redirect_port {
+        let redirect_host = format!("{}:{}", config.host, redirect_port);
+        let redirect_config = config.clone();
+        let redirect_access = squire::access::rules(&config);
+        let redirect_server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(redirect_config.clone()))
+                .app_data(web::Data::new(redirect_access.clone()))
+                .wrap(middleware::from_fn(squire::middleware::access_control))
+                .default_service(web::to(routes::basics::https_redirect))
+        })
+            .workers(1)
+            .bind(&redirect_host);
+        match redirect_server {
+            Ok(redirect_server) => {
+                log::info!("Redirecting http://{} to HTTPS", &redirect_host);
+                actix_web::rt::spawn(redirect_server.run());
+            }
+            Err(err) => {
+                log::error!("Failed to bind the redirect listener: {}", err);
+                return Err(err);
+            }
         }
     }
+    bound_server.run().await
 }
@@ -0,0 +1,422 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sysinfo::System;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::{resources, squire};
+
+/// Interval between two consecutive samples taken by the collector.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum interval (in seconds) a subscriber can request between two frames.
+pub const MAX_INTERVAL: u64 = 300;
+
+/// Number of frames that can be queued for a subscriber, before newer frames are dropped.
+const QUEUE_SIZE: usize = 4;
+
+/// Number of recent samples retained to replay for subscribers that resume a stream.
+const REPLAY_SIZE: usize = 60;
+
+/// Enum for the topics a subscriber can choose to receive.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topic {
+    Cpu,
+    Memory,
+    Disks,
+    #[serde(rename = "disk_io")]
+    DiskIo,
+    Network,
+    Docker,
+    Services,
+    Processes,
+}
+
+impl Topic {
+    /// Returns all the available topics.
+    pub fn all() -> Vec<Topic> {
+        vec![
+            Topic::Cpu, Topic::Memory, Topic::Disks, Topic::DiskIo, Topic::Network,
+            Topic::Docker, Topic::Services, Topic::Processes
+        ]
+    }
+
+    /// Returns the minimum role required to subscribe to the topic.
+    ///
+    /// # See Also
+    ///
+    /// Docker, services and processes reveal what runs on the server, so they are limited to operators.
+    pub fn role(&self) -> squire::settings::Role {
+        match self {
+            Topic::Docker | Topic::Services | Topic::Processes => squire::settings::Role::Operator,
+            _ => squire::settings::Role::Viewer,
+        }
+    }
+
+    /// Returns the topics that a role is allowed to subscribe to.
+    pub fn allowed(role: squire::settings::Role) -> Vec<Topic> {
+        Topic::all().into_iter().filter(|topic| topic.role() <= role).collect()
+    }
+
+    /// Returns the topics that a role is not allowed to subscribe to.
+    pub fn denied(role: squire::settings::Role) -> Vec<Topic> {
+        Topic::all().into_iter().filter(|topic| topic.role() > role).collect()
+    }
+
+    /// Returns the topics that are sampled at all times, since they are cheap and retained in history.
+    fn recorded() -> Vec<Topic> {
+        vec![Topic::Cpu, Topic::Memory, Topic::Disks, Topic::DiskIo, Topic::Network]
+    }
+
+    /// Returns the keys in the frame that belong to the topic.
+    pub fn keys(&self) -> &'static [&'static str] {
+        match self {
+            Topic::Cpu => &["cpu_usage", "load_averages"],
+            Topic::Memory => &["memory_info", "swap_info"],
+            Topic::Disks => &["disk_info", "disk_usage"],
+            Topic::DiskIo => &["disk_io"],
+            Topic::Network => &["network_stats"],
+            Topic::Docker => &["docker_stats"],
+            Topic::Services => &["service_stats"],
+            Topic::Processes => &["process_stats"],
+        }
+    }
+}
+
+/// Type alias for the metrics gathered in a single sample, keyed by their name in the frame.
+pub type Sample = HashMap<String, serde_json::Value>;
+
+/// Parses a number from a value in the sample, that may be formatted as a string with a trailing percent sign.
+pub fn number(value: &serde_json::Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str().and_then(|text| text.trim().trim_end_matches('%').parse().ok()))
+}
+
+/// Computes the usage in percent, from an object in the sample with `total` and `used` fields.
+pub fn percent(info: &serde_json::Value) -> Option<f64> {
+    let total = info.get("total").and_then(number)?;
+    let used = info.get("used").and_then(number)?;
+    (total > 0.0).then(|| used / total * 100.0)
+}
+
+/// Struct for a serialized frame along with the sequence number of the sample it was built from.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub seq: u64,
+    pub payload: Arc<String>,
+}
+
+/// Struct to store the preferences and the sending half of the channel for each subscriber.
+struct Subscription {
+    sender: mpsc::Sender<Frame>,
+    topics: HashSet<Topic>,
+    interval: Duration,
+    paused: bool,
+    next_due: Instant,
+}
+
+/// Struct for a snapshot of a subscription's preferences, that is sent back to the client.
+#[derive(Serialize, Debug)]
+pub struct SubscriptionState {
+    pub topics: Vec<Topic>,
+    pub interval: u64,
+    pub paused: bool,
+}
+
+/// Struct for the shared background collector, that samples system resources and fans them out to subscribers.
+///
+/// ## Fields
+///
+/// * `subscribers` - Used to store the subscriber ID and its subscription as key value pairs.
+/// * `next_id` - Counter used to assign a unique ID to every new subscriber.
+/// * `seq` - Sequence number of the latest sample.
+/// * `recent` - Recent samples along with their sequence numbers, used to replay missed frames.
+/// * `history` - Time series store that retains the metrics from every sample.
+pub struct Collector {
+    subscribers: Mutex<HashMap<u64, Subscription>>,
+    next_id: AtomicU64,
+    seq: AtomicU64,
+    recent: Mutex<VecDeque<(u64, Arc<Sample>)>>,
+    history: Arc<resources::history::History>,
+}
+
+/// Serializes the keys in a sample that belong to the given topics.
+///
+/// # Arguments
+///
+/// * `system_resources` - Sampled frame containing the topics requested by the subscribers.
+/// * `topics` - Topics that the subscriber is interested in.
+///
+/// # Returns
+///
+/// Returns the serialized frame as a `String`.
+fn serialize(system_resources: &Sample, topics: &BTreeSet<Topic>) -> String {
+    let filtered: HashMap<&String, &serde_json::Value> = system_resources
+        .iter()
+        .filter(|(key, _)| topics.iter().any(|topic| topic.keys().contains(&key.as_str())))
+        .collect();
+    serde_json::to_string(&filtered).unwrap()
+}
+
+/// Struct for an active subscription to the collector.
+///
+/// ## See Also
+///
+/// The subscription is removed from the collector when this object is dropped.
+pub struct Subscriber {
+    id: u64,
+    collector: Arc<Collector>,
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.collector.subscribers.lock().unwrap().remove(&self.id);
+        log::debug!("Subscriber {} has been removed from the collector", self.id);
+    }
+}
+
+impl Subscriber {
+    /// Applies a change to the subscription and returns its latest state.
+    fn modify<F: FnOnce(&mut Subscription)>(&self, change: F) -> SubscriptionState {
+        let mut subscribers = self.collector.subscribers.lock().unwrap();
+        let subscription = subscribers.get_mut(&self.id).unwrap();
+        change(subscription);
+        let mut topics: Vec<Topic> = subscription.topics.iter().copied().collect();
+        topics.sort();
+        SubscriptionState {
+            topics,
+            interval: subscription.interval.as_secs(),
+            paused: subscription.paused,
+        }
+    }
+
+    /// Adds the topics to the subscription.
+    pub fn add_topics(&self, topics: &[Topic]) -> SubscriptionState {
+        self.modify(|subscription| subscription.topics.extend(topics))
+    }
+
+    /// Removes the topics from the subscription.
+    pub fn remove_topics(&self, topics: &[Topic]) -> SubscriptionState {
+        self.modify(|subscription| subscription.topics.retain(|topic| !topics.contains(topic)))
+    }
+
+    /// Sets the interval between two frames for the subscription.
+    pub fn set_interval(&self, interval: Duration) -> SubscriptionState {
+        self.modify(|subscription| {
+            subscription.interval = interval;
+            subscription.next_due = Instant::now();
+        })
+    }
+
+    /// Pauses or resumes the frames for the subscription.
+    pub fn set_paused(&self, paused: bool) -> SubscriptionState {
+        self.modify(|subscription| {
+            subscription.paused = paused;
+            subscription.next_due = Instant::now();
+        })
+    }
+}
+
+impl Collector {
+    /// Registers a new subscriber, that will receive all the topics at every sample by default.
+    ///
+    /// # Returns
+    ///
+    /// Returns a tuple of the `Subscriber` object and the receiving half of its channel.
+    pub fn subscribe(self: &Arc<Self>) -> (Subscriber, mpsc::Receiver<Frame>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        let subscription = Subscription {
+            sender,
+            topics: HashSet::from_iter(Topic::all()),
+            interval: SAMPLE_INTERVAL,
+            paused: false,
+            next_due: Instant::now(),
+        };
+        self.subscribers.lock().unwrap().insert(id, subscription);
+        log::debug!("Subscriber {} has been added to the collector", id);
+        let subscriber = Subscriber {
+            id,
+            collector: self.clone(),
+        };
+        (subscriber, receiver)
+    }
+
+    /// Waits for a single sample of the topics, through a temporary subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `topics` - Topics to include in the sample.
+    /// * `timeout` - Maximum time to wait for the collector.
+    ///
+    /// # See Also
+    ///
+    /// Expensive topics like docker, services and processes are only sampled on demand,
+    /// so they are sampled along with the next iteration of the collector.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` with the sample, or `None` when the collector didn't respond in time.
+    pub async fn sample(self: &Arc<Self>, topics: &[Topic], timeout: Duration) -> Option<Sample> {
+        let (subscriber, mut receiver) = self.subscribe();
+        let excluded: Vec<Topic> = Topic::all().into_iter().filter(|topic| !topics.contains(topic)).collect();
+        subscriber.remove_topics(&excluded);
+        let frame = actix_web::rt::time::timeout(timeout, receiver.recv()).await.ok()??;
+        serde_json::from_str(&frame.payload).ok()
+    }
+
+    /// Retrieves the recent samples that were taken after the given sequence number.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - Sequence number of the last frame received by the subscriber.
+    /// * `topics` - Topics to include in the frames.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec` of frames, that is empty if the sequence number is unknown to the collector.
+    pub fn replay(&self, seq: u64, topics: &[Topic]) -> Vec<Frame> {
+        let topics = BTreeSet::from_iter(topics.iter().copied());
+        self.recent.lock().unwrap()
+            .iter()
+            .filter(|(sample_seq, _)| *sample_seq > seq)
+            .map(|(sample_seq, system_resources)| Frame {
+                seq: *sample_seq,
+                payload: Arc::new(serialize(system_resources, &topics)),
+            })
+            .collect()
+    }
+
+    /// Stores the sample to replay it for subscribers that resume a stream.
+    ///
+    /// # Returns
+    ///
+    /// Returns the sequence number assigned to the sample.
+    fn retain(&self, system_resources: &Arc<Sample>) -> u64 {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == REPLAY_SIZE {
+            recent.pop_front();
+        }
+        recent.push_back((seq, system_resources.clone()));
+        seq
+    }
+
+    /// Gathers the union of topics for all the subscribers that are due for a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Time at which the current sample was started.
+    fn due_topics(&self, now: Instant) -> HashSet<Topic> {
+        let mut topics = HashSet::new();
+        for subscription in self.subscribers.lock().unwrap().values() {
+            if !subscription.paused && subscription.next_due <= now {
+                topics.extend(&subscription.topics);
+            }
+        }
+        topics
+    }
+
+    /// Sends the frame to all the subscribers that are due, without blocking the collector.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - Sequence number of the sample.
+    /// * `system_resources` - Sampled frame containing the topics requested by the subscribers.
+    /// * `now` - Time at which the current sample was started.
+    ///
+    /// # See Also
+    ///
+    /// Subscribers with the same set of topics share the same serialized frame.
+    fn broadcast(&self, seq: u64, system_resources: &Sample, now: Instant) {
+        let mut serialized: HashMap<BTreeSet<Topic>, Arc<String>> = HashMap::new();
+        for (id, subscription) in self.subscribers.lock().unwrap().iter_mut() {
+            if subscription.paused || subscription.next_due > now || subscription.topics.is_empty() {
+                continue;
+            }
+            subscription.next_due = now + subscription.interval;
+            let topics = BTreeSet::from_iter(subscription.topics.iter().copied());
+            let payload = serialized.entry(topics).or_insert_with_key(|topics| {
+                Arc::new(serialize(system_resources, topics))
+            });
+            let frame = Frame { seq, payload: payload.clone() };
+            if let Err(TrySendError::Full(_)) = subscription.sender.try_send(frame) {
+                log::debug!("Subscriber {} is lagging behind, dropping frame", id);
+            }
+        }
+    }
+
+    /// Samples system resources through a loop, using a single persistent `System` object.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration data for the application.
+    fn run(&self, config: &squire::settings::Config) {
+        let mut system = System::new_all();
+        // CPU usage is calculated as the difference between two refreshes
+        thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        let mut usage_sampler = resources::disks::UsageSampler::new(config);
+        let mut network_sampler = resources::network::NetworkSampler::new();
+        let mut disk_sampler = resources::disks::DiskSampler::new();
+        loop {
+            let started = Instant::now();
+            // Keep the CPU usage fresh, so that it's always an average since the previous sample
+            system.refresh_cpu_usage();
+            let mut topics = self.due_topics(started);
+            // Cheap topics are always sampled, so that the history has no gaps when nobody is watching
+            topics.extend(Topic::recorded());
+            let mut system_resources = resources::stream::system_resources(&mut system, config, &topics);
+            if topics.contains(&Topic::Disks) {
+                let disk_usage = usage_sampler.sample();
+                system_resources.insert("disk_info".to_string(), resources::disks::aggregate(&disk_usage));
+                system_resources.insert("disk_usage".to_string(), serde_json::json!(disk_usage));
+            }
+            if topics.contains(&Topic::DiskIo) {
+                let disk_io = disk_sampler.sample();
+                system_resources.insert("disk_io".to_string(), serde_json::json!(disk_io));
+            }
+            if topics.contains(&Topic::Network) {
+                let network_stats = network_sampler.sample();
+                system_resources.insert("network_stats".to_string(), serde_json::json!(network_stats));
+            }
+            self.history.record(Utc::now().timestamp(), &system_resources);
+            let system_resources = Arc::new(system_resources);
+            let seq = self.retain(&system_resources);
+            self.broadcast(seq, &system_resources, started);
+            thread::sleep(SAMPLE_INTERVAL.saturating_sub(started.elapsed()));
+        }
+    }
+}
+
+/// Spawns the collector in a dedicated thread, since sampling involves blocking calls.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `history` - Time series store that is fed with every sample.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Collector` struct.
+pub fn start(config: Arc<squire::settings::Config>, history: Arc<resources::history::History>) -> Arc<Collector> {
+    let collector = Arc::new(Collector {
+        subscribers: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1),
+        // Start from the current time in milliseconds, so that sequence numbers keep increasing across restarts
+        seq: AtomicU64::new(Utc::now().timestamp_millis() as u64),
+        recent: Mutex::new(VecDeque::with_capacity(REPLAY_SIZE)),
+        history,
+    });
+    let worker = collector.clone();
+    thread::Builder::new()
+        .name("collector".to_string())
+        .spawn(move || worker.run(&config))
+        .expect("Failed to spawn the collector thread");
+    collector
+}
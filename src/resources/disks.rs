@@ -0,0 +1,318 @@
+use crate::squire;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use sysinfo::Disks;
+
+/// Size of a sector in bytes, as reported by `/proc/diskstats` irrespective of the device's sector size.
+const SECTOR_SIZE: u64 = 512;
+
+/// Struct to hold the cumulative counters of a block device.
+#[derive(Clone, Debug, Default)]
+struct DeviceCounters {
+    reads: u64,
+    writes: u64,
+    read_sectors: u64,
+    write_sectors: u64,
+    read_ms: u64,
+    write_ms: u64,
+    io_ms: u64,
+}
+
+/// Struct to hold the I/O statistics of a block device, that is streamed via websockets.
+///
+/// ## Fields
+///
+/// * `name` - Name of the block device.
+/// * `read_bytes` - Bytes read per second.
+/// * `write_bytes` - Bytes written per second.
+/// * `read_iops` - Read operations completed per second.
+/// * `write_iops` - Write operations completed per second.
+/// * `await_ms` - Average time (in milliseconds) taken to serve an I/O request, including the time in queue.
+/// * `utilization` - Percentage of time the device was busy serving I/O requests.
+#[derive(Serialize, Debug)]
+pub struct DeviceStats {
+    pub name: String,
+    pub read_bytes: f64,
+    pub write_bytes: f64,
+    pub read_iops: f64,
+    pub write_iops: f64,
+    pub await_ms: f64,
+    pub utilization: f64,
+}
+
+impl DeviceStats {
+    /// Computes the I/O statistics between two set of counters.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the block device.
+    /// * `previous` - Counters from the previous sample.
+    /// * `current` - Counters from the current sample.
+    /// * `elapsed` - Seconds elapsed between the two samples.
+    fn between(name: &str, previous: &DeviceCounters, current: &DeviceCounters, elapsed: f64) -> DeviceStats {
+        // Counters wrap around on 32-bit kernels, so saturate instead of reporting a negative rate
+        let delta = |prev: u64, curr: u64| curr.saturating_sub(prev);
+        let reads = delta(previous.reads, current.reads);
+        let writes = delta(previous.writes, current.writes);
+        let wait_ms = delta(previous.read_ms, current.read_ms) + delta(previous.write_ms, current.write_ms);
+        let await_ms = if reads + writes > 0 {
+            wait_ms as f64 / (reads + writes) as f64
+        } else {
+            0.0
+        };
+        let utilization = delta(previous.io_ms, current.io_ms) as f64 / (elapsed * 1000.0) * 100.0;
+        DeviceStats {
+            name: name.to_string(),
+            read_bytes: (delta(previous.read_sectors, current.read_sectors) * SECTOR_SIZE) as f64 / elapsed,
+            write_bytes: (delta(previous.write_sectors, current.write_sectors) * SECTOR_SIZE) as f64 / elapsed,
+            read_iops: reads as f64 / elapsed,
+            write_iops: writes as f64 / elapsed,
+            await_ms,
+            utilization: utilization.min(100.0),
+        }
+    }
+}
+
+/// Function to check if a block device is a whole disk, that is worth reporting.
+///
+/// # See Also
+///
+/// Partitions are not listed under `/sys/block`, and loop, ram and zram devices are excluded.
+///
+/// # Arguments
+///
+/// * `name` - Name of the block device.
+///
+/// # Returns
+///
+/// A boolean value to indicate whether the device should be reported.
+fn is_physical_device(name: &str) -> bool {
+    let virtual_prefixes = ["loop", "ram", "zram"];
+    if virtual_prefixes.iter().any(|prefix| name.starts_with(prefix)) {
+        return false;
+    }
+    Path::new("/sys/block").join(name).exists()
+}
+
+/// Function to get the cumulative counters of all block devices on Linux.
+///
+/// # Arguments
+///
+/// * `lib_path` - The path to the file that contains the block device statistics.
+///
+/// # Returns
+///
+/// A `HashMap` of device name and its counters.
+fn linux_counters(lib_path: &str) -> HashMap<String, DeviceCounters> {
+    let content = match fs::read_to_string(lib_path) {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("Failed to read '{}': {}", lib_path, err);
+            return HashMap::new();
+        }
+    };
+    let mut counters = HashMap::new();
+    for line in content.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        // Major number, minor number, device name followed by at least 11 fields
+        if columns.len() < 14 || !is_physical_device(columns[2]) {
+            continue;
+        }
+        let fields: Vec<u64> = columns[3..]
+            .iter()
+            .map(|value| value.parse().unwrap_or_default())
+            .collect();
+        counters.insert(columns[2].to_string(), DeviceCounters {
+            reads: fields[0],
+            read_sectors: fields[2],
+            read_ms: fields[3],
+            writes: fields[4],
+            write_sectors: fields[6],
+            write_ms: fields[7],
+            io_ms: fields[9],
+        });
+    }
+    counters
+}
+
+/// Struct to track block device counters between samples, to compute throughput and latency.
+pub struct DiskSampler {
+    previous: HashMap<String, DeviceCounters>,
+    sampled_at: Instant,
+}
+
+impl DiskSampler {
+    /// Creates a new sampler with the current counters as the baseline.
+    pub fn new() -> DiskSampler {
+        if std::env::consts::OS != "linux" {
+            log::warn!("Disk I/O statistics are only supported on Linux");
+        }
+        DiskSampler {
+            previous: DiskSampler::counters(),
+            sampled_at: Instant::now(),
+        }
+    }
+
+    /// Function to get the cumulative counters of all block devices, which is only supported on Linux.
+    fn counters() -> HashMap<String, DeviceCounters> {
+        match std::env::consts::OS {
+            "linux" => linux_counters("/proc/diskstats"),
+            _ => HashMap::new(),
+        }
+    }
+
+    /// Samples the block devices and computes the statistics since the previous sample.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `DeviceStats` sorted by device name.
+    pub fn sample(&mut self) -> Vec<DeviceStats> {
+        let current = DiskSampler::counters();
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.sampled_at).as_secs_f64().max(f64::EPSILON);
+        let mut stats: Vec<DeviceStats> = current.iter()
+            .map(|(name, counters)| {
+                let previous = self.previous.get(name).unwrap_or(counters);
+                DeviceStats::between(name, previous, counters, elapsed)
+            })
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        self.previous = current;
+        self.sampled_at = now;
+        stats
+    }
+}
+
+/// Struct to hold the usage of a mounted filesystem, that is streamed via websockets.
+///
+/// ## Fields
+///
+/// * `mount_point` - Path where the filesystem is mounted.
+/// * `file_system` - Type of the filesystem.
+/// * `device` - Name of the device that backs the filesystem.
+/// * `total` - Total space in bytes.
+/// * `used` - Used space in bytes.
+#[derive(Serialize, Debug)]
+pub struct MountUsage {
+    pub mount_point: String,
+    pub file_system: String,
+    pub device: String,
+    pub total: u64,
+    pub used: u64,
+}
+
+/// Struct to filter the mounted filesystems by their filesystem type and mount point.
+///
+/// ## See Also
+///
+/// A mount is reported when it matches any of the include patterns (or when there are none),
+/// and none of the exclude patterns.
+pub struct MountFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl MountFilter {
+    /// Creates a new filter from the patterns, which are validated during startup.
+    ///
+    /// # Arguments
+    ///
+    /// * `include` - Patterns for the mounts to include.
+    /// * `exclude` - Patterns for the mounts to exclude.
+    pub fn new(include: &[String], exclude: &[String]) -> MountFilter {
+        let compile = |patterns: &[String]| -> Vec<Regex> {
+            patterns.iter()
+                .map(|pattern| Regex::new(pattern).expect("Disk filter patterns are validated during startup"))
+                .collect()
+        };
+        MountFilter {
+            include: compile(include),
+            exclude: compile(exclude),
+        }
+    }
+
+    /// Checks whether a mount should be reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_system` - Type of the filesystem.
+    /// * `mount_point` - Path where the filesystem is mounted.
+    ///
+    /// # Returns
+    ///
+    /// A boolean value to indicate whether the mount is allowed by the filter.
+    pub fn allows(&self, file_system: &str, mount_point: &str) -> bool {
+        let matches = |pattern: &Regex| pattern.is_match(file_system) || pattern.is_match(mount_point);
+        (self.include.is_empty() || self.include.iter().any(matches)) && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Struct to sample the usage of mounted filesystems, refreshing the list of mounts every time.
+pub struct UsageSampler {
+    disks: Disks,
+    filter: MountFilter,
+}
+
+impl UsageSampler {
+    /// Creates a new sampler with the mount filters from the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration data for the application.
+    pub fn new(config: &squire::settings::Config) -> UsageSampler {
+        UsageSampler {
+            disks: Disks::new(),
+            filter: MountFilter::new(&config.disk_include, &config.disk_exclude),
+        }
+    }
+
+    /// Samples the usage of all the mounts that are allowed by the filter.
+    ///
+    /// # See Also
+    ///
+    /// Bind mounts of the same device are reported only once, using the shortest mount point.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `MountUsage` sorted by mount point.
+    pub fn sample(&mut self) -> Vec<MountUsage> {
+        // Refreshing the list picks up the mounts that were added or removed since the previous sample
+        self.disks.refresh_list();
+        let mut mounts: Vec<MountUsage> = self.disks.list().iter()
+            .map(|disk| MountUsage {
+                mount_point: disk.mount_point().to_string_lossy().to_string(),
+                file_system: disk.file_system().to_string_lossy().to_string(),
+                device: disk.name().to_string_lossy().to_string(),
+                total: disk.total_space(),
+                used: disk.total_space().saturating_sub(disk.available_space()),
+            })
+            .filter(|mount| self.filter.allows(&mount.file_system, &mount.mount_point))
+            .collect();
+        mounts.sort_by_key(|mount| mount.mount_point.len());
+        let mut devices = HashSet::new();
+        // Pseudo filesystems don't have a device path, so they can't be identified as duplicates
+        mounts.retain(|mount| !mount.device.starts_with('/') || devices.insert(mount.device.clone()));
+        mounts.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        mounts
+    }
+}
+
+/// Function to get the aggregate disk usage across the mounts.
+///
+/// # Arguments
+///
+/// * `mounts` - Usage of the mounted filesystems.
+///
+/// # Returns
+///
+/// A `serde_json::Value` with the total and used space in bytes.
+pub fn aggregate(mounts: &[MountUsage]) -> serde_json::Value {
+    serde_json::json!({
+        "total": mounts.iter().map(|mount| mount.total).sum::<u64>(),
+        "used": mounts.iter().map(|mount| mount.used).sum::<u64>(),
+    })
+}
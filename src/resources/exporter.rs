@@ -0,0 +1,417 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::resources::collector::{number, Sample};
+use crate::squire::lockout::Stats;
+
+/// Type alias for the labels of a sample, as a list of label names and their values.
+type Labels = Vec<(&'static str, String)>;
+
+/// Struct to build the Prometheus text exposition format, one metric family at a time.
+///
+/// # References
+///
+/// * [Exposition formats](https://prometheus.io/docs/instrumenting/exposition_formats/)
+struct Exposition {
+    output: String,
+}
+
+impl Exposition {
+    /// Writes a metric family along with its help text and type, skipping the family when there are no samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the metric.
+    /// * `kind` - Type of the metric, either `gauge` or `counter`.
+    /// * `help` - Description of the metric.
+    /// * `samples` - Labels and values of the samples.
+    fn family(&mut self, name: &str, kind: &str, help: &str, samples: Vec<(Labels, f64)>) {
+        if samples.is_empty() {
+            return;
+        }
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            if labels.is_empty() {
+                let _ = writeln!(self.output, "{} {}", name, value);
+                continue;
+            }
+            let labels: Vec<String> = labels.iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+                .collect();
+            let _ = writeln!(self.output, "{}{{{}}} {}", name, labels.join(","), value);
+        }
+    }
+}
+
+/// Escapes a label value, as required by the exposition format.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Parses a human-readable size reported by `docker stats`, like `1.5MiB` or `12kB`.
+///
+/// # Returns
+///
+/// Returns an `Option` containing the size in bytes.
+fn docker_size(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let split = text.find(|char: char| char.is_ascii_alphabetic())?;
+    let (value, unit) = text.split_at(split);
+    let multiplier = match unit {
+        "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024f64.powi(2),
+        "GiB" => 1024f64.powi(3),
+        "TiB" => 1024f64.powi(4),
+        _ => return None,
+    };
+    value.trim().parse::<f64>().ok().map(|value| value * multiplier)
+}
+
+/// Parses a pair of sizes reported by `docker stats`, like `1.5MiB / 2GiB`.
+fn docker_pair(value: &serde_json::Value) -> Option<(f64, f64)> {
+    let (first, second) = value.as_str()?.split_once('/')?;
+    Some((docker_size(first)?, docker_size(second)?))
+}
+
+/// Builds the samples of a numeric field, for each item in a list within the sample.
+///
+/// # Arguments
+///
+/// * `sample` - Sample taken by the collector.
+/// * `key` - Key of the list in the sample.
+/// * `labels` - Function to build the labels of an item.
+/// * `value` - Function to extract the value from an item.
+fn samples_of<L, V>(sample: &Sample, key: &str, labels: L, value: V) -> Vec<(Labels, f64)>
+where
+    L: Fn(&serde_json::Value) -> Labels,
+    V: Fn(&serde_json::Value) -> Option<f64>,
+{
+    sample.get(key)
+        .and_then(|items| items.as_array())
+        .map(|items| {
+            items.iter()
+                .filter_map(|item| value(item).map(|value| (labels(item), value)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Struct for the rows of the services or the processes that share a name, which are exported as one.
+///
+/// ## Fields
+///
+/// * `count` - Number of rows with the name.
+/// * `pid` - First non-zero PID among the rows, or `0` when none of them is running.
+/// * `values` - Raw values added up across the rows that are running, with the longest uptime.
+#[derive(Default)]
+struct Usage {
+    count: usize,
+    pid: f64,
+    values: Option<HashMap<&'static str, f64>>,
+}
+
+/// Raw values of a service or a process row that are exported.
+const USAGE_FIELDS: [&str; 5] = ["cpu_percent", "memory_bytes", "uptime_seconds", "read_bytes", "written_bytes"];
+
+/// Groups the rows of the services or the processes by their name.
+///
+/// # Arguments
+///
+/// * `sample` - Sample taken by the collector.
+/// * `key` - Key of the rows in the sample.
+///
+/// # See Also
+///
+/// A name is matched by every process that contains it, so several rows can share the name of a process.
+/// The rows are labeled by the name alone, so that a restart doesn't leave a stale series behind for the old PID.
+///
+/// # Returns
+///
+/// Returns a `BTreeMap` of the names and their `Usage`.
+fn usages(sample: &Sample, key: &str) -> BTreeMap<String, Usage> {
+    let mut usages: BTreeMap<String, Usage> = BTreeMap::new();
+    let rows = sample.get(key).and_then(|rows| rows.as_array()).into_iter().flatten();
+    for row in rows {
+        let usage = usages.entry(text(row, "name")).or_default();
+        usage.count += 1;
+        if usage.pid == 0.0 {
+            usage.pid = number(&row["pid"]).unwrap_or_default();
+        }
+        if !row["values"].is_object() {
+            continue;
+        }
+        let values = usage.values.get_or_insert_with(HashMap::new);
+        for field in USAGE_FIELDS {
+            if let Some(value) = row["values"].get(field).and_then(number) {
+                let total = values.entry(field).or_default();
+                *total = if field == "uptime_seconds" { total.max(value) } else { *total + value };
+            }
+        }
+    }
+    usages
+}
+
+/// Returns the string value of a field in a JSON object, or an empty string if missing.
+fn text(item: &serde_json::Value, key: &str) -> String {
+    match &item[key] {
+        serde_json::Value::String(value) => value.to_string(),
+        serde_json::Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Renders a sample with all the topics, in the Prometheus text exposition format.
+///
+/// # Arguments
+///
+/// * `sample` - Sample taken by the collector, containing all the topics.
+///
+/// # Returns
+///
+/// Returns the exposition as a `String`.
+pub fn render(sample: &Sample) -> String {
+    let mut exposition = Exposition { output: String::new() };
+
+    let cpu_usage = samples_of(sample, "cpu_usage", |_| vec![], number)
+        .into_iter()
+        .enumerate()
+        .map(|(core, (_, value))| (vec![("core", core.to_string())], value))
+        .collect();
+    exposition.family("sysmonk_cpu_usage_percent", "gauge", "CPU usage per core in percent.", cpu_usage);
+
+    let load_averages = [("m1", "1m"), ("m5", "5m"), ("m15", "15m")].iter()
+        .filter_map(|(key, period)| {
+            sample.get("load_averages")
+                .and_then(|load| load.get(*key))
+                .and_then(number)
+                .map(|value| (vec![("period", period.to_string())], value))
+        })
+        .collect();
+    exposition.family("sysmonk_load_average", "gauge", "CPU load average.", load_averages);
+
+    for (key, metric, description) in [("memory_info", "memory", "Memory"), ("swap_info", "swap", "Swap")] {
+        if let Some(info) = sample.get(key) {
+            for field in ["total", "used"] {
+                let samples = info.get(field).and_then(number).map(|value| (vec![], value)).into_iter().collect();
+                exposition.family(
+                    &format!("sysmonk_{}_{}_bytes", metric, field), "gauge",
+                    &format!("{} {} in bytes.", description, field), samples,
+                );
+            }
+        }
+    }
+
+    let mount = |item: &serde_json::Value| vec![
+        ("mount_point", text(item, "mount_point")),
+        ("file_system", text(item, "file_system")),
+        ("device", text(item, "device")),
+    ];
+    for field in ["total", "used"] {
+        exposition.family(
+            &format!("sysmonk_disk_{}_bytes", field), "gauge",
+            &format!("Disk space {} per mount in bytes.", field),
+            samples_of(sample, "disk_usage", mount, |item| item.get(field).and_then(number)),
+        );
+    }
+
+    let device = |item: &serde_json::Value| vec![("device", text(item, "name"))];
+    for (field, metric, description) in [
+        ("read_bytes", "sysmonk_disk_read_bytes_per_second", "Bytes read per second."),
+        ("write_bytes", "sysmonk_disk_write_bytes_per_second", "Bytes written per second."),
+        ("read_iops", "sysmonk_disk_reads_per_second", "Read operations completed per second."),
+        ("write_iops", "sysmonk_disk_writes_per_second", "Write operations completed per second."),
+        ("await_ms", "sysmonk_disk_await_milliseconds", "Average time taken to serve an I/O request."),
+        ("utilization", "sysmonk_disk_utilization_percent", "Percentage of time the device was busy."),
+    ] {
+        exposition.family(
+            metric, "gauge", description,
+            samples_of(sample, "disk_io", device, |item| item.get(field).and_then(number)),
+        );
+    }
+
+    let interface = |item: &serde_json::Value| vec![("interface", text(item, "name"))];
+    exposition.family(
+        "sysmonk_network_up", "gauge", "Whether the operational state of the interface is up.",
+        samples_of(sample, "network_stats", interface, |item| Some(if text(item, "state") == "up" { 1.0 } else { 0.0 })),
+    );
+    for (field, metric, description) in [
+        ("rx_bytes", "sysmonk_network_receive_bytes_total", "Bytes received."),
+        ("tx_bytes", "sysmonk_network_transmit_bytes_total", "Bytes transmitted."),
+        ("rx_packets", "sysmonk_network_receive_packets_total", "Packets received."),
+        ("tx_packets", "sysmonk_network_transmit_packets_total", "Packets transmitted."),
+        ("rx_errors", "sysmonk_network_receive_errors_total", "Errors while receiving."),
+        ("tx_errors", "sysmonk_network_transmit_errors_total", "Errors while transmitting."),
+        ("rx_dropped", "sysmonk_network_receive_dropped_total", "Packets dropped while receiving."),
+        ("tx_dropped", "sysmonk_network_transmit_dropped_total", "Packets dropped while transmitting."),
+    ] {
+        exposition.family(
+            metric, "counter", description,
+            samples_of(sample, "network_stats", interface, |item| item["totals"].get(field).and_then(number)),
+        );
+    }
+
+    let container = |item: &serde_json::Value| vec![("id", text(item, "ID")), ("name", text(item, "Name"))];
+    exposition.family(
+        "sysmonk_docker_cpu_percent", "gauge", "CPU usage of the container in percent.",
+        samples_of(sample, "docker_stats", container, |item| number(&item["CPUPerc"])),
+    );
+    exposition.family(
+        "sysmonk_docker_memory_percent", "gauge", "Memory usage of the container in percent.",
+        samples_of(sample, "docker_stats", container, |item| number(&item["MemPerc"])),
+    );
+    // Network and block I/O are reported since the container started, so they only go up until it is restarted
+    for (key, first, second, kind, description) in [
+        ("MemUsage", "sysmonk_docker_memory_used_bytes", "sysmonk_docker_memory_limit_bytes", "gauge",
+         "Memory of the container in bytes"),
+        ("NetIO", "sysmonk_docker_network_receive_bytes_total", "sysmonk_docker_network_transmit_bytes_total",
+         "counter", "Network I/O of the container in bytes since it started"),
+        ("BlockIO", "sysmonk_docker_block_read_bytes_total", "sysmonk_docker_block_write_bytes_total",
+         "counter", "Block I/O of the container in bytes since it started"),
+    ] {
+        exposition.family(
+            first, kind, &format!("{}, as reported by docker stats.", description),
+            samples_of(sample, "docker_stats", container, |item| docker_pair(&item[key]).map(|pair| pair.0)),
+        );
+        exposition.family(
+            second, kind, &format!("{}, as reported by docker stats.", description),
+            samples_of(sample, "docker_stats", container, |item| docker_pair(&item[key]).map(|pair| pair.1)),
+        );
+    }
+    exposition.family(
+        "sysmonk_docker_pids", "gauge", "Number of processes in the container.",
+        samples_of(sample, "docker_stats", container, |item| number(&item["PIDs"])),
+    );
+
+    for (key, kind) in [("service_stats", "service"), ("process_stats", "process")] {
+        let usages = usages(sample, key);
+        let samples = |value: &dyn Fn(&Usage) -> Option<f64>| -> Vec<(Labels, f64)> {
+            usages.iter()
+                .filter_map(|(name, usage)| value(usage).map(|value| (vec![("name", name.to_string())], value)))
+                .collect()
+        };
+        exposition.family(
+            &format!("sysmonk_{}_up", kind), "gauge", &format!("Whether the {} is running.", kind),
+            samples(&|usage| Some(if usage.values.is_some() { 1.0 } else { 0.0 })),
+        );
+        if kind == "service" {
+            exposition.family(
+                "sysmonk_service_pid", "gauge", "PID of the service, or 0 when it is not running.",
+                samples(&|usage| Some(usage.pid)),
+            );
+        } else {
+            exposition.family(
+                "sysmonk_process_count", "gauge", "Number of running processes with the name.",
+                samples(&|usage| Some(usage.count as f64)),
+            );
+        }
+        for (field, metric, metric_kind, description) in [
+            ("cpu_percent", "cpu_percent", "gauge", "CPU usage in percent."),
+            ("memory_bytes", "memory_bytes", "gauge", "Resident memory in bytes."),
+            ("uptime_seconds", "uptime_seconds", "gauge", "Time since the process was started."),
+            ("read_bytes", "read_bytes_total", "counter", "Bytes read from disk."),
+            ("written_bytes", "written_bytes_total", "counter", "Bytes written to disk."),
+        ] {
+            exposition.family(
+                &format!("sysmonk_{}_{}", kind, metric), metric_kind, description,
+                samples(&|usage| usage.values.as_ref().and_then(|values| values.get(field).copied())),
+            );
+        }
+    }
+    exposition.output
+}
+
+/// Renders the counters of the login attempts, in the Prometheus text exposition format.
+///
+/// # Arguments
+///
+/// * `stats` - Counters of the login attempts.
+///
+/// # Returns
+///
+/// Returns the exposition as a `String`.
+pub fn render_logins(stats: &Stats) -> String {
+    let mut exposition = Exposition { output: String::new() };
+    exposition.family("sysmonk_login_failures_total", "counter", "Failed login attempts.",
+                      vec![(vec![], stats.failures as f64)]);
+    exposition.family("sysmonk_login_rejected_total", "counter",
+                      "Login attempts rejected, since the IP address or the username was locked out.",
+                      vec![(vec![], stats.rejected as f64)]);
+    exposition.family("sysmonk_login_lockouts_total", "counter",
+                      "Times an IP address or a username was locked out.",
+                      vec![(vec![], stats.lockouts as f64)]);
+    let locked = stats.locked.iter()
+        .map(|(kind, count)| (vec![("kind", format!("{:?}", kind).to_lowercase())], *count as f64))
+        .collect();
+    exposition.family("sysmonk_login_locked", "gauge",
+                      "IP addresses and usernames that are locked out.", locked);
+    exposition.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Returns the lines of a metric family in the exposition, including its help text and type.
+    fn family<'a>(exposition: &'a str, name: &str) -> Vec<&'a str> {
+        exposition.lines()
+            .filter(|line| line.split([' ', '{']).any(|word| word == name))
+            .collect()
+    }
+
+    #[test]
+    fn test_docker_io_is_a_counter() {
+        let sample = Sample::from([("docker_stats".to_string(), json!([{
+            "ID": "abc", "Name": "redis", "CPUPerc": "1.5%", "MemPerc": "0.2%", "PIDs": "4",
+            "MemUsage": "1.5MiB / 2GiB", "NetIO": "12kB / 3MB", "BlockIO": "0B / 1.5GB"
+        }]))]);
+        let exposition = render(&sample);
+        assert_eq!(family(&exposition, "sysmonk_docker_network_receive_bytes_total"), vec![
+            "# HELP sysmonk_docker_network_receive_bytes_total Network I/O of the container in bytes since it started, as reported by docker stats.",
+            "# TYPE sysmonk_docker_network_receive_bytes_total counter",
+            "sysmonk_docker_network_receive_bytes_total{id=\"abc\",name=\"redis\"} 12000",
+        ]);
+        assert_eq!(family(&exposition, "sysmonk_docker_block_write_bytes_total")[1],
+                   "# TYPE sysmonk_docker_block_write_bytes_total counter");
+        assert_eq!(family(&exposition, "sysmonk_docker_memory_used_bytes")[1],
+                   "# TYPE sysmonk_docker_memory_used_bytes gauge");
+        assert_eq!(family(&exposition, "sysmonk_docker_memory_limit_bytes")[2],
+                   "sysmonk_docker_memory_limit_bytes{id=\"abc\",name=\"redis\"} 2147483648");
+        assert!(!exposition.contains("sysmonk_docker_network_receive_bytes{"));
+    }
+
+    #[test]
+    fn test_usage_is_labeled_by_name() {
+        let values = |cpu: f64, uptime: u64| json!({
+            "cpu_percent": cpu, "memory_bytes": 100, "uptime_seconds": uptime, "read_bytes": 10, "written_bytes": 5
+        });
+        let sample = Sample::from([
+            ("process_stats".to_string(), json!([
+                {"name": "python", "pid": 10, "values": values(1.5, 30)},
+                {"name": "python", "pid": 20, "values": values(2.0, 90)},
+            ])),
+            ("service_stats".to_string(), json!([
+                {"name": "nginx", "pid": 42, "values": values(0.5, 60)},
+                {"name": "ssh", "pid": 0, "values": null},
+            ])),
+        ]);
+        let exposition = render(&sample);
+        assert!(!exposition.contains("pid=\""));
+        assert_eq!(family(&exposition, "sysmonk_process_cpu_percent")[2], "sysmonk_process_cpu_percent{name=\"python\"} 3.5");
+        assert_eq!(family(&exposition, "sysmonk_process_uptime_seconds")[2], "sysmonk_process_uptime_seconds{name=\"python\"} 90");
+        assert_eq!(family(&exposition, "sysmonk_process_read_bytes_total")[2], "sysmonk_process_read_bytes_total{name=\"python\"} 20");
+        assert_eq!(family(&exposition, "sysmonk_process_count")[2], "sysmonk_process_count{name=\"python\"} 2");
+        assert_eq!(family(&exposition, "sysmonk_service_up")[2..], ["sysmonk_service_up{name=\"nginx\"} 1", "sysmonk_service_up{name=\"ssh\"} 0"]);
+        assert_eq!(family(&exposition, "sysmonk_service_pid")[2..], ["sysmonk_service_pid{name=\"nginx\"} 42", "sysmonk_service_pid{name=\"ssh\"} 0"]);
+        // A service that isn't running has no values to export
+        assert_eq!(family(&exposition, "sysmonk_service_memory_bytes")[2..], ["sysmonk_service_memory_bytes{name=\"nginx\"} 100"]);
+    }
+}
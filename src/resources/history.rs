@@ -0,0 +1,515 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::resources::collector::{number, percent, Sample};
+use crate::resources::storage;
+use crate::squire;
+
+/// Struct for the specification of a tier.
+///
+/// ## Fields
+///
+/// * `name` - Name of the tier.
+/// * `resolution` - Interval (in seconds) between two points.
+/// * `capacity` - Number of points retained in memory.
+/// * `segment` - Time span (in seconds) covered by a single segment file, when persisted to disk.
+#[derive(Clone, Copy, Debug)]
+struct TierSpec {
+    name: &'static str,
+    resolution: i64,
+    capacity: usize,
+    segment: i64,
+}
+
+impl TierSpec {
+    /// Returns the earliest timestamp that the tier can retain in memory.
+    fn horizon(&self, now: i64) -> i64 {
+        now - self.resolution * self.capacity as i64
+    }
+}
+
+/// Maximum time span (in seconds) that can be retained at full resolution in memory, which is what the minute tier holds.
+pub const MAX_HISTORY_RAW: i64 = 86_400;
+
+/// Number of tiers in every time series.
+const TIER_COUNT: usize = 3;
+
+/// Builds the specification of each tier, from the finest to the coarsest.
+///
+/// # Arguments
+///
+/// * `history_raw` - Time span (in seconds) to retain at full resolution in memory.
+///
+/// ## See Also
+///
+/// Retains `history_raw` at full resolution, 24 hours at 1 minute and 30 days at 1 hour resolution in memory.
+fn tiers(history_raw: i64) -> [TierSpec; TIER_COUNT] {
+    [
+        TierSpec { name: "raw", resolution: 1, capacity: history_raw as usize, segment: 3600 },
+        TierSpec { name: "minute", resolution: 60, capacity: 1440, segment: 86400 },
+        TierSpec { name: "hour", resolution: 3600, capacity: 720, segment: 2592000 },
+    ]
+}
+
+/// Maximum number of points returned for a single query, beyond which the step is widened.
+pub const MAX_POINTS: i64 = 3600;
+
+/// Type alias for a point in the time series, as a tuple of epoch timestamp (in seconds) and its value.
+pub type Point = (i64, f64);
+
+/// Struct to accumulate the values that fall within the same bucket of a tier.
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    start: i64,
+    sum: f64,
+    count: u32,
+}
+
+impl Bucket {
+    /// Returns the average of the values in the bucket as a point.
+    fn point(&self) -> Point {
+        (self.start, self.sum / self.count as f64)
+    }
+}
+
+/// Struct for a single resolution of a time series, which is downsampled by averaging the values in a bucket.
+struct Tier {
+    spec: TierSpec,
+    points: VecDeque<Point>,
+    pending: Option<Bucket>,
+}
+
+impl Tier {
+    /// Adds a value to the tier, flushing the pending bucket when the value belongs to the next one.
+    ///
+    /// # Returns
+    ///
+    /// Returns the point that was flushed, if any.
+    fn push(&mut self, timestamp: i64, value: f64) -> Option<Point> {
+        let start = timestamp - timestamp.rem_euclid(self.spec.resolution);
+        let flushed = match self.pending.as_mut() {
+            Some(bucket) if bucket.start == start => {
+                bucket.sum += value;
+                bucket.count += 1;
+                return None;
+            }
+            Some(bucket) => Some(bucket.point()),
+            None => None,
+        };
+        if let Some(point) = flushed {
+            self.restore(point);
+        }
+        self.pending = Some(Bucket { start, sum: value, count: 1 });
+        flushed
+    }
+
+    /// Adds a complete point to the tier, dropping the oldest point when the tier is full.
+    fn restore(&mut self, point: Point) {
+        if self.points.len() == self.spec.capacity {
+            self.points.pop_front();
+        }
+        self.points.push_back(point);
+    }
+
+    /// Returns an iterator over all the points in the tier, including the pending bucket.
+    fn iter(&self) -> impl Iterator<Item=Point> + '_ {
+        self.points.iter().copied().chain(self.pending.map(|bucket| bucket.point()))
+    }
+
+    /// Returns the timestamp of the latest value in the tier.
+    fn latest(&self) -> Option<i64> {
+        self.pending.map(|bucket| bucket.start).or(self.points.back().map(|point| point.0))
+    }
+}
+
+/// Struct for a time series with multiple tiers of resolution.
+struct Series {
+    tiers: Vec<Tier>,
+}
+
+impl Series {
+    fn new(specs: &[TierSpec]) -> Series {
+        let tiers = specs.iter()
+            .map(|spec| Tier {
+                spec: *spec,
+                points: VecDeque::with_capacity(spec.capacity),
+                pending: None,
+            })
+            .collect();
+        Series { tiers }
+    }
+}
+
+/// Struct for the in-memory time series store, that is fed by the collector.
+///
+/// ## Fields
+///
+/// * `specs` - Specification of each tier, from the finest to the coarsest.
+/// * `series` - Used to store the metric name and its time series as key value pairs.
+/// * `storage` - Optional storage on disk, that retains the points for longer than the memory does.
+pub struct History {
+    specs: [TierSpec; TIER_COUNT],
+    series: Mutex<HashMap<String, Series>>,
+    storage: Option<Mutex<storage::Storage>>,
+}
+
+/// Struct for the result of a query against the time series store.
+///
+/// ## Fields
+///
+/// * `metric` - Name of the metric.
+/// * `step` - Interval (in seconds) between two points.
+/// * `points` - Points within the requested time range.
+#[derive(Serialize, Debug)]
+pub struct Query {
+    pub metric: String,
+    pub step: i64,
+    pub points: Vec<Point>,
+}
+
+/// Flattens the sample into scalar metrics that are worth retaining over time.
+///
+/// # Arguments
+///
+/// * `sample` - Sample taken by the collector.
+///
+/// # Returns
+///
+/// Returns a `Vec` of metric names and their values.
+fn flatten(sample: &Sample) -> Vec<(String, f64)> {
+    let mut metrics = Vec::new();
+    if let Some(cores) = sample.get("cpu_usage").and_then(|value| value.as_array()) {
+        let usages: Vec<f64> = cores.iter().filter_map(number).collect();
+        if !usages.is_empty() {
+            metrics.push(("cpu".to_string(), usages.iter().sum::<f64>() / usages.len() as f64));
+        }
+    }
+    if let Some(load_averages) = sample.get("load_averages") {
+        for key in ["m1", "m5", "m15"] {
+            if let Some(value) = load_averages.get(key).and_then(number) {
+                metrics.push((format!("load.{}", key), value));
+            }
+        }
+    }
+    for (key, metric) in [("memory_info", "memory"), ("swap_info", "swap"), ("disk_info", "disk")] {
+        if let Some(usage) = sample.get(key).and_then(percent) {
+            metrics.push((metric.to_string(), usage));
+        }
+    }
+    if let Some(interfaces) = sample.get("network_stats").and_then(|value| value.as_array()) {
+        for interface in interfaces {
+            let name = interface.get("name").and_then(|value| value.as_str()).unwrap_or_default();
+            let rates = &interface["rates"];
+            for (key, suffix) in [("rx_bytes", "rx"), ("tx_bytes", "tx")] {
+                if let Some(value) = rates.get(key).and_then(number) {
+                    metrics.push((format!("network.{}.{}", name, suffix), value));
+                }
+            }
+        }
+    }
+    if let Some(devices) = sample.get("disk_io").and_then(|value| value.as_array()) {
+        for device in devices {
+            let name = device.get("name").and_then(|value| value.as_str()).unwrap_or_default();
+            for (key, suffix) in [("read_bytes", "read"), ("write_bytes", "write"), ("utilization", "util")] {
+                if let Some(value) = device.get(key).and_then(number) {
+                    metrics.push((format!("disk_io.{}.{}", name, suffix), value));
+                }
+            }
+        }
+    }
+    metrics
+}
+
+impl History {
+    /// Records the metrics in a sample taken by the collector.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Epoch timestamp (in seconds) at which the sample was taken.
+    /// * `sample` - Sample taken by the collector.
+    pub fn record(&self, timestamp: i64, sample: &Sample) {
+        let mut series = self.series.lock().unwrap();
+        let mut flushed: Vec<BTreeMap<i64, BTreeMap<String, f64>>> = vec![BTreeMap::new(); TIER_COUNT];
+        for (metric, value) in flatten(sample) {
+            let entry = series.entry(metric.clone()).or_insert_with(|| Series::new(&self.specs));
+            for (index, tier) in entry.tiers.iter_mut().enumerate() {
+                if let Some((bucket, average)) = tier.push(timestamp, value) {
+                    flushed[index].entry(bucket).or_default().insert(metric.clone(), average);
+                }
+            }
+        }
+        // Drop the metrics that are gone for longer than the coarsest tier can retain, like removed interfaces
+        series.retain(|_, entry| {
+            entry.tiers.last()
+                .and_then(|tier| tier.latest().map(|latest| latest >= tier.spec.horizon(timestamp)))
+                .unwrap_or(false)
+        });
+        // Queries can read the memory while the flushed points are written to the disk
+        drop(series);
+        if let Some(storage) = &self.storage {
+            let mut storage = storage.lock().unwrap();
+            for (index, rows) in flushed.into_iter().enumerate() {
+                for (timestamp, metrics) in rows {
+                    storage.append(index, &storage::Row { timestamp, metrics });
+                }
+            }
+        }
+    }
+
+    /// Lists the names of all the metrics in the store.
+    pub fn metrics(&self) -> Vec<String> {
+        let mut metrics: Vec<String> = self.series.lock().unwrap().keys().cloned().collect();
+        metrics.sort();
+        metrics
+    }
+
+    /// Queries a metric within a time range.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - Name of the metric.
+    /// * `from` - Epoch timestamp (in seconds) for the start of the range.
+    /// * `to` - Epoch timestamp (in seconds) for the end of the range.
+    /// * `step` - Requested interval (in seconds) between two points.
+    ///
+    /// # See Also
+    ///
+    /// - The step is widened to limit the points to `MAX_POINTS`, and once the range holds more than that at full
+    ///   resolution, it is rounded up to the resolution of the next tier. So a range of days reads the minute tier.
+    /// - Among the tiers that still retain the start of the range, the coarsest one that is at least as fine as the
+    ///   step is used, and the step is widened to the resolution of that tier.
+    /// - Points that are no longer in memory are read from the storage on disk, when enabled. Only the segment files
+    ///   are listed while the storage is locked, so the collector isn't held up while they are parsed.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` containing the query result, or `None` if the metric is unknown.
+    pub fn query(&self, metric: &str, from: i64, to: i64, step: i64) -> Option<Query> {
+        let now = Utc::now().timestamp();
+        let mut step = step.max((to - from + MAX_POINTS - 1) / MAX_POINTS);
+        if to - from > MAX_POINTS * self.specs[0].resolution {
+            if let Some(spec) = self.specs.iter().find(|spec| spec.resolution >= step) {
+                step = spec.resolution;
+            }
+        }
+        let horizon = |index: usize| match &self.storage {
+            Some(storage) => storage.lock().unwrap().horizon(index, now),
+            None => self.specs[index].horizon(now),
+        };
+        let covering: Vec<usize> = (0..TIER_COUNT).filter(|index| horizon(*index) <= from).collect();
+        let index = covering.iter().rev()
+            .find(|index| self.specs[**index].resolution <= step)
+            .or(covering.first())
+            .copied()
+            .unwrap_or(TIER_COUNT - 1);
+        let step = step.max(self.specs[index].resolution);
+        let mut points: BTreeMap<i64, f64> = BTreeMap::new();
+        if let Some(storage) = &self.storage {
+            if from < self.specs[index].horizon(now) {
+                let paths = storage.lock().unwrap().segments(index, from, to);
+                for row in storage::read(&paths, from, to) {
+                    if let Some(value) = row.metrics.get(metric) {
+                        points.insert(row.timestamp, *value);
+                    }
+                }
+            }
+        }
+        match self.series.lock().unwrap().get(metric) {
+            Some(entry) => points.extend(
+                entry.tiers[index].iter().filter(|(timestamp, _)| *timestamp >= from && *timestamp <= to)
+            ),
+            None if points.is_empty() => return None,
+            None => (),
+        }
+        let mut buckets: BTreeMap<i64, Bucket> = BTreeMap::new();
+        for (timestamp, value) in points {
+            let start = timestamp - timestamp.rem_euclid(step);
+            let bucket = buckets.entry(start).or_insert(Bucket { start, sum: 0.0, count: 0 });
+            bucket.sum += value;
+            bucket.count += 1;
+        }
+        Some(Query {
+            metric: metric.to_string(),
+            step,
+            points: buckets.values().map(|bucket| bucket.point()).collect(),
+        })
+    }
+
+    /// Retrieves the most recent points at full resolution for all the metrics.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Number of points to retrieve for each metric.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `HashMap` of metric name and its recent points.
+    pub fn recent(&self, count: usize) -> HashMap<String, Vec<Point>> {
+        self.series.lock().unwrap()
+            .iter()
+            .map(|(metric, entry)| {
+                let points: Vec<Point> = entry.tiers[0].iter().collect();
+                let skip = points.len().saturating_sub(count);
+                (metric.to_string(), points[skip..].to_vec())
+            })
+            .collect()
+    }
+}
+
+impl History {
+    /// Loads the points that fit in memory from the storage on disk, so that the history survives restarts.
+    fn load(&self) {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return,
+        };
+        let now = Utc::now().timestamp();
+        for (index, spec) in self.specs.iter().enumerate() {
+            let paths = storage.lock().unwrap().segments(index, spec.horizon(now), now);
+            let rows = storage::read(&paths, spec.horizon(now), now);
+            log::info!("Loaded {} rows for the '{}' tier from the storage", rows.len(), spec.name);
+            let mut series = self.series.lock().unwrap();
+            for row in rows {
+                for (metric, value) in row.metrics {
+                    let entry = series.entry(metric).or_insert_with(|| Series::new(&self.specs));
+                    entry.tiers[index].restore((row.timestamp, value));
+                }
+            }
+        }
+    }
+}
+
+/// Creates the time series store, which is backed by the storage on disk when `storage_dir` is set.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `History` struct.
+pub fn store(config: &squire::settings::Config) -> Arc<History> {
+    let specs = tiers(config.history_raw);
+    let storage = config.storage_dir.as_ref().and_then(|directory| {
+        let retention = [config.retention_raw, config.retention_minute, config.retention_hour];
+        let policies = specs.iter().zip(retention)
+            .map(|(spec, retention)| storage::Policy { name: spec.name, segment: spec.segment, retention })
+            .collect();
+        match storage::Storage::open(directory, policies) {
+            Ok(storage) => {
+                log::info!("Persisting metrics to '{}'", directory);
+                Some(Mutex::new(storage))
+            }
+            Err(err) => {
+                log::error!("Failed to open the storage at '{}', metrics won't be persisted: {}", directory, err);
+                None
+            }
+        }
+    });
+    let history = History {
+        specs,
+        series: Mutex::new(HashMap::new()),
+        storage,
+    };
+    history.load();
+    Arc::new(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Creates an empty directory for a test, which is unique to the test and the process.
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("sysmonk-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    /// Opens a store, and loads what the storage holds like a restart does.
+    fn open(directory: Option<&Path>, history_raw: i64) -> History {
+        let specs = tiers(history_raw);
+        let storage = directory.map(|directory| {
+            let policies = specs.iter()
+                .map(|spec| storage::Policy { name: spec.name, segment: spec.segment, retention: 2_592_000 })
+                .collect();
+            Mutex::new(storage::Storage::open(directory.to_str().unwrap(), policies).unwrap())
+        });
+        let history = History { specs, series: Mutex::new(HashMap::new()), storage };
+        history.load();
+        history
+    }
+
+    fn sample(value: f64) -> Sample {
+        Sample::from([("load_averages".to_string(), serde_json::json!({"m1": value}))])
+    }
+
+    #[test]
+    fn test_tier_rollup_and_flush() {
+        let spec = TierSpec { name: "minute", resolution: 60, capacity: 2, segment: 86400 };
+        let mut tier = Tier { spec, points: VecDeque::new(), pending: None };
+        assert_eq!(tier.push(0, 1.0), None);
+        assert_eq!(tier.push(59, 3.0), None);
+        assert_eq!(tier.iter().collect::<Vec<Point>>(), vec![(0, 2.0)]);
+        // A value in the next bucket flushes the average of the pending one
+        assert_eq!(tier.push(61, 5.0), Some((0, 2.0)));
+        assert_eq!(tier.push(125, 7.0), Some((60, 5.0)));
+        assert_eq!(tier.push(180, 9.0), Some((120, 7.0)));
+        // Only the capacity is retained, besides the pending bucket
+        assert_eq!(tier.iter().collect::<Vec<Point>>(), vec![(60, 5.0), (120, 7.0), (180, 9.0)]);
+        assert_eq!(tier.latest(), Some(180));
+    }
+
+    #[test]
+    fn test_query_steps() {
+        let history = open(None, MAX_HISTORY_RAW);
+        let now = Utc::now().timestamp();
+        for timestamp in now - 7200..=now {
+            history.record(timestamp, &sample(1.0));
+        }
+        let query = history.query("load.m1", now - 600, now, 1).unwrap();
+        assert_eq!(query.step, 1);
+        assert_eq!(query.points.len(), 601);
+        assert_eq!(history.query("load.m1", now - 3600, now, 1).unwrap().step, 1);
+        // More than an hour at full resolution is read from the minute tier, instead of widening the raw step
+        let query = history.query("load.m1", now - 7200, now, 1).unwrap();
+        assert_eq!(query.step, 60);
+        assert!(query.points.iter().all(|(timestamp, value)| timestamp % 60 == 0 && *value == 1.0));
+        assert_eq!(history.query("load.m1", now - 600, now, 90).unwrap().step, 90);
+        assert!(history.query("load.m5", now - 600, now, 1).is_none());
+        // Ranges that start before the raw tier retains are read from the minute tier as well
+        let history = open(None, 60);
+        history.record(now, &sample(1.0));
+        assert_eq!(history.query("load.m1", now - 600, now, 1).unwrap().step, 60);
+    }
+
+    #[test]
+    fn test_load_after_restart() {
+        let directory = directory("history-load");
+        let now = Utc::now().timestamp();
+        let history = open(Some(&directory), 60);
+        for timestamp in now - 180..now {
+            history.record(timestamp, &sample((timestamp % 7) as f64));
+        }
+        drop(history);
+        let history = open(Some(&directory), 60);
+        let raw = history.recent(60).remove("load.m1").unwrap();
+        // The raw tier is loaded for the last minute, up to the value that was still pending
+        assert!(raw.len() >= 55);
+        assert_eq!(raw.last().unwrap().0, now - 2);
+        assert!(raw.iter().all(|(timestamp, value)| *value == (timestamp % 7) as f64));
+        let series = history.series.lock().unwrap();
+        let minute: Vec<Point> = series["load.m1"].tiers[1].iter().collect();
+        assert!(!minute.is_empty());
+        assert!(minute.iter().all(|(timestamp, _)| timestamp % 60 == 0 && *timestamp < now - now % 60));
+        drop(series);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
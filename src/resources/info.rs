@@ -18,6 +18,30 @@ pub fn get_disk_usage(disks: &Disks) -> u64 {
     disks_space.iter().sum()
 }
 
+/// Function to get the disks to list, preferring the legacy functions when they find the physical devices.
+///
+/// # Arguments
+///
+/// * `disks` - A reference to the refreshed list of `Disks`.
+///
+/// # Returns
+///
+/// A `Vec` of disks, each with at least a name and a size.
+pub fn get_disk_devices(disks: &Disks) -> Vec<HashMap<String, String>> {
+    let legacy_disk_info = legacy::disks::get_all_disks();
+    // legacy functions have a mechanism to check for physical devices, so it takes precedence
+    let has_name_and_size = !legacy_disk_info.is_empty() &&
+        legacy_disk_info.iter().all(|disk| {
+            disk.contains_key("Name") && disk.contains_key("Size")
+        });
+    if has_name_and_size {
+        log::debug!("Using legacy methods for disks!");
+        legacy_disk_info
+    } else {
+        get_disks(disks)
+    }
+}
+
 /// Function to get individual disk specs.
 ///
 /// # Returns
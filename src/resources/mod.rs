@@ -2,9 +2,19 @@
 pub mod info;
 /// This module contains all the network related functions.
 pub mod network;
+/// This module contains the disk usage and disk I/O related functions.
+pub mod disks;
 /// This module contains system related functions.
 pub mod system;
 /// This module contains functions that are responsible to stream information via websockets.
 pub mod stream;
 /// This module contains functions related to service and process monitoring.
 pub mod operations;
+/// This module contains the background collector that samples system resources once for all subscribers.
+pub mod collector;
+/// This module contains the in-memory time series store for historical metrics.
+pub mod history;
+/// This module contains the append-only storage on disk for historical metrics.
+pub mod storage;
+/// This module contains the Prometheus exporter for the sampled resources.
+pub mod exporter;
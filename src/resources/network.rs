@@ -2,7 +2,10 @@ use crate::squire;
 use reqwest;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
 use std::net::UdpSocket;
+use std::time::Instant;
+use sysinfo::Networks;
 
 /// Function to retrieve the public IP address
 ///
@@ -73,6 +76,7 @@ fn private_ip_address() -> Option<String> {
 ///
 /// * `private_ip_address` - The private IP address of the system
 /// * `public_ip_address` - The public IP address of the system
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SystemInfoNetwork {
     private_ip_address_raw: String,
@@ -90,3 +94,215 @@ pub async fn get_network_info() -> HashMap<&'static str, String> {
         ("Public IP address", public_ip),
     ])
 }
+
+/// Struct to hold the cumulative counters of a network interface.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+}
+
+/// Struct to hold the per second rates of a network interface, computed from the delta between two samples.
+#[derive(Serialize, Debug, Default)]
+pub struct InterfaceRates {
+    pub rx_bytes: f64,
+    pub tx_bytes: f64,
+    pub rx_packets: f64,
+    pub tx_packets: f64,
+    pub rx_errors: f64,
+    pub tx_errors: f64,
+    pub rx_dropped: f64,
+    pub tx_dropped: f64,
+}
+
+impl InterfaceRates {
+    /// Computes the per second rates between two set of counters.
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - Counters from the previous sample.
+    /// * `current` - Counters from the current sample.
+    /// * `elapsed` - Seconds elapsed between the two samples.
+    fn between(previous: &InterfaceCounters, current: &InterfaceCounters, elapsed: f64) -> InterfaceRates {
+        // Counters reset when an interface is re-created, so saturate instead of reporting a negative rate
+        let rate = |prev: u64, curr: u64| curr.saturating_sub(prev) as f64 / elapsed;
+        InterfaceRates {
+            rx_bytes: rate(previous.rx_bytes, current.rx_bytes),
+            tx_bytes: rate(previous.tx_bytes, current.tx_bytes),
+            rx_packets: rate(previous.rx_packets, current.rx_packets),
+            tx_packets: rate(previous.tx_packets, current.tx_packets),
+            rx_errors: rate(previous.rx_errors, current.rx_errors),
+            tx_errors: rate(previous.tx_errors, current.tx_errors),
+            rx_dropped: rate(previous.rx_dropped, current.rx_dropped),
+            tx_dropped: rate(previous.tx_dropped, current.tx_dropped),
+        }
+    }
+}
+
+/// Struct to hold the network statistics of an interface, that is streamed via websockets.
+#[derive(Serialize, Debug)]
+pub struct InterfaceStats {
+    pub name: String,
+    pub state: String,
+    pub totals: InterfaceCounters,
+    pub rates: InterfaceRates,
+}
+
+/// Function to get the cumulative counters of all network interfaces on Linux.
+///
+/// # Arguments
+///
+/// * `lib_path` - The path to the file that contains the network device statistics.
+///
+/// # Returns
+///
+/// A `HashMap` of interface name and its counters.
+fn linux_counters(lib_path: &str) -> HashMap<String, InterfaceCounters> {
+    let content = match fs::read_to_string(lib_path) {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("Failed to read '{}': {}", lib_path, err);
+            return HashMap::new();
+        }
+    };
+    let mut counters = HashMap::new();
+    // Skip the two header lines
+    for line in content.lines().skip(2) {
+        let (name, values) = match line.split_once(':') {
+            Some(split) => split,
+            None => continue,
+        };
+        let fields: Vec<u64> = values
+            .split_whitespace()
+            .map(|value| value.parse().unwrap_or_default())
+            .collect();
+        // Receive and transmit have 8 columns each
+        if fields.len() < 16 {
+            continue;
+        }
+        counters.insert(name.trim().to_string(), InterfaceCounters {
+            rx_bytes: fields[0],
+            rx_packets: fields[1],
+            rx_errors: fields[2],
+            rx_dropped: fields[3],
+            tx_bytes: fields[8],
+            tx_packets: fields[9],
+            tx_errors: fields[10],
+            tx_dropped: fields[11],
+        });
+    }
+    counters
+}
+
+/// Function to get the link state of a network interface on Linux.
+///
+/// # Arguments
+///
+/// * `interface` - Name of the network interface.
+///
+/// # Returns
+///
+/// A `String` with the operational state of the interface.
+fn linux_link_state(interface: &str) -> String {
+    fs::read_to_string(format!("/sys/class/net/{}/operstate", interface))
+        .map(|state| state.trim().to_string())
+        .unwrap_or("unknown".to_string())
+}
+
+/// Function to get the cumulative counters of all network interfaces using `sysinfo`.
+///
+/// # See Also
+///
+/// Dropped packets are not reported by `sysinfo`, so they remain as zero.
+///
+/// # Arguments
+///
+/// * `networks` - A mutable reference to the `Networks` struct.
+///
+/// # Returns
+///
+/// A `HashMap` of interface name and its counters.
+fn sysinfo_counters(networks: &mut Networks) -> HashMap<String, InterfaceCounters> {
+    networks.refresh_list();
+    networks.iter()
+        .map(|(name, data)| {
+            (name.to_string(), InterfaceCounters {
+                rx_bytes: data.total_received(),
+                tx_bytes: data.total_transmitted(),
+                rx_packets: data.total_packets_received(),
+                tx_packets: data.total_packets_transmitted(),
+                rx_errors: data.total_errors_on_received(),
+                tx_errors: data.total_errors_on_transmitted(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Struct to track network interface counters between samples, to compute throughput.
+pub struct NetworkSampler {
+    networks: Networks,
+    previous: HashMap<String, InterfaceCounters>,
+    sampled_at: Instant,
+}
+
+impl NetworkSampler {
+    /// Creates a new sampler with the current counters as the baseline.
+    pub fn new() -> NetworkSampler {
+        let mut sampler = NetworkSampler {
+            networks: Networks::new(),
+            previous: HashMap::new(),
+            sampled_at: Instant::now(),
+        };
+        sampler.previous = sampler.counters();
+        sampler
+    }
+
+    /// OS-agnostic function to get the cumulative counters of all network interfaces.
+    fn counters(&mut self) -> HashMap<String, InterfaceCounters> {
+        match std::env::consts::OS {
+            "linux" => linux_counters("/proc/net/dev"),
+            _ => sysinfo_counters(&mut self.networks),
+        }
+    }
+
+    /// Samples the network interfaces and computes the rates since the previous sample.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `InterfaceStats` sorted by interface name, excluding the loopback interface.
+    pub fn sample(&mut self) -> Vec<InterfaceStats> {
+        let current = self.counters();
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.sampled_at).as_secs_f64().max(f64::EPSILON);
+        let mut stats: Vec<InterfaceStats> = current.iter()
+            .filter(|(name, _)| name.as_str() != "lo" && !name.starts_with("lo0"))
+            .map(|(name, counters)| {
+                let rates = match self.previous.get(name) {
+                    Some(previous) => InterfaceRates::between(previous, counters, elapsed),
+                    None => InterfaceRates::default(),
+                };
+                let state = match std::env::consts::OS {
+                    "linux" => linux_link_state(name),
+                    _ => "unknown".to_string(),
+                };
+                InterfaceStats {
+                    name: name.to_string(),
+                    state,
+                    totals: counters.clone(),
+                    rates,
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        self.previous = current;
+        self.sampled_at = now;
+        stats
+    }
+}
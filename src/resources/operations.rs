@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use crate::squire;
-use sysinfo::{Pid, ProcessesToUpdate, System};
+use sysinfo::{Pid, Process, ProcessesToUpdate, System};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Usage {
@@ -10,10 +10,41 @@ pub struct Usage {
     memory: String,
     uptime: String,
     read_io: String,
-    write_io: String
+    write_io: String,
+    values: Option<UsageValues>
 }
 
-pub fn process_monitor(system: &mut System, process_names: &Vec<String>) -> Vec<Usage> {
+/// Struct to hold the raw values of a `Usage` row, for consumers that need numbers instead of formatted strings.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UsageValues {
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub uptime_seconds: u64,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+}
+
+/// Function to get the raw values of a process.
+///
+/// # Arguments
+///
+/// * `process` - A reference to the `Process` struct.
+///
+/// # Returns
+///
+/// A `UsageValues` struct with the CPU, memory, uptime and total disk I/O of the process.
+fn usage_values(process: &Process) -> UsageValues {
+    let disk_usage = process.disk_usage();
+    UsageValues {
+        cpu_percent: process.cpu_usage(),
+        memory_bytes: process.memory(),
+        uptime_seconds: process.run_time(),
+        read_bytes: disk_usage.total_read_bytes,
+        written_bytes: disk_usage.total_written_bytes,
+    }
+}
+
+pub fn process_monitor(system: &mut System, process_names: &[String]) -> Vec<Usage> {
     let mut usages: Vec<Usage> = Vec::new();
     system.refresh_processes(ProcessesToUpdate::All);
     for (pid, process) in system.processes() {
@@ -39,18 +70,19 @@ pub fn process_monitor(system: &mut System, process_names: &Vec<String>) -> Vec<
                 memory,
                 uptime,
                 read_io: format!("{}/{}", read_since, read),
-                write_io: format!("{}/{}", written_since, written)
+                write_io: format!("{}/{}", written_since, written),
+                values: Some(usage_values(process))
             });
         }
     }
     usages
 }
 
-pub fn service_monitor(system: &mut System, service_names: &Vec<String>) -> Vec<Usage> {
+pub fn service_monitor(system: &mut System, service_names: &[String]) -> Vec<Usage> {
     let mut usages: Vec<Usage> = Vec::new();
     system.refresh_processes(ProcessesToUpdate::All);
     for service_name in service_names {
-        match service_monitor_fn(system, &service_name) {
+        match service_monitor_fn(system, service_name) {
             Ok(usage) => usages.push(usage),
             Err(err) => {
                 log::debug!("{}", err);
@@ -61,7 +93,8 @@ pub fn service_monitor(system: &mut System, service_names: &Vec<String>) -> Vec<
                     cpu: "N/A".to_string(),
                     uptime: "N/A".to_string(),
                     read_io: "N/A".to_string(),
-                    write_io: "N/A".to_string()
+                    write_io: "N/A".to_string(),
+                    values: None
                 });
             }
         };
@@ -69,7 +102,7 @@ pub fn service_monitor(system: &mut System, service_names: &Vec<String>) -> Vec<
     usages
 }
 
-fn service_monitor_fn(system: &System, service_name: &String) -> Result<Usage, String> {
+fn service_monitor_fn(system: &System, service_name: &str) -> Result<Usage, String> {
     let pid = match get_service_pid(service_name) {
         Some(pid) => pid,
         None => return Err(format!("Failed to get PID for service: {}", service_name)),
@@ -96,7 +129,8 @@ fn service_monitor_fn(system: &System, service_name: &String) -> Result<Usage, S
             memory,
             uptime,
             read_io: format!("{}/{}", read_since, read),
-            write_io: format!("{}/{}", written_since, written)
+            write_io: format!("{}/{}", written_since, written),
+            values: Some(usage_values(process))
         })
     } else {
         Err(format!("Process with PID {} not found", pid))
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Struct for a row in a segment file, holding the values of all metrics at a timestamp.
+///
+/// ## Fields
+///
+/// * `timestamp` - Epoch timestamp (in seconds) for the start of the bucket.
+/// * `metrics` - Metric names and their values.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Row {
+    pub timestamp: i64,
+    pub metrics: BTreeMap<String, f64>,
+}
+
+/// Struct for the retention policy of a tier on disk.
+///
+/// ## Fields
+///
+/// * `name` - Name of the tier, which is also the name of its directory.
+/// * `segment` - Time span (in seconds) covered by a single segment file.
+/// * `retention` - Time span (in seconds) after which a segment file is deleted.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub name: &'static str,
+    pub segment: i64,
+    pub retention: i64,
+}
+
+/// Struct for the segment file that is currently being appended to, for a tier.
+struct Segment {
+    start: i64,
+    file: fs::File,
+}
+
+/// Struct for the append-only storage of metrics, with a directory of segment files per tier.
+///
+/// ## See Also
+///
+/// Every segment file is named after the epoch timestamp it starts from, and contains JSON rows separated by
+/// new lines. Partially written rows (after a crash) are skipped when reading.
+pub struct Storage {
+    directory: PathBuf,
+    policies: Vec<Policy>,
+    segments: HashMap<&'static str, Segment>,
+}
+
+/// Lists the segment files of a tier along with their start timestamps.
+///
+/// # Arguments
+///
+/// * `directory` - Directory of the tier.
+///
+/// # Returns
+///
+/// Returns a `Vec` of start timestamps and paths, sorted by the start timestamp.
+fn segment_files(directory: &Path) -> Vec<(i64, PathBuf)> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::error!("Failed to list '{}': {}", directory.display(), err);
+            return Vec::new();
+        }
+    };
+    let mut segments: Vec<(i64, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "jsonl"))
+        .filter_map(|path| {
+            let start = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((start, path))
+        })
+        .collect();
+    segments.sort();
+    segments
+}
+
+impl Storage {
+    /// Opens the storage, creating a directory for each tier if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Root directory for the storage.
+    /// * `policies` - Retention policy for each tier.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Storage` struct, or the error from creating the directories.
+    pub fn open(directory: &str, policies: Vec<Policy>) -> std::io::Result<Storage> {
+        let directory = PathBuf::from(directory);
+        for policy in &policies {
+            fs::create_dir_all(directory.join(policy.name))?;
+        }
+        Ok(Storage {
+            directory,
+            policies,
+            segments: HashMap::new(),
+        })
+    }
+
+    /// Appends a row to the current segment file of a tier, starting a new segment when the current one is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `tier` - Index of the tier.
+    /// * `row` - Row to append.
+    pub fn append(&mut self, tier: usize, row: &Row) {
+        let policy = &self.policies[tier];
+        let start = row.timestamp - row.timestamp.rem_euclid(policy.segment);
+        if self.segments.get(policy.name).map(|segment| segment.start) != Some(start) {
+            let path = self.directory.join(policy.name).join(format!("{}.jsonl", start));
+            match fs::OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => {
+                    self.segments.insert(policy.name, Segment { start, file });
+                }
+                Err(err) => {
+                    log::error!("Failed to open '{}': {}", path.display(), err);
+                    return;
+                }
+            }
+            self.sweep(tier, row.timestamp);
+        }
+        let segment = self.segments.get_mut(self.policies[tier].name).unwrap();
+        let line = format!("{}\n", serde_json::to_string(row).unwrap());
+        if let Err(err) = segment.file.write_all(line.as_bytes()) {
+            log::error!("Failed to write to the '{}' segment: {}", self.policies[tier].name, err);
+        }
+    }
+
+    /// Deletes the segment files of a tier that are beyond its retention.
+    ///
+    /// # Arguments
+    ///
+    /// * `tier` - Index of the tier.
+    /// * `now` - Current epoch timestamp (in seconds).
+    fn sweep(&self, tier: usize, now: i64) {
+        let policy = &self.policies[tier];
+        for (start, path) in segment_files(&self.directory.join(policy.name)) {
+            if start + policy.segment > now - policy.retention {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(_) => log::info!("Removed expired segment '{}'", path.display()),
+                Err(err) => log::error!("Failed to remove '{}': {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Lists the segment files of a tier that overlap with a time range.
+    ///
+    /// # Arguments
+    ///
+    /// * `tier` - Index of the tier.
+    /// * `from` - Epoch timestamp (in seconds) for the start of the range.
+    /// * `to` - Epoch timestamp (in seconds) for the end of the range.
+    ///
+    /// # See Also
+    ///
+    /// Only the directory is listed here, so that the files can be read with `read` without holding the storage.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec` of paths, sorted by the start timestamp of the segment.
+    pub fn segments(&self, tier: usize, from: i64, to: i64) -> Vec<PathBuf> {
+        let policy = &self.policies[tier];
+        segment_files(&self.directory.join(policy.name))
+            .into_iter()
+            .filter(|(start, _)| *start <= to && start + policy.segment > from)
+            .map(|(_, path)| path)
+            .collect()
+    }
+
+    /// Returns the earliest timestamp that a tier retains on disk.
+    pub fn horizon(&self, tier: usize, now: i64) -> i64 {
+        now - self.policies[tier].retention
+    }
+}
+
+/// Reads the rows within a time range from segment files, as they are listed by `Storage::segments`.
+///
+/// # Arguments
+///
+/// * `paths` - Paths of the segment files, sorted by their start timestamp.
+/// * `from` - Epoch timestamp (in seconds) for the start of the range.
+/// * `to` - Epoch timestamp (in seconds) for the end of the range.
+///
+/// # See Also
+///
+/// A segment that is swept while it is being read is skipped, and so is a row that is still being written.
+///
+/// # Returns
+///
+/// Returns a `Vec` of rows, sorted by their timestamp.
+pub fn read(paths: &[PathBuf], from: i64, to: i64) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for path in paths {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => {
+                log::error!("Failed to read '{}': {}", path.display(), err);
+                continue;
+            }
+        };
+        rows.extend(
+            content.lines()
+                .filter_map(|line| serde_json::from_str::<Row>(line).ok())
+                .filter(|row| row.timestamp >= from && row.timestamp <= to)
+        );
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Creates an empty directory for a test, which is unique to the test and the process.
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("sysmonk-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn row(timestamp: i64, value: f64) -> Row {
+        Row { timestamp, metrics: BTreeMap::from([("cpu".to_string(), value)]) }
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let directory = directory("storage-read");
+        let policy = Policy { name: "raw", segment: 10, retention: 1000 };
+        let mut storage = Storage::open(directory.to_str().unwrap(), vec![policy]).unwrap();
+        for timestamp in 0..30 {
+            storage.append(0, &row(timestamp, timestamp as f64));
+        }
+        let paths = storage.segments(0, 5, 14);
+        assert_eq!(paths.len(), 2);
+        let rows = read(&paths, 5, 14);
+        assert_eq!(rows.iter().map(|row| row.timestamp).collect::<Vec<i64>>(), (5..=14).collect::<Vec<i64>>());
+        assert_eq!(rows[0].metrics["cpu"], 5.0);
+        // A row that was cut short by a crash is skipped
+        fs::OpenOptions::new().append(true).open(&paths[1]).unwrap().write_all(b"{\"timestamp\":19,").unwrap();
+        assert_eq!(read(&storage.segments(0, 10, 19), 10, 19).len(), 10);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_sweep_retention() {
+        let directory = directory("storage-sweep");
+        let policy = Policy { name: "raw", segment: 10, retention: 20 };
+        let mut storage = Storage::open(directory.to_str().unwrap(), vec![policy]).unwrap();
+        for timestamp in (0..50).step_by(5) {
+            storage.append(0, &row(timestamp, 1.0));
+        }
+        let starts = |storage: &Storage| -> Vec<i64> {
+            segment_files(&storage.directory.join("raw")).into_iter().map(|(start, _)| start).collect()
+        };
+        // Segments are swept as a new one is started, once they end before the retention
+        assert_eq!(starts(&storage), vec![20, 30, 40]);
+        storage.append(0, &row(50, 1.0));
+        assert_eq!(starts(&storage), vec![30, 40, 50]);
+        assert_eq!(storage.horizon(0, 50), 30);
+        assert!(read(&storage.segments(0, 0, 29), 0, 29).is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
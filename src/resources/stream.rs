@@ -1,28 +1,10 @@
-use std::collections::HashMap;
-use sysinfo::{CpuRefreshKind, Disks, RefreshKind, System};
+use std::collections::{HashMap, HashSet};
+use sysinfo::System;
 
+use crate::resources::collector::Topic;
 use crate::{resources, squire};
 use serde_json;
 
-/// Function to get disk statistics.
-///
-/// # Returns
-///
-/// A `Value` object with total and used disk space.
-pub fn get_disk_stats() -> serde_json::Value {
-    let disks = Disks::new_with_refreshed_list();
-    let disks_total = resources::info::get_disk_usage(&disks);
-    let mut disk_available: Vec<u64> = [].to_vec();
-    for disk in disks.list() {
-        disk_available.push(disk.available_space());
-    }
-    let disks_available: u64 = disk_available.iter().sum();
-    serde_json::json!({
-        "total": disks_total,
-        "used": disks_total - disks_available,
-    })
-}
-
 /// Function to get docker stats via commandline.
 ///
 /// # Returns
@@ -80,15 +62,14 @@ fn get_process_stats(
 
 /// Function to get CPU usage percentage.
 ///
+/// # Arguments
+///
+/// * `system` - A reference to the `System` struct, with CPU usage refreshed since the previous sample.
+///
 /// # Returns
 ///
 /// A `Vec` containing the CPU usage percentage of each core.
-fn get_cpu_percent() -> Vec<String> {
-    let mut system = System::new_with_specifics(
-        RefreshKind::new().with_cpu(CpuRefreshKind::everything()),
-    );
-    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
-    system.refresh_cpu_all();
+fn get_cpu_percent(system: &System) -> Vec<String> {
     let mut cpu_usage = Vec::new();
     for core in system.cpus() {
         cpu_usage.push(format!("{:.2}", core.cpu_usage()));
@@ -96,15 +77,28 @@ fn get_cpu_percent() -> Vec<String> {
     cpu_usage
 }
 
-/// Function to get system metrics.
+/// Function to get CPU load averages.
 ///
 /// # Returns
 ///
-/// A `HashMap` containing the system metrics with CPU load average, memory and swap usage.
-fn get_system_metrics(system: &System) -> HashMap<String, serde_json::Value> {
+/// A `Value` object with the load averages for the last 1, 5 and 15 minutes.
+fn get_load_averages() -> serde_json::Value {
     // https://docs.rs/sysinfo/0.31.4/sysinfo/struct.System.html#method.load_average
     // Currently this doesn't work on Windows
     let load_avg = System::load_average();
+    serde_json::json!({
+        "m1": load_avg.one,
+        "m5": load_avg.five,
+        "m15": load_avg.fifteen,
+    })
+}
+
+/// Function to get memory metrics.
+///
+/// # Returns
+///
+/// A `HashMap` containing the memory and swap usage.
+fn get_memory_metrics(system: &System) -> HashMap<String, serde_json::Value> {
     let mut hash_vec = vec![
         (
             "memory_info".to_string(),
@@ -113,14 +107,6 @@ fn get_system_metrics(system: &System) -> HashMap<String, serde_json::Value> {
                 "used": system.used_memory(),
             }),
         ),
-        (
-            "load_averages".to_string(),
-            serde_json::json!({
-                "m1": load_avg.one,
-                "m5": load_avg.five,
-                "m15": load_avg.fifteen,
-            }),
-        ),
     ];
 
     let total_swap = system.total_swap();
@@ -137,25 +123,42 @@ fn get_system_metrics(system: &System) -> HashMap<String, serde_json::Value> {
 }
 
 
-/// Function to get the system information.
+/// Function to get the system information for the requested topics.
+///
+/// # Arguments
+///
+/// * `system` - A mutable reference to the persistent `System` struct owned by the collector.
+/// * `config` - Configuration data for the application.
+/// * `topics` - Topics that at least one of the subscribers is interested in.
 ///
 /// # Returns
 ///
 /// A `HashMap` containing the system information with basic system information and memory/storage information.
-pub fn system_resources(config: &squire::settings::Config) -> HashMap<String, serde_json::Value> {
-    let mut system = System::new_all();
-    system.refresh_all();
-    let mut system_metrics = get_system_metrics(&system);
-    let cpu_percent = get_cpu_percent();
-    let docker_stats = get_docker_stats().unwrap();
-    system_metrics.insert("cpu_usage".to_string(), serde_json::json!(cpu_percent));
-    system_metrics.insert("docker_stats".to_string(), serde_json::json!(docker_stats));
-    if !config.services.is_empty() {
-        let service_stats = get_service_stats(&mut system, &config);
+pub fn system_resources(
+    system: &mut System,
+    config: &squire::settings::Config,
+    topics: &HashSet<Topic>,
+) -> HashMap<String, serde_json::Value> {
+    let mut system_metrics = HashMap::new();
+    if topics.contains(&Topic::Cpu) {
+        let cpu_percent = get_cpu_percent(system);
+        system_metrics.insert("cpu_usage".to_string(), serde_json::json!(cpu_percent));
+        system_metrics.insert("load_averages".to_string(), get_load_averages());
+    }
+    if topics.contains(&Topic::Memory) {
+        system.refresh_memory();
+        system_metrics.extend(get_memory_metrics(system));
+    }
+    if topics.contains(&Topic::Docker) {
+        let docker_stats = get_docker_stats().unwrap();
+        system_metrics.insert("docker_stats".to_string(), serde_json::json!(docker_stats));
+    }
+    if topics.contains(&Topic::Services) && !config.services.is_empty() {
+        let service_stats = get_service_stats(system, config);
         system_metrics.insert("service_stats".to_string(), serde_json::json!(service_stats));
     }
-    if !config.processes.is_empty() {
-        let process_stats = get_process_stats(&mut system, &config);
+    if topics.contains(&Topic::Processes) && !config.processes.is_empty() {
+        let process_stats = get_process_stats(system, config);
         system_metrics.insert("process_stats".to_string(), serde_json::json!(process_stats));
     }
     system_metrics
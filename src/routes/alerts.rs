@@ -0,0 +1,31 @@
+use crate::{alerting, constant, routes, squire};
+use actix_web::{web, HttpRequest, HttpResponse};
+use fernet::MultiFernet;
+use std::sync::Arc;
+
+/// Handles the alerts endpoint, returning the alerts that are pending, firing or recently resolved.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `engine` - Alerting engine that evaluates the rules.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the list of alerts in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/alerts")]
+pub async fn alerts(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    engine: web::Data<Arc<alerting::engine::Engine>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    HttpResponse::Ok().json(engine.alerts())
+}
@@ -0,0 +1,305 @@
+use crate::resources::collector::Topic;
+use crate::{constant, resources, routes, squire};
+use actix_web::{web, HttpRequest, HttpResponse};
+use fernet::MultiFernet;
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::Disks;
+
+/// Maximum time to wait for the collector to sample the topics for a request.
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Verifies that the request is authenticated with a session or an API token, that has at least the given role.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `role` - Minimum role required to access the endpoint.
+///
+/// # Returns
+///
+/// Returns an `Option` with the `HttpResponse` to send, when the request is not allowed.
+fn deny(request: &HttpRequest,
+        fernet: &MultiFernet,
+        session_info: &constant::Session,
+        config: &squire::settings::Config,
+        role: squire::settings::Role) -> Option<HttpResponse> {
+    let auth_response = squire::authenticator::verify_token(request, config, fernet, session_info);
+    if !auth_response.ok {
+        return Some(routes::auth::unauthorized(auth_response));
+    }
+    if !auth_response.allows(role) {
+        return Some(routes::auth::forbidden(&auth_response, role));
+    }
+    None
+}
+
+/// Samples the topics and collects their keys into a JSON object, along with any additional fields.
+///
+/// # Arguments
+///
+/// * `collector` - Shared background collector that fans out the sampled frames.
+/// * `topics` - Topics to sample.
+/// * `extra` - Additional fields to include in the response.
+///
+/// # See Also
+///
+/// Keys that weren't sampled, like the swap on a server without one, or the services when none are
+/// configured to be monitored, are set to `null` so that every response has the same shape.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the sampled topics in JSON format.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+async fn sampled(collector: &Arc<resources::collector::Collector>,
+                 topics: &[Topic],
+                 extra: serde_json::Map<String, serde_json::Value>) -> HttpResponse {
+    let mut sample = match collector.sample(topics, SAMPLE_TIMEOUT).await {
+        Some(sample) => sample,
+        None => {
+            return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                detail: "timed out waiting for the collector".to_string()
+            });
+        }
+    };
+    let mut response = extra;
+    for key in topics.iter().flat_map(|topic| topic.keys()) {
+        response.insert(key.to_string(), sample.remove(*key).unwrap_or_default());
+    }
+    HttpResponse::Ok().json(response)
+}
+
+/// Handles the endpoints that don't exist within the API, with the same JSON error as the rest of it.
+///
+/// # Returns
+///
+/// * `404` - HttpResponse with an error message for an unknown endpoint.
+pub async fn not_found(request: HttpRequest) -> HttpResponse {
+    HttpResponse::NotFound().json(routes::auth::DetailError {
+        detail: format!("'{} {}' is not a valid endpoint", request.method(), request.path())
+    })
+}
+
+/// Handles the system endpoint, with the static information about the host.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the basic, memory and storage information in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/system")]
+pub async fn system(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, squire::settings::Role::Viewer) {
+        return response;
+    }
+    HttpResponse::Ok().json(resources::info::get_sys_info(&Disks::new_with_refreshed_list()))
+}
+
+/// Handles the disks endpoint, with the physical disks along with the usage and I/O of the mounts.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the disks, usage and I/O in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/disks")]
+pub async fn disks(request: HttpRequest,
+                   fernet: web::Data<Arc<MultiFernet>>,
+                   session_info: web::Data<Arc<constant::Session>>,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Disks.role()) {
+        return response;
+    }
+    let devices = resources::info::get_disk_devices(&Disks::new_with_refreshed_list());
+    let mut extra = serde_json::Map::new();
+    extra.insert("devices".to_string(), serde_json::json!(devices));
+    sampled(&collector, &[Topic::Disks, Topic::DiskIo], extra).await
+}
+
+/// Handles the network endpoint, with the IP addresses and the throughput of the interfaces.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the IP addresses and the interfaces in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/network")]
+pub async fn network(request: HttpRequest,
+                     fernet: web::Data<Arc<MultiFernet>>,
+                     session_info: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Network.role()) {
+        return response;
+    }
+    let mut extra = serde_json::Map::new();
+    extra.insert("addresses".to_string(), serde_json::json!(resources::network::get_network_info().await));
+    sampled(&collector, &[Topic::Network], extra).await
+}
+
+/// Handles the CPU endpoint, with the usage per core and the load averages.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the CPU usage in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/cpu")]
+pub async fn cpu(request: HttpRequest,
+                 fernet: web::Data<Arc<MultiFernet>>,
+                 session_info: web::Data<Arc<constant::Session>>,
+                 config: web::Data<Arc<squire::settings::Config>>,
+                 collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Cpu.role()) {
+        return response;
+    }
+    sampled(&collector, &[Topic::Cpu], serde_json::Map::new()).await
+}
+
+/// Handles the memory endpoint, with the memory and swap usage in bytes.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the memory usage in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/memory")]
+pub async fn memory(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Memory.role()) {
+        return response;
+    }
+    sampled(&collector, &[Topic::Memory], serde_json::Map::new()).await
+}
+
+/// Handles the docker endpoint, with the stats of the running containers.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the container stats in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an operator.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/docker")]
+pub async fn docker(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Docker.role()) {
+        return response;
+    }
+    sampled(&collector, &[Topic::Docker], serde_json::Map::new()).await
+}
+
+/// Handles the services endpoint, with the usage of the services configured to be monitored.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the service usage in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an operator.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/services")]
+pub async fn services(request: HttpRequest,
+                      fernet: web::Data<Arc<MultiFernet>>,
+                      session_info: web::Data<Arc<constant::Session>>,
+                      config: web::Data<Arc<squire::settings::Config>>,
+                      collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Services.role()) {
+        return response;
+    }
+    sampled(&collector, &[Topic::Services], serde_json::Map::new()).await
+}
+
+/// Handles the processes endpoint, with the usage of the processes configured to be monitored.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the process usage in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an operator.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/processes")]
+pub async fn processes(request: HttpRequest,
+                       fernet: web::Data<Arc<MultiFernet>>,
+                       session_info: web::Data<Arc<constant::Session>>,
+                       config: web::Data<Arc<squire::settings::Config>>,
+                       collector: web::Data<Arc<resources::collector::Collector>>) -> HttpResponse {
+    if let Some(response) = deny(&request, &fernet, &session_info, &config, Topic::Processes.role()) {
+        return response;
+    }
+    sampled(&collector, &[Topic::Processes], serde_json::Map::new()).await
+}
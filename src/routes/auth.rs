@@ -2,11 +2,12 @@ use std::sync::Arc;
 
 use actix_web::cookie::time::{Duration, OffsetDateTime};
 use actix_web::cookie::{Cookie, SameSite};
-use actix_web::http::StatusCode;
+use actix_web::http::{header, StatusCode};
 use actix_web::{web, HttpRequest, HttpResponse};
-use fernet::Fernet;
+use chrono::Utc;
+use fernet::MultiFernet;
 use minijinja;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{constant, squire};
 
@@ -22,7 +23,89 @@ pub struct DetailError {
     pub detail: String,
 }
 
-/// Handles the login endpoint, verifying credentials and creating session tokens.
+/// Struct for the query parameters accepted by the challenge endpoint.
+#[derive(Deserialize, Debug)]
+pub struct ChallengeParams {
+    username: String,
+}
+
+/// Struct for the query parameters accepted by the logout endpoint.
+///
+/// ## Fields
+///
+/// * `everywhere` - Flag to end all the sessions of the user, instead of just the current one.
+#[derive(Deserialize, Debug)]
+pub struct LogoutParams {
+    #[serde(default)]
+    everywhere: bool,
+}
+
+/// Struct for the response of the challenge endpoint.
+///
+/// ## Fields
+///
+/// * `params` - Algorithm and parameters to derive the key from the password.
+/// * `nonce` - Nonce to sign along with the login, which can only be used once.
+/// * `expires_in` - Time (in seconds) the nonce can be used for.
+#[derive(Serialize)]
+struct ChallengeResponse {
+    #[serde(flatten)]
+    params: squire::secure::HashParams,
+    nonce: String,
+    expires_in: i64,
+}
+
+/// Handles the challenge endpoint, returning the parameters to derive the key from the password.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `params` - Query parameters with the username.
+/// * `credentials` - Verifiers of the passwords, that the logins are checked against.
+/// * `nonces` - Nonces issued by the challenge endpoint, that haven't been used yet.
+///
+/// # See Also
+///
+/// - The client derives the Argon2id hash of the password with the salt and parameters of the stored verifier,
+///   and proves the knowledge of it with the login request. So the password never leaves the browser.
+/// - Usernames that don't exist receive the parameters of a fake hash, so they can't be told apart.
+/// - A nonce is issued for every challenge, which the login has to sign and can only be used once.
+/// - The nonces that haven't been used are capped for each IP address, so a client can't crowd out the others.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the algorithm and the parameters of the hash, along with the nonce in JSON format.
+/// * `429` - HttpResponse with an error message when the IP address has too many challenges outstanding.
+#[get("/challenge")]
+pub async fn challenge(request: HttpRequest,
+                       params: web::Query<ChallengeParams>,
+                       credentials: web::Data<Arc<squire::credentials::Credentials>>,
+                       nonces: web::Data<Arc<squire::nonces::Nonces>>) -> HttpResponse {
+    let ip = squire::middleware::client_ip(&request);
+    match nonces.issue(&params.username, &ip, Utc::now().timestamp()) {
+        Ok(nonce) => HttpResponse::Ok().json(ChallengeResponse {
+            params: credentials.params(&params.username),
+            nonce,
+            expires_in: squire::nonces::NONCE_TTL,
+        }),
+        Err(retry_after) => {
+            log::warn!("Too many outstanding challenges from '{}', refused for {}s", ip, retry_after);
+            HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                .json(DetailError {
+                    detail: format!("Too many login attempts, try again in {}s", retry_after)
+                })
+        }
+    }
+}
+
+/// Struct for representing a JSON Response when the login is waiting for the second factor.
+#[derive(Serialize)]
+struct MfaRequiredResponse {
+    mfa_required: bool,
+}
+
+/// Constructs the response for a verified login, setting the `session_token` cookie for a new session.
 ///
 /// # Arguments
 ///
@@ -30,38 +113,32 @@ pub struct DetailError {
 /// * `config` - Configuration data for the application.
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `username` - Username of the authenticated user.
 ///
 /// # Returns
 ///
-/// * `200` - HttpResponse with a `session_token` and redirect URL to the `/monitor` entrypoint.
-/// * `401` - HttpResponse with an error message for failed authentication.
-#[post("/login")]
-pub async fn login(request: HttpRequest,
-                   config: web::Data<Arc<squire::settings::Config>>,
-                   fernet: web::Data<Arc<Fernet>>,
-                   session: web::Data<Arc<constant::Session>>) -> HttpResponse {
-    let verified = squire::authenticator::verify_login(&request, &config, &session);
-    if let Err(err) = verified {
-        let err_message = err.to_string();
-        log::warn!("Error response::{}", err_message);
-        return HttpResponse::Unauthorized().json(DetailError {
-            detail: err_message
-        });
-    }
-
-    let mapped = verified.unwrap();
+/// Returns an `HttpResponse` with a `session_token` and redirect URL to the `/monitor` entrypoint.
+pub fn session_response(request: &HttpRequest,
+                        config: &squire::settings::Config,
+                        fernet: &MultiFernet,
+                        session: &constant::Session,
+                        username: &str) -> HttpResponse {
+    let mapped = squire::authenticator::create_session(request, config, session, username);
     let payload = serde_json::to_string(&mapped).unwrap();
     let encrypted_payload = fernet.encrypt(payload.as_bytes());
 
     let cookie_duration = Duration::seconds(config.session_duration);
     let expiration = OffsetDateTime::now_utc() + cookie_duration;
+    // Path is explicit, since the cookie is also issued by '/login/mfa' whose default path is '/login'
     let cookie = Cookie::build("session_token", encrypted_payload)
+        .path("/")
         .http_only(true)
+        .secure(squire::middleware::is_secure(request))
         .same_site(SameSite::Strict)
         .max_age(cookie_duration)
         .expires(expiration)
         .finish();
-    log::info!("Session for '{}' will be valid until {}", mapped.get("username").unwrap(), expiration);
+    log::info!("Session for '{}' will be valid until {}", username, expiration);
 
     let mut response = HttpResponse::Ok().json(RedirectResponse {
         redirect_url: "/monitor".to_string(),
@@ -70,23 +147,95 @@ pub async fn login(request: HttpRequest,
     response
 }
 
+/// Handles the login endpoint, verifying credentials and creating session tokens.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `credentials` - Verifiers of the passwords, that the logins are checked against.
+/// * `lockout` - Tracker for the failed login attempts per IP address and per username.
+/// * `nonces` - Nonces issued by the challenge endpoint, that haven't been used yet.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # See Also
+///
+/// For a user enrolled in two-factor authentication, the session is only created once the code is entered
+/// at `/login/mfa`, with the `mfa_token` cookie that holds the login until then.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with a `session_token` and redirect URL to the `/monitor` entrypoint,
+///   or with an `mfa_token` when the code is required.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `429` - HttpResponse with an error message when the IP address or the username is locked out.
+#[post("/login")]
+#[allow(clippy::too_many_arguments)]
+pub async fn login(request: HttpRequest,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   fernet: web::Data<Arc<MultiFernet>>,
+                   session: web::Data<Arc<constant::Session>>,
+                   credentials: web::Data<Arc<squire::credentials::Credentials>>,
+                   lockout: web::Data<Arc<squire::lockout::Lockout>>,
+                   nonces: web::Data<Arc<squire::nonces::Nonces>>,
+                   mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let username = match squire::authenticator::verify_login(&request, &credentials, &lockout, &nonces) {
+        Ok(username) => username,
+        Err(err) => {
+            log::warn!("Error response::{}", err.detail);
+            if let Some(retry_after) = err.retry_after {
+                return HttpResponse::TooManyRequests()
+                    .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                    .json(DetailError { detail: err.detail });
+            }
+            return HttpResponse::Unauthorized().json(DetailError {
+                detail: err.detail
+            });
+        }
+    };
+    if !mfa.is_enrolled(&username) {
+        return session_response(&request, &config, &fernet, &session, &username);
+    }
+    log::info!("Password verified for '{}', waiting for the two-factor code", username);
+    let token = mfa.begin_login(&username, Utc::now().timestamp());
+    let cookie = Cookie::build("mfa_token", token)
+        .path("/login/mfa")
+        .http_only(true)
+        .secure(squire::middleware::is_secure(&request))
+        .same_site(SameSite::Strict)
+        .max_age(Duration::seconds(squire::mfa::PENDING_TTL))
+        .finish();
+    let mut response = HttpResponse::Ok().json(MfaRequiredResponse { mfa_required: true });
+    response.add_cookie(&cookie).unwrap();
+    response
+}
+
 /// Handles the logout endpoint, logging out the user and rendering the appropriate HTML page.
 ///
 /// # Arguments
 ///
 /// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `params` - Query parameters to log out of all the sessions.
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` to handle sessions.
 /// * `metadata` - Struct containing metadata of the application.
 /// * `config` - Configuration data for the application.
 /// * `template` - Configuration container for the loaded templates.
 ///
+/// # See Also
+///
+/// The session is destroyed on the server, so a copy of the `session_token` cookie can't be used after logging out.
+/// Open websocket and event stream connections of the session are closed by their own session checks.
+///
 /// # Returns
 ///
 /// Returns an `HTTPResponse` with the cookie for `session_token` reset if available.
 #[get("/logout")]
 pub async fn logout(request: HttpRequest,
-                    fernet: web::Data<Arc<Fernet>>,
+                    params: web::Query<LogoutParams>,
+                    fernet: web::Data<Arc<MultiFernet>>,
                     session: web::Data<Arc<constant::Session>>,
                     metadata: web::Data<Arc<constant::MetaData>>,
                     config: web::Data<Arc<squire::settings::Config>>,
@@ -105,12 +254,26 @@ pub async fn logout(request: HttpRequest,
     }
 
     if auth_response.ok {
+        let detail = if params.everywhere {
+            let revoked = session.revoke_user(&auth_response.username);
+            log::info!("{} logged out of {} sessions", auth_response.username, revoked);
+            "You have been logged out of all sessions successfully."
+        } else {
+            if let Some(id) = &auth_response.session {
+                session.revoke(id);
+            }
+            "You have been logged out successfully."
+        };
         rendered = logout_template.render(minijinja::context!(
             version => metadata.pkg_version,
-            detail => "You have been logged out successfully."
+            nonce => squire::middleware::csp_nonce(&request),
+            detail => detail
         )).unwrap();
 
         let mut cookie = Cookie::new("session_token", "");
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+        cookie.set_secure(squire::middleware::is_secure(&request));
         cookie.set_same_site(SameSite::Strict);
         cookie.make_removal();
         response.cookie(cookie);
@@ -118,6 +281,7 @@ pub async fn logout(request: HttpRequest,
         log::debug!("{} - {}", auth_response.detail, host);
         rendered = logout_template.render(minijinja::context!(
                 version => metadata.pkg_version,
+                nonce => squire::middleware::csp_nonce(&request),
                 detail => "You are not logged in. Please click the button below to proceed.",
                 show_login => true
             )).unwrap();
@@ -147,6 +311,7 @@ pub async fn error(request: HttpRequest,
             .content_type("text/html; charset=utf-8")
             .body(session.render(minijinja::context!(
                 version => metadata.pkg_version,
+                nonce => squire::middleware::csp_nonce(&request),
                 reason => detail.value()
             )).unwrap());
     }
@@ -157,6 +322,7 @@ pub async fn error(request: HttpRequest,
         .content_type("text/html; charset=utf-8")
         .body(error.render(minijinja::context!(
             version => metadata.pkg_version,
+            nonce => squire::middleware::csp_nonce(&request),
             title => "LOGIN FAILED",
             description => "USER ERROR - REPLACE USER",
             help => r"Forgot Password?\n\nRelax and try to remember your password.",
@@ -169,19 +335,20 @@ pub async fn error(request: HttpRequest,
 ///
 /// # Arguments
 ///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
 /// * `auth_response` - The authentication response containing details of the failure.
-/// * `config` - Configuration data for the application.
 ///
 /// # Returns
 ///
 /// Returns an `HttpResponse` with a redirect, setting a cookie with the failure detail.
-pub fn failed_auth(auth_response: squire::authenticator::AuthToken) -> HttpResponse {
+pub fn failed_auth(request: &HttpRequest, auth_response: squire::authenticator::AuthToken) -> HttpResponse {
     let mut response = HttpResponse::build(StatusCode::FOUND);
     let detail = auth_response.detail;
     let age = Duration::new(3, 0);
     let cookie = Cookie::build("detail", detail)
         .path("/error")
         .http_only(true)
+        .secure(squire::middleware::is_secure(request))
         .same_site(SameSite::Strict)
         .max_age(age)
         .finish();
@@ -189,3 +356,52 @@ pub fn failed_auth(auth_response: squire::authenticator::AuthToken) -> HttpRespo
     response.append_header(("Location", "/error"));
     response.finish()
 }
+
+/// Constructs a JSON response for failed authentication, for endpoints that are not rendered in a browser.
+///
+/// # Arguments
+///
+/// * `auth_response` - The authentication response containing details of the failure.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with a `401` status code and the reason in JSON format.
+pub fn unauthorized(auth_response: squire::authenticator::AuthToken) -> HttpResponse {
+    log::warn!("Error response::{}", auth_response.detail);
+    HttpResponse::Unauthorized().json(DetailError {
+        detail: auth_response.detail
+    })
+}
+
+/// Constructs a JSON response for an authenticated user, whose role is not allowed to access the endpoint.
+///
+/// # Arguments
+///
+/// * `auth_response` - The authentication response of the user.
+/// * `required` - Minimum role required to access the endpoint.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with a `403` status code and the reason in JSON format.
+pub fn forbidden(auth_response: &squire::authenticator::AuthToken, required: squire::settings::Role) -> HttpResponse {
+    log::warn!("'{}' was denied access, since the '{:?}' role is required", auth_response.username, required);
+    HttpResponse::Forbidden().json(DetailError {
+        detail: format!("'{:?}' role is required", required).to_lowercase()
+    })
+}
+
+/// Constructs a JSON response for a machine client, calling an endpoint that is only meant for a login session.
+///
+/// # Arguments
+///
+/// * `auth_response` - The authentication response of the API token.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with a `403` status code and the reason in JSON format.
+pub fn session_required(auth_response: &squire::authenticator::AuthToken) -> HttpResponse {
+    log::warn!("'{}' was denied access, since a login session is required", auth_response.username);
+    HttpResponse::Forbidden().json(DetailError {
+        detail: "a login session is required, API tokens are not accepted".to_string()
+    })
+}
@@ -1,5 +1,5 @@
-use crate::{constant, squire};
-use actix_web::http::StatusCode;
+use crate::{constant, squire, templates};
+use actix_web::http::{header, StatusCode};
 use actix_web::{web, HttpRequest, HttpResponse};
 use std::sync::Arc;
 
@@ -36,5 +36,55 @@ pub async fn root(request: HttpRequest,
     let index = template.get_template("index").unwrap();
     HttpResponse::build(StatusCode::OK)
         .content_type("text/html; charset=utf-8")
-        .body(index.render(minijinja::context!(version => &metadata.pkg_version)).unwrap())
+        .body(index.render(minijinja::context!(
+            version => &metadata.pkg_version,
+            nonce => squire::middleware::csp_nonce(&request)
+        )).unwrap())
+}
+
+/// Handles the script that derives the Argon2id hash of the password, for the login on the index page.
+///
+/// # See Also
+///
+/// The script is served from the binary, instead of a CDN that would be trusted with the password.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with the script as its body.
+#[get("/static/argon2.js")]
+pub async fn argon2() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/javascript; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "public, max-age=86400"))
+        .body(templates::argon2::get_content())
+}
+
+/// Handles every request on the plain HTTP listener, redirecting it to the same path over HTTPS.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with a status of 308 (Permanent Redirect), which preserves the method and the body.
+pub async fn https_redirect(request: HttpRequest,
+                            config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    let connection_info = request.connection_info();
+    let host = connection_info.host();
+    // Port is stripped from the host header, while brackets of an IPv6 address are retained
+    let hostname = match host.rsplit_once(':') {
+        Some((hostname, port)) if port.chars().all(|char| char.is_ascii_digit()) => hostname,
+        _ => host,
+    };
+    let path = request.uri().path_and_query().map(|path| path.as_str()).unwrap_or("/");
+    let location = if config.port == 443 {
+        format!("https://{}{}", hostname, path)
+    } else {
+        format!("https://{}:{}{}", hostname, config.port, path)
+    };
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
 }
@@ -0,0 +1,77 @@
+use crate::{constant, resources, routes, squire};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use fernet::MultiFernet;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Default time range (in seconds) for a query, when the start of the range is not specified.
+const DEFAULT_RANGE: i64 = 3600;
+
+/// Struct for the query parameters accepted by the history endpoint.
+///
+/// ## Fields
+///
+/// * `metric` - Name of the metric to retrieve.
+/// * `from` - Epoch timestamp (in seconds) for the start of the range. Defaults to an hour before `to`.
+/// * `to` - Epoch timestamp (in seconds) for the end of the range. Defaults to the current time.
+/// * `step` - Interval (in seconds) between two points. Defaults to the resolution of the store.
+#[derive(Deserialize, Debug)]
+pub struct HistoryParams {
+    metric: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    step: Option<i64>,
+}
+
+/// Handles the history endpoint, returning the time series of a metric in JSON format.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `params` - Query parameters for the metric and the time range.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `history` - Time series store that is fed by the collector.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the metric, step and a list of `[timestamp, value]` points.
+/// * `400` - HttpResponse with an error message for an invalid query.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `404` - HttpResponse with an error message listing the available metrics.
+#[get("/api/history")]
+pub async fn history(request: HttpRequest,
+                     params: web::Query<HistoryParams>,
+                     fernet: web::Data<Arc<MultiFernet>>,
+                     session_info: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     history: web::Data<Arc<resources::history::History>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    let metric = match &params.metric {
+        Some(metric) => metric,
+        None => {
+            return HttpResponse::BadRequest().json(routes::auth::DetailError {
+                detail: "'metric' is a required parameter".to_string()
+            });
+        }
+    };
+    let to = params.to.unwrap_or(Utc::now().timestamp());
+    let from = params.from.unwrap_or(to - DEFAULT_RANGE);
+    let step = params.step.unwrap_or(1);
+    if from > to || step < 1 {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: "'from' must not be later than 'to', and 'step' must be a positive number".to_string()
+        });
+    }
+    match history.query(metric, from, to, step) {
+        Some(result) => HttpResponse::Ok().json(result),
+        None => HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' is not a known metric, available metrics: {}", metric, history.metrics().join(", "))
+        }),
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{constant, routes, squire};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use fernet::MultiFernet;
+use std::sync::Arc;
+
+/// Handles the lockouts endpoint, listing the IP addresses and usernames with failed login attempts.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `lockout` - Tracker for the failed login attempts per IP address and per username.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the failed login attempts in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an admin.
+#[get("/api/lockouts")]
+pub async fn list(request: HttpRequest,
+                  fernet: web::Data<Arc<MultiFernet>>,
+                  session_info: web::Data<Arc<constant::Session>>,
+                  config: web::Data<Arc<squire::settings::Config>>,
+                  lockout: web::Data<Arc<squire::lockout::Lockout>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if !auth_response.allows(squire::settings::Role::Admin) {
+        return routes::auth::forbidden(&auth_response, squire::settings::Role::Admin);
+    }
+    HttpResponse::Ok().json(lockout.entries(Utc::now().timestamp()))
+}
+
+/// Handles the unlock of an IP address or a username, clearing its failed login attempts.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - Kind of the subject (`ip` or `username`) and the subject itself.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `lockout` - Tracker for the failed login attempts per IP address and per username.
+///
+/// # Returns
+///
+/// * `204` - HttpResponse with no content, when the subject is unlocked.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an admin.
+/// * `404` - HttpResponse with an error message when the subject has no failed login attempts.
+#[delete("/api/lockouts/{kind}/{subject}")]
+pub async fn unlock(request: HttpRequest,
+                    path: web::Path<(squire::lockout::Kind, String)>,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    lockout: web::Data<Arc<squire::lockout::Lockout>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if !auth_response.allows(squire::settings::Role::Admin) {
+        return routes::auth::forbidden(&auth_response, squire::settings::Role::Admin);
+    }
+    let (kind, subject) = path.into_inner();
+    if lockout.unlock(kind, &subject) {
+        log::info!("{:?} '{}' unlocked by '{}'", kind, subject, auth_response.username);
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' has no failed login attempts", subject)
+        })
+    }
+}
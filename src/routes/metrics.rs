@@ -0,0 +1,91 @@
+use crate::{resources, routes, squire};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Maximum time to wait for the collector to sample all the topics for a scrape.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Checks the bearer token in the `Authorization` header, when a `metrics_token` is configured.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+///
+/// # See Also
+///
+/// An API token is accepted in place of the `metrics_token`, in which case only the topics allowed
+/// for the role of the token are exposed.
+///
+/// # Returns
+///
+/// Returns an `Option` with the role that the scrape is allowed as, or `None` when it isn't allowed.
+fn authorized(request: &HttpRequest, config: &squire::settings::Config) -> Option<squire::settings::Role> {
+    // Scrapes with the metrics_token, or without one configured, have always exposed every topic
+    let token = match &config.metrics_token {
+        Some(token) => token,
+        None => return Some(squire::settings::Role::Admin),
+    };
+    let bearer = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))?;
+    // Hashes are compared instead of the tokens, so that the time taken doesn't reveal a matching prefix
+    if squire::secure::calculate_hash(bearer.to_string()) == squire::secure::calculate_hash(token.to_string()) {
+        return Some(squire::settings::Role::Admin);
+    }
+    let auth_response = squire::authenticator::verify_bearer(request, config, bearer);
+    if auth_response.ok { auth_response.role } else { None }
+}
+
+/// Handles the metrics endpoint, exposing all the sampled resources in Prometheus text exposition format.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+/// * `lockout` - Tracker for the failed login attempts, whose counters are exposed along with the resources.
+///
+/// # See Also
+///
+/// Every scrape registers a temporary subscription for all the allowed topics, since expensive topics like docker,
+/// services and processes are only sampled on demand.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the metrics in text exposition format.
+/// * `401` - HttpResponse with an error message for a missing or invalid bearer token.
+/// * `503` - HttpResponse with an error message when the collector didn't respond in time.
+#[get("/metrics")]
+pub async fn metrics(request: HttpRequest,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     collector: web::Data<Arc<resources::collector::Collector>>,
+                     lockout: web::Data<Arc<squire::lockout::Lockout>>) -> HttpResponse {
+    let role = match authorized(&request, &config) {
+        Some(role) => role,
+        None => {
+            log::warn!("Unauthorized scrape from '{}'", request.connection_info().host());
+            return HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json(routes::auth::DetailError {
+                    detail: "a valid bearer token is required".to_string()
+                });
+        }
+    };
+    let topics = resources::collector::Topic::allowed(role);
+    let sample = match collector.sample(&topics, SCRAPE_TIMEOUT).await {
+        Some(sample) => sample,
+        None => {
+            return HttpResponse::ServiceUnavailable().json(routes::auth::DetailError {
+                detail: "timed out waiting for the collector".to_string()
+            });
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(resources::exporter::render(&sample) +
+            &resources::exporter::render_logins(&lockout.stats(Utc::now().timestamp())))
+}
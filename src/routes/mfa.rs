@@ -0,0 +1,353 @@
+use crate::{constant, routes, squire};
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use fernet::MultiFernet;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Name of the service shown in the authenticator apps.
+const ISSUER: &str = "SysMonk";
+
+/// Struct for the payload with a TOTP code or a backup code.
+///
+/// ## Fields
+///
+/// * `code` - TOTP code generated by the authenticator app, or one of the backup codes.
+#[derive(Deserialize, Debug)]
+pub struct CodePayload {
+    code: String,
+}
+
+/// Struct for the response of an enrollment, to be added to the authenticator app.
+///
+/// ## Fields
+///
+/// * `secret` - Secret encoded as base32, for the apps that can't scan a QR code.
+/// * `uri` - URI to render as a QR code.
+/// * `expires_in` - Time (in seconds) to confirm the enrollment with a code.
+#[derive(Serialize, Debug)]
+struct EnrollmentResponse {
+    secret: String,
+    uri: String,
+    expires_in: i64,
+}
+
+/// Struct for the response of a confirmed enrollment.
+///
+/// ## Fields
+///
+/// * `backup_codes` - Codes that can be used once each in place of a TOTP code, which are never shown again.
+#[derive(Serialize, Debug)]
+struct BackupCodesResponse {
+    backup_codes: Vec<String>,
+}
+
+/// Parses a JSON payload, which browsers don't allow cross-origin forms to send.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `body` - Raw body of the request.
+///
+/// # Returns
+///
+/// Returns a `Result` with the payload, or the `HttpResponse` to send for an invalid payload.
+fn json_payload<T: DeserializeOwned>(request: &HttpRequest, body: &[u8]) -> Result<T, Box<HttpResponse>> {
+    let is_json = request.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return Err(Box::new(HttpResponse::UnsupportedMediaType().json(routes::auth::DetailError {
+            detail: "payload must be sent as 'application/json'".to_string()
+        })));
+    }
+    serde_json::from_slice(body).map_err(|err| {
+        Box::new(HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: format!("invalid payload: {}", err)
+        }))
+    })
+}
+
+/// Handles the second step of a login, verifying the code of a user enrolled in two-factor authentication.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `body` - Raw body of the request, containing the `CodePayload` in JSON format.
+/// * `config` - Configuration data for the application.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `lockout` - Tracker for the failed login attempts per IP address and per username.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # See Also
+///
+/// Invalid codes count as failed logins, and the password has to be entered again after a few of them.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with a `session_token` and redirect URL to the `/monitor` entrypoint.
+/// * `400` - HttpResponse with an error message for an invalid payload.
+/// * `401` - HttpResponse with an error message for an invalid code, or a login that has expired.
+/// * `415` - HttpResponse with an error message when the body is not JSON.
+/// * `429` - HttpResponse with an error message when the IP address or the username is locked out.
+#[post("/login/mfa")]
+pub async fn login(request: HttpRequest,
+                   body: web::Bytes,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   fernet: web::Data<Arc<MultiFernet>>,
+                   session: web::Data<Arc<constant::Session>>,
+                   lockout: web::Data<Arc<squire::lockout::Lockout>>,
+                   mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let now = Utc::now().timestamp();
+    let token = request.cookie("mfa_token").map(|cookie| cookie.value().to_string()).unwrap_or_default();
+    let username = match mfa.pending_login(&token, now) {
+        Some(username) => username,
+        None => {
+            return HttpResponse::Unauthorized().json(routes::auth::DetailError {
+                detail: "Login has expired, please enter your password again".to_string()
+            });
+        }
+    };
+    let payload: CodePayload = match json_payload(&request, &body) {
+        Ok(payload) => payload,
+        Err(response) => return *response,
+    };
+    let ip = squire::middleware::client_ip(&request);
+    if let Some(retry_after) = lockout.retry_after(&ip, &username, now) {
+        return HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .json(routes::auth::DetailError {
+                detail: format!("Too many failed attempts, try again in {}s", retry_after)
+            });
+    }
+    if !mfa.verify(&username, &payload.code, now) {
+        log::warn!("{} entered an invalid two-factor code from '{}'", username, ip);
+        lockout.failure(&ip, &username, now);
+        mfa.fail_login(&token);
+        return HttpResponse::Unauthorized().json(routes::auth::DetailError {
+            detail: "Invalid two-factor code".to_string()
+        });
+    }
+    mfa.finish_login(&token);
+    lockout.success(&username);
+    let mut response = routes::auth::session_response(&request, &config, &fernet, &session, &username);
+    let mut cookie = Cookie::new("mfa_token", "");
+    cookie.set_path("/login/mfa");
+    cookie.set_http_only(true);
+    cookie.set_secure(squire::middleware::is_secure(&request));
+    cookie.set_same_site(SameSite::Strict);
+    cookie.make_removal();
+    response.add_cookie(&cookie).unwrap();
+    response
+}
+
+/// Handles the two-factor authentication status of the authenticated user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the status in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when authenticated with an API token.
+#[get("/api/mfa")]
+pub async fn status(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if auth_response.session.is_none() {
+        return routes::auth::session_required(&auth_response);
+    }
+    HttpResponse::Ok().json(mfa.status(&auth_response.username))
+}
+
+/// Handles the start of an enrollment for the authenticated user, generating a new secret.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the secret and the URI for the authenticator app in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when authenticated with an API token.
+/// * `409` - HttpResponse with an error message when the user has already enrolled.
+#[post("/api/mfa/enroll")]
+pub async fn enroll(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if auth_response.session.is_none() {
+        return routes::auth::session_required(&auth_response);
+    }
+    // Enrolling again would replace the secret without proving the possession of the current one
+    if mfa.is_enrolled(&auth_response.username) {
+        return HttpResponse::Conflict().json(routes::auth::DetailError {
+            detail: "two-factor authentication is already enabled, disable it first".to_string()
+        });
+    }
+    let secret = mfa.begin_enrollment(&auth_response.username, Utc::now().timestamp());
+    HttpResponse::Ok().json(EnrollmentResponse {
+        uri: squire::totp::provisioning_uri(ISSUER, &auth_response.username, &secret),
+        secret,
+        expires_in: squire::mfa::PENDING_TTL,
+    })
+}
+
+/// Handles the confirmation of an enrollment, with a code generated by the authenticator app.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `body` - Raw body of the request, containing the `CodePayload` in JSON format.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # Returns
+///
+/// * `201` - HttpResponse with the backup codes in JSON format.
+/// * `400` - HttpResponse with an error message for an invalid payload or code.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when authenticated with an API token.
+/// * `415` - HttpResponse with an error message when the body is not JSON.
+#[post("/api/mfa/confirm")]
+pub async fn confirm(request: HttpRequest,
+                     body: web::Bytes,
+                     fernet: web::Data<Arc<MultiFernet>>,
+                     session_info: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if auth_response.session.is_none() {
+        return routes::auth::session_required(&auth_response);
+    }
+    let payload: CodePayload = match json_payload(&request, &body) {
+        Ok(payload) => payload,
+        Err(response) => return *response,
+    };
+    match mfa.confirm_enrollment(&auth_response.username, &payload.code, Utc::now().timestamp()) {
+        Ok(backup_codes) => {
+            log::info!("Two-factor authentication enabled for '{}'", auth_response.username);
+            HttpResponse::Created().json(BackupCodesResponse { backup_codes })
+        }
+        Err(err) => HttpResponse::BadRequest().json(routes::auth::DetailError { detail: err.to_string() }),
+    }
+}
+
+/// Handles disabling two-factor authentication for the authenticated user, which requires a valid code.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `body` - Raw body of the request, containing the `CodePayload` in JSON format.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # Returns
+///
+/// * `204` - HttpResponse with no content, when two-factor authentication is disabled.
+/// * `400` - HttpResponse with an error message for an invalid payload or code.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when authenticated with an API token.
+/// * `415` - HttpResponse with an error message when the body is not JSON.
+#[delete("/api/mfa")]
+pub async fn disable(request: HttpRequest,
+                     body: web::Bytes,
+                     fernet: web::Data<Arc<MultiFernet>>,
+                     session_info: web::Data<Arc<constant::Session>>,
+                     config: web::Data<Arc<squire::settings::Config>>,
+                     mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if auth_response.session.is_none() {
+        return routes::auth::session_required(&auth_response);
+    }
+    let payload: CodePayload = match json_payload(&request, &body) {
+        Ok(payload) => payload,
+        Err(response) => return *response,
+    };
+    if !mfa.verify(&auth_response.username, &payload.code, Utc::now().timestamp()) {
+        return HttpResponse::BadRequest().json(routes::auth::DetailError {
+            detail: "invalid code".to_string()
+        });
+    }
+    mfa.remove(&auth_response.username);
+    log::info!("Two-factor authentication disabled by '{}'", auth_response.username);
+    HttpResponse::NoContent().finish()
+}
+
+/// Handles the reset of two-factor authentication for a user who lost their authenticator and backup codes.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - Username of the user.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `mfa` - Two-factor authentication of the users.
+///
+/// # Returns
+///
+/// * `204` - HttpResponse with no content, when two-factor authentication is reset.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an admin.
+/// * `404` - HttpResponse with an error message when the user hasn't enrolled.
+#[delete("/api/mfa/{username}")]
+pub async fn reset(request: HttpRequest,
+                   path: web::Path<String>,
+                   fernet: web::Data<Arc<MultiFernet>>,
+                   session_info: web::Data<Arc<constant::Session>>,
+                   config: web::Data<Arc<squire::settings::Config>>,
+                   mfa: web::Data<Arc<squire::mfa::Mfa>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if !auth_response.allows(squire::settings::Role::Admin) {
+        return routes::auth::forbidden(&auth_response, squire::settings::Role::Admin);
+    }
+    let username = path.into_inner();
+    if mfa.remove(&username) {
+        log::warn!("Two-factor authentication for '{}' reset by '{}'", username, auth_response.username);
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("'{}' hasn't enabled two-factor authentication", username)
+        })
+    }
+}
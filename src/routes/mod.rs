@@ -1,11 +1,31 @@
 /// Module for `/` and `/health` entrypoint.
 pub mod basics;
-/// Module for `/login`, `/logout` and `/error` entrypoint.
+/// Module for `/challenge`, `/login`, `/logout` and `/error` entrypoint.
 pub mod auth;
 /// Module for `/monitor` entrypoint.
 pub mod monitor;
 /// Module for `/ws/system` entrypoint.
 pub mod websocket;
+/// Module for `/sse/system` entrypoint.
+pub mod sse;
+/// Module for `/api/history` entrypoint.
+pub mod history;
+/// Module for `/metrics` entrypoint.
+pub mod metrics;
+/// Module for `/api/alerts` entrypoint.
+pub mod alerts;
+/// Module for `/api/silences` entrypoint.
+pub mod silences;
+/// Module for `/api/sessions` entrypoint.
+pub mod sessions;
+/// Module for `/api/lockouts` entrypoint.
+pub mod lockouts;
+/// Module for `/login/mfa` and `/api/mfa` entrypoint.
+pub mod mfa;
+/// Module for `/api/tokens` entrypoint.
+pub mod tokens;
+/// Module for `/api/v1` entrypoint.
+pub mod api;
 
 use actix_web::web;
 
@@ -17,3 +37,23 @@ use actix_web::web;
 pub fn configure_websocket(cfg: &mut web::ServiceConfig) {
     cfg.service(websocket::echo);
 }
+
+/// Configures the versioned JSON API, where unknown endpoints are answered with a JSON error.
+///
+/// # Arguments
+///
+/// * `cfg` - A mutable reference to the Actix web `ServiceConfig` object.
+pub fn configure_api(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/v1")
+            .service(api::system)
+            .service(api::disks)
+            .service(api::network)
+            .service(api::cpu)
+            .service(api::memory)
+            .service(api::docker)
+            .service(api::services)
+            .service(api::processes)
+            .default_service(web::to(api::not_found))
+    );
+}
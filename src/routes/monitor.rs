@@ -1,7 +1,7 @@
-use crate::{constant, legacy, resources, routes, squire};
+use crate::{constant, resources, routes, squire};
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpRequest, HttpResponse};
-use fernet::Fernet;
+use fernet::MultiFernet;
 use std::sync::Arc;
 use sysinfo::Disks;
 
@@ -21,14 +21,18 @@ use sysinfo::Disks;
 /// Returns an `HTTPResponse` with the cookie for `session_token` reset if available.
 #[get("/monitor")]
 pub async fn monitor(request: HttpRequest,
-                     fernet: web::Data<Arc<Fernet>>,
+                     fernet: web::Data<Arc<MultiFernet>>,
                      session: web::Data<Arc<constant::Session>>,
                      metadata: web::Data<Arc<constant::MetaData>>,
                      config: web::Data<Arc<squire::settings::Config>>,
                      template: web::Data<Arc<minijinja::Environment<'static>>>) -> HttpResponse {
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session);
     if !auth_response.ok {
-        return routes::auth::failed_auth(auth_response);
+        return routes::auth::failed_auth(&request, auth_response);
+    }
+    // Dashboard relies on the session cookie for its connections, which an API token doesn't have
+    if auth_response.session.is_none() {
+        return routes::auth::session_required(&auth_response);
     }
     let monitor_template = template.get_template("monitor").unwrap();
     let mut response = HttpResponse::build(StatusCode::OK);
@@ -39,19 +43,7 @@ pub async fn monitor(request: HttpRequest,
     let disks = Disks::new_with_refreshed_list();
 
     let sys_info_map = resources::info::get_sys_info(&disks);
-    let legacy_disk_info = legacy::disks::get_all_disks();
-
-    // legacy functions have a mechanism to check for physical devices, so it takes precedence
-    let has_name_and_size = !legacy_disk_info.is_empty() &&
-        legacy_disk_info.iter().all(|disk| {
-            disk.contains_key("Name") && disk.contains_key("Size")
-        });
-    let sys_info_disks = if has_name_and_size {
-        log::debug!("Using legacy methods for disks!");
-        legacy_disk_info
-    } else {
-        resources::info::get_disks(&disks)
-    };
+    let sys_info_disks = resources::info::get_disk_devices(&disks);
 
     let sys_info_network = resources::network::get_network_info().await;
 
@@ -60,11 +52,15 @@ pub async fn monitor(request: HttpRequest,
 
     let rendered = monitor_template.render(minijinja::context!(
         version => metadata.pkg_version,
+        nonce => squire::middleware::csp_nonce(&request),
         logout => "/logout",
         sys_info_basic => sys_info_basic,
         sys_info_mem_storage => sys_info_mem_storage,
         sys_info_network => sys_info_network,
-        sys_info_disks => sys_info_disks
+        sys_info_disks => sys_info_disks,
+        topics => resources::collector::Topic::allowed(auth_response.role.unwrap_or(squire::settings::Role::Viewer)),
+        can_silence => auth_response.allows(squire::settings::Role::Operator),
+        is_admin => auth_response.allows(squire::settings::Role::Admin)
     )).unwrap();
     response.body(rendered)
 }
@@ -0,0 +1,97 @@
+use crate::{constant, routes, squire};
+use actix_web::{web, HttpRequest, HttpResponse};
+use fernet::MultiFernet;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Struct for the response of the sessions endpoint.
+///
+/// ## Fields
+///
+/// * `current` - Identifier of the session that made the request.
+/// * `sessions` - Sessions of the user, or the sessions of all users for an admin.
+#[derive(Serialize, Debug)]
+struct SessionsResponse {
+    current: Option<String>,
+    sessions: Vec<constant::SessionRecord>,
+}
+
+/// Handles the sessions endpoint, listing the active sessions.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+///
+/// # See Also
+///
+/// Admins receive the sessions of all users, while everyone else only receives their own.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the sessions in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/sessions")]
+pub async fn list(request: HttpRequest,
+                  fernet: web::Data<Arc<MultiFernet>>,
+                  session_info: web::Data<Arc<constant::Session>>,
+                  config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    let sessions = if auth_response.allows(squire::settings::Role::Admin) {
+        session_info.list(None)
+    } else {
+        session_info.list(Some(&auth_response.username))
+    };
+    HttpResponse::Ok().json(SessionsResponse {
+        current: auth_response.session,
+        sessions,
+    })
+}
+
+/// Handles the revocation of a session, which logs out the browser that holds it.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - Identifier of the session.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+///
+/// # See Also
+///
+/// Admins can revoke any session, while everyone else can only revoke their own.
+/// Sessions of other users are reported as missing, so their identifiers can't be probed.
+///
+/// # Returns
+///
+/// * `204` - HttpResponse with no content, when the session is revoked.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `404` - HttpResponse with an error message when the session doesn't exist.
+#[delete("/api/sessions/{id}")]
+pub async fn revoke(request: HttpRequest,
+                    path: web::Path<String>,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    let id = path.into_inner();
+    let is_admin = auth_response.allows(squire::settings::Role::Admin);
+    let found = session_info.list(None).into_iter().find(|record| record.id == id);
+    if let Some(record) = found.filter(|record| is_admin || record.username == auth_response.username) {
+        session_info.revoke(&record.id);
+        log::info!("Session '{}' of '{}' revoked by '{}'", id, record.username, auth_response.username);
+        return HttpResponse::NoContent().finish();
+    }
+    HttpResponse::NotFound().json(routes::auth::DetailError {
+        detail: format!("session '{}' doesn't exist", id)
+    })
+}
@@ -0,0 +1,174 @@
+use crate::{alerting, constant, routes, squire};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use fernet::MultiFernet;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Default duration (in seconds) of a silence, when neither the duration nor the end is specified.
+const DEFAULT_DURATION: i64 = 3600;
+
+/// Struct for the payload accepted to create a silence.
+///
+/// ## Fields
+///
+/// * `matcher` - Alerts to mute, like `host`, `rule:^cpu_avg`, `service:nginx`, `process:postgres` or `docker:redis`.
+/// * `comment` - Reason for the silence.
+/// * `starts_at` - Epoch timestamp (in seconds) at which the silence starts. Defaults to the current time.
+/// * `ends_at` - Epoch timestamp (in seconds) at which the silence expires.
+/// * `duration` - Duration (in seconds) of the silence, when `ends_at` is not specified. Defaults to an hour.
+#[derive(Deserialize, Debug)]
+pub struct SilencePayload {
+    matcher: String,
+    comment: String,
+    starts_at: Option<i64>,
+    ends_at: Option<i64>,
+    duration: Option<i64>,
+}
+
+/// Struct for the response of the silences endpoint.
+///
+/// ## Fields
+///
+/// * `silences` - Silences that are active or upcoming.
+/// * `maintenance_windows` - Maintenance windows declared in the configuration.
+#[derive(Serialize, Debug)]
+struct SilencesResponse {
+    silences: Vec<alerting::silences::Silence>,
+    maintenance_windows: Vec<alerting::silences::WindowStatus>,
+}
+
+/// Handles the silences endpoint, listing the silences and the maintenance windows.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `silences` - Silences and maintenance windows that mute the alerts.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the silences and maintenance windows in JSON format.
+/// * `401` - HttpResponse with an error message for failed authentication.
+#[get("/api/silences")]
+pub async fn list(request: HttpRequest,
+                  fernet: web::Data<Arc<MultiFernet>>,
+                  session_info: web::Data<Arc<constant::Session>>,
+                  config: web::Data<Arc<squire::settings::Config>>,
+                  silences: web::Data<Arc<alerting::silences::Silences>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    let now = Utc::now().timestamp();
+    HttpResponse::Ok().json(SilencesResponse {
+        silences: silences.silences(now),
+        maintenance_windows: silences.windows(now),
+    })
+}
+
+/// Handles the creation of a silence, which is attributed to the authenticated user.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `body` - Raw body of the request, containing the `SilencePayload` in JSON format.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `silences` - Silences and maintenance windows that mute the alerts.
+///
+/// # See Also
+///
+/// The body must be sent as `application/json`, which browsers don't allow cross-origin forms to do.
+///
+/// # Returns
+///
+/// * `201` - HttpResponse with the created silence in JSON format.
+/// * `400` - HttpResponse with an error message for an invalid payload.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an operator.
+/// * `415` - HttpResponse with an error message when the body is not JSON.
+#[post("/api/silences")]
+pub async fn create(request: HttpRequest,
+                    body: web::Bytes,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    silences: web::Data<Arc<alerting::silences::Silences>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if !auth_response.allows(squire::settings::Role::Operator) {
+        return routes::auth::forbidden(&auth_response, squire::settings::Role::Operator);
+    }
+    let is_json = request.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return HttpResponse::UnsupportedMediaType().json(routes::auth::DetailError {
+            detail: "payload must be sent as 'application/json'".to_string()
+        });
+    }
+    let payload: SilencePayload = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(err) => {
+            return HttpResponse::BadRequest().json(routes::auth::DetailError {
+                detail: format!("invalid payload: {}", err)
+            });
+        }
+    };
+    let now = Utc::now().timestamp();
+    let starts_at = payload.starts_at.unwrap_or(now);
+    let ends_at = payload.ends_at.unwrap_or(starts_at + payload.duration.unwrap_or(DEFAULT_DURATION));
+    match silences.create(&payload.matcher, starts_at, ends_at, &auth_response.username, &payload.comment, now) {
+        Ok(silence) => HttpResponse::Created().json(silence),
+        Err(err) => HttpResponse::BadRequest().json(routes::auth::DetailError { detail: err }),
+    }
+}
+
+/// Handles the removal of a silence, which expires it right away.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - Identifier of the silence.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `silences` - Silences and maintenance windows that mute the alerts.
+///
+/// # Returns
+///
+/// * `204` - HttpResponse with no content, when the silence is removed.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an operator.
+/// * `404` - HttpResponse with an error message when the silence doesn't exist.
+#[delete("/api/silences/{id}")]
+pub async fn remove(request: HttpRequest,
+                    path: web::Path<String>,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    silences: web::Data<Arc<alerting::silences::Silences>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::unauthorized(auth_response);
+    }
+    if !auth_response.allows(squire::settings::Role::Operator) {
+        return routes::auth::forbidden(&auth_response, squire::settings::Role::Operator);
+    }
+    let id = path.into_inner();
+    if silences.remove(&id) {
+        log::info!("Silence '{}' removed by '{}'", id, auth_response.username);
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("silence '{}' doesn't exist", id)
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{constant, resources, routes, squire};
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{rt, web, Error, HttpRequest, HttpResponse};
+use chrono::Utc;
+use fernet::MultiFernet;
+use futures::stream;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Interval after which a heartbeat comment is sent, when there are no frames to stream.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Interval between two checks of the session, for an open stream.
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time (in milliseconds) the browser should wait before reconnecting to the stream.
+const RETRY_INTERVAL: u64 = 3000;
+
+/// Struct to store the state of an event stream between two events.
+///
+/// ## Fields
+///
+/// * `_subscriber` - Subscription with the collector, that is removed when the stream is dropped.
+/// * `receiver` - Receiving half of the channel for the subscription.
+/// * `backlog` - Frames that were missed by the client and have to be replayed first.
+/// * `last_seq` - Sequence number of the last frame sent, to avoid sending duplicates after a replay.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `tokens` - API tokens for the machine clients.
+/// * `credential` - Session or API token the stream was opened with, which ends the stream once it is gone.
+/// * `heartbeat` - Time at which a heartbeat comment is due, when there are no frames to stream.
+/// * `started` - Flag to indicate whether the retry interval was sent to the client.
+struct EventStream {
+    _subscriber: resources::collector::Subscriber,
+    receiver: mpsc::Receiver<resources::collector::Frame>,
+    backlog: VecDeque<resources::collector::Frame>,
+    last_seq: u64,
+    session_info: Arc<constant::Session>,
+    tokens: Arc<squire::tokens::Tokens>,
+    credential: squire::authenticator::Credential,
+    heartbeat: Instant,
+    started: bool,
+}
+
+/// Formats a frame as a server-sent event.
+///
+/// # Arguments
+///
+/// * `frame` - Serialized frame along with its sequence number.
+///
+/// # Returns
+///
+/// Returns the event as `Bytes`, with the sequence number as the event ID.
+fn event(frame: &resources::collector::Frame) -> Bytes {
+    Bytes::from(format!("id: {}\ndata: {}\n\n", frame.seq, frame.payload))
+}
+
+/// Yields the next event in the stream, sending a heartbeat comment when the collector is idle.
+///
+/// # Arguments
+///
+/// * `state` - State of the event stream.
+///
+/// # Returns
+///
+/// Returns an `Option` with the event and the state for the next iteration, or `None` to end the stream.
+async fn next_event(mut state: EventStream) -> Option<(Result<Bytes, Error>, EventStream)> {
+    if !state.started {
+        state.started = true;
+        return Some((Ok(Bytes::from(format!("retry: {}\n\n", RETRY_INTERVAL))), state));
+    }
+    if let Some(frame) = state.backlog.pop_front() {
+        state.last_seq = frame.seq;
+        return Some((Ok(event(&frame)), state));
+    }
+    loop {
+        if !state.credential.is_active(&state.session_info, &state.tokens, Utc::now().timestamp()) {
+            log::info!("Session is no longer active, ending the event stream");
+            return None;
+        }
+        let remaining = state.heartbeat.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            state.heartbeat = Instant::now() + HEARTBEAT_INTERVAL;
+            return Some((Ok(Bytes::from_static(b": heartbeat\n\n")), state));
+        }
+        match rt::time::timeout(SESSION_CHECK_INTERVAL.min(remaining), state.receiver.recv()).await {
+            Ok(Some(frame)) if frame.seq <= state.last_seq => continue,
+            Ok(Some(frame)) => {
+                state.last_seq = frame.seq;
+                state.heartbeat = Instant::now() + HEARTBEAT_INTERVAL;
+                return Some((Ok(event(&frame)), state));
+            }
+            Ok(None) => return None,
+            // Timed out, so the session is checked again before waiting for the next frame
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Handles the Server-Sent Events endpoint for system resources.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+/// * `tokens` - API tokens for the machine clients, which can connect with a bearer instead of the cookie.
+///
+/// # See Also
+///
+/// - Serves the same payload as the websocket, for clients behind proxies that strip websocket upgrades.
+/// - Frames missed since the `Last-Event-ID` header are replayed, as long as the collector still retains them.
+///
+/// # Returns
+///
+/// Returns an `HttpResponse` with a `text/event-stream` body.
+#[get("/sse/system")]
+pub async fn system(request: HttpRequest,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    collector: web::Data<Arc<resources::collector::Collector>>,
+                    tokens: web::Data<Arc<squire::tokens::Tokens>>) -> HttpResponse {
+    log::info!("Event stream initiated");
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if !auth_response.ok {
+        return routes::auth::failed_auth(&request, auth_response);
+    }
+    let role = auth_response.role.unwrap_or(squire::settings::Role::Viewer);
+    let (subscriber, receiver) = collector.subscribe();
+    subscriber.remove_topics(&resources::collector::Topic::denied(role));
+    let last_event_id = request.headers().get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    let backlog = match last_event_id {
+        Some(seq) => {
+            log::info!("Resuming event stream after event ID {}", seq);
+            VecDeque::from(collector.replay(seq, &resources::collector::Topic::allowed(role)))
+        }
+        None => VecDeque::new(),
+    };
+    let state = EventStream {
+        _subscriber: subscriber,
+        receiver,
+        backlog,
+        last_seq: last_event_id.unwrap_or_default(),
+        session_info: session_info.get_ref().clone(),
+        tokens: tokens.get_ref().clone(),
+        credential: auth_response.credential(),
+        heartbeat: Instant::now() + HEARTBEAT_INTERVAL,
+        started: false,
+    };
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream::unfold(state, next_event))
+}
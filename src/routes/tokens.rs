@@ -0,0 +1,189 @@
+use crate::{constant, routes, squire};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use fernet::MultiFernet;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Struct for the payload accepted to create an API token.
+///
+/// ## Fields
+///
+/// * `name` - Name of the token, to tell the clients apart.
+/// * `role` - Role granted to the token. Defaults to `viewer`.
+/// * `expires_in` - Time (in seconds) until the token expires. Never expires when not specified.
+#[derive(Deserialize, Debug)]
+pub struct TokenPayload {
+    name: String,
+    #[serde(default = "squire::settings::default_role")]
+    role: squire::settings::Role,
+    expires_in: Option<i64>,
+}
+
+/// Struct for the response of a created API token.
+///
+/// ## Fields
+///
+/// * `token` - Token to send as a bearer, which is never shown again.
+/// * `details` - Details of the token, as it is listed.
+#[derive(Serialize, Debug)]
+struct CreatedResponse {
+    token: String,
+    #[serde(flatten)]
+    details: squire::tokens::ApiToken,
+}
+
+/// Verifies that the request was made by an admin with a login session, since tokens can't manage tokens.
+///
+/// # Arguments
+///
+/// * `auth_response` - The authentication response of the request.
+///
+/// # Returns
+///
+/// Returns an `Option` with the `HttpResponse` to send, when the request is not allowed.
+fn deny(auth_response: squire::authenticator::AuthToken) -> Option<HttpResponse> {
+    if !auth_response.ok {
+        return Some(routes::auth::unauthorized(auth_response));
+    }
+    if !auth_response.allows(squire::settings::Role::Admin) {
+        return Some(routes::auth::forbidden(&auth_response, squire::settings::Role::Admin));
+    }
+    // A leaked token shouldn't be able to mint more of them, or revoke the ones it would be replaced with
+    if auth_response.session.is_none() {
+        return Some(routes::auth::session_required(&auth_response));
+    }
+    None
+}
+
+/// Handles the tokens endpoint, listing the API tokens that haven't expired.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `tokens` - API tokens for the machine clients.
+///
+/// # Returns
+///
+/// * `200` - HttpResponse with the API tokens in JSON format, without the tokens themselves.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an admin, or used an API token.
+#[get("/api/tokens")]
+pub async fn list(request: HttpRequest,
+                  fernet: web::Data<Arc<MultiFernet>>,
+                  session_info: web::Data<Arc<constant::Session>>,
+                  config: web::Data<Arc<squire::settings::Config>>,
+                  tokens: web::Data<Arc<squire::tokens::Tokens>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    if let Some(response) = deny(auth_response) {
+        return response;
+    }
+    HttpResponse::Ok().json(tokens.list(Utc::now().timestamp()))
+}
+
+/// Handles the creation of an API token, which is attributed to the authenticated admin.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `body` - Raw body of the request, containing the `TokenPayload` in JSON format.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `tokens` - API tokens for the machine clients.
+///
+/// # See Also
+///
+/// Only a hash of the token is stored, so the response is the only time the token can be seen.
+///
+/// # Returns
+///
+/// * `201` - HttpResponse with the token and its details in JSON format.
+/// * `400` - HttpResponse with an error message for an invalid payload.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an admin, or used an API token.
+/// * `415` - HttpResponse with an error message when the body is not JSON.
+#[post("/api/tokens")]
+pub async fn create(request: HttpRequest,
+                    body: web::Bytes,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    tokens: web::Data<Arc<squire::tokens::Tokens>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    let username = auth_response.username.clone();
+    if let Some(response) = deny(auth_response) {
+        return response;
+    }
+    let is_json = request.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return HttpResponse::UnsupportedMediaType().json(routes::auth::DetailError {
+            detail: "payload must be sent as 'application/json'".to_string()
+        });
+    }
+    let payload: TokenPayload = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(err) => {
+            return HttpResponse::BadRequest().json(routes::auth::DetailError {
+                detail: format!("invalid payload: {}", err)
+            });
+        }
+    };
+    match tokens.create(&payload.name, payload.role, &username, payload.expires_in, Utc::now().timestamp()) {
+        Ok((token, details)) => {
+            log::info!("API token '{}' with the '{:?}' role created by '{}'", details.name, details.role, username);
+            HttpResponse::Created().json(CreatedResponse { token, details })
+        }
+        Err(err) => HttpResponse::BadRequest().json(routes::auth::DetailError { detail: err }),
+    }
+}
+
+/// Handles the revocation of an API token, which is rejected from the next request onwards.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `path` - Identifier of the API token.
+/// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `config` - Configuration data for the application.
+/// * `tokens` - API tokens for the machine clients.
+///
+/// # See Also
+///
+/// Websocket connections and event streams opened with the token are closed within a few seconds.
+///
+/// # Returns
+///
+/// * `204` - HttpResponse with no content, when the API token is revoked.
+/// * `401` - HttpResponse with an error message for failed authentication.
+/// * `403` - HttpResponse with an error message when the user is not an admin, or used an API token.
+/// * `404` - HttpResponse with an error message when the API token doesn't exist.
+#[delete("/api/tokens/{id}")]
+pub async fn revoke(request: HttpRequest,
+                    path: web::Path<String>,
+                    fernet: web::Data<Arc<MultiFernet>>,
+                    session_info: web::Data<Arc<constant::Session>>,
+                    config: web::Data<Arc<squire::settings::Config>>,
+                    tokens: web::Data<Arc<squire::tokens::Tokens>>) -> HttpResponse {
+    let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
+    let username = auth_response.username.clone();
+    if let Some(response) = deny(auth_response) {
+        return response;
+    }
+    let id = path.into_inner();
+    if tokens.revoke(&id) {
+        log::info!("API token '{}' revoked by '{}'", id, username);
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(routes::auth::DetailError {
+            detail: format!("API token '{}' doesn't exist", id)
+        })
+    }
+}
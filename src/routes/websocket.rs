@@ -2,41 +2,129 @@ use crate::{constant, resources, routes, squire};
 use actix;
 use actix_web::{rt, web, Error, HttpRequest, HttpResponse};
 use actix_ws::AggregatedMessage;
-use fernet::Fernet;
+use chrono::Utc;
+use fernet::MultiFernet;
 use futures::future;
 use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::pin::pin;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
 
-/// Streams system resources via websocket through a loop.
+/// Enum for the commands a client can send over the websocket to control its subscription.
+///
+/// # Examples
+///
+/// ```json
+/// {"action": "subscribe", "topics": ["cpu", "memory"]}
+/// {"action": "unsubscribe", "topics": ["docker"]}
+/// {"action": "interval", "seconds": 5}
+/// {"action": "pause"}
+/// {"action": "resume"}
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum Command {
+    Subscribe { topics: Vec<resources::collector::Topic> },
+    Unsubscribe { topics: Vec<resources::collector::Topic> },
+    Interval { seconds: u64 },
+    Pause,
+    Resume,
+}
+
+/// Struct for representing the acknowledgement of a command in JSON format.
+#[derive(Serialize)]
+struct Acknowledgement {
+    r#type: &'static str,
+    action: &'static str,
+    #[serde(flatten)]
+    state: resources::collector::SubscriptionState,
+}
+
+/// Interval between two checks of the session, for an open connection.
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of recent points per metric that are sent to backfill the charts, when a client connects.
+const BACKFILL_POINTS: usize = 60;
+
+/// Struct for representing the backfill of recent history in JSON format.
+#[derive(Serialize)]
+struct Backfill {
+    r#type: &'static str,
+    metrics: HashMap<String, Vec<resources::history::Point>>,
+}
+
+/// Struct for representing an error for a command in JSON format.
+#[derive(Serialize)]
+struct CommandError {
+    r#type: &'static str,
+    detail: String,
+}
+
+/// Applies a command received from the client to its subscription.
+///
+/// # Arguments
+///
+/// * `subscriber` - Subscription of the client with the collector.
+/// * `role` - Role of the authenticated user, which limits the topics that can be subscribed.
+/// * `text` - Raw text message received from the client.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the serialized acknowledgement or the serialized error message.
+fn apply_command(subscriber: &resources::collector::Subscriber,
+                 role: squire::settings::Role,
+                 text: &str) -> Result<String, String> {
+    let command = serde_json::from_str::<Command>(text).map_err(|err| err.to_string())?;
+    let (action, state) = match command {
+        Command::Subscribe { topics } => {
+            if let Some(topic) = topics.iter().find(|topic| topic.role() > role) {
+                return Err(format!("'{:?}' role is required for the topic {:?}", topic.role(), topic).to_lowercase());
+            }
+            ("subscribe", subscriber.add_topics(&topics))
+        }
+        Command::Unsubscribe { topics } => ("unsubscribe", subscriber.remove_topics(&topics)),
+        Command::Interval { seconds } => {
+            if seconds == 0 || seconds > resources::collector::MAX_INTERVAL {
+                return Err(format!(
+                    "interval must be between 1 and {} seconds", resources::collector::MAX_INTERVAL
+                ));
+            }
+            ("interval", subscriber.set_interval(Duration::from_secs(seconds)))
+        }
+        Command::Pause => ("pause", subscriber.set_paused(true)),
+        Command::Resume => ("resume", subscriber.set_paused(false)),
+    };
+    Ok(serde_json::to_string(&Acknowledgement { r#type: "ack", action, state }).unwrap())
+}
+
+/// Streams system resources via websocket, as and when the collector sends frames for the subscription.
 ///
 /// # Arguments
 ///
 /// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `session` - A reference to the Actix web `Session` object.
+/// * `receiver` - Receiving half of the channel for the subscription.
 async fn send_system_resources(
     request: HttpRequest,
     mut session: actix_ws::Session,
-    config: web::Data<Arc<squire::settings::Config>>,
+    mut receiver: mpsc::Receiver<resources::collector::Frame>,
 ) {
     let host = request.connection_info().host().to_string();
-    let disk_stats = resources::stream::get_disk_stats();
-    loop {
-        let mut system_resources = resources::stream::system_resources(&config);
-        system_resources.insert("disk_info".to_string(), disk_stats.clone());
-        let serialized = serde_json::to_string(&system_resources).unwrap();
-        match session.text(serialized).await {
+    while let Some(frame) = receiver.recv().await {
+        match session.text(frame.payload.as_str()).await {
             Ok(_) => (),
             Err(err) => {
                 log::info!("Connection from '{}' has been {}", host, err.to_string().to_lowercase());
                 break;
             }
         }
-        // 500ms / 0.5s delay
-        rt::time::sleep(Duration::from_secs(1)).await;
     }
 }
 
-/// Receives messages from the client and sends them back.
+/// Receives commands from the client and acknowledges them.
 ///
 /// # Summary
 ///
@@ -54,40 +142,63 @@ async fn send_system_resources(
 ///
 /// * `session` - A reference to the Actix web `Session` object.
 /// * `stream` - A stream of `AggregatedMessage` objects.
+/// * `subscriber` - Subscription of the client with the collector.
+/// * `role` - Role of the authenticated user.
 async fn receive_messages(
     mut session: actix_ws::Session,
     mut stream: impl futures::Stream<Item=Result<AggregatedMessage, actix_ws::ProtocolError>> + Unpin,
+    subscriber: &resources::collector::Subscriber,
+    role: squire::settings::Role,
 ) {
     while let Some(msg) = stream.next().await {
-        match msg {
-            Ok(AggregatedMessage::Text(text)) => {
-                // echo text message
-                session.text(text).await.unwrap();
-            }
-            Ok(AggregatedMessage::Binary(bin)) => {
-                // echo binary message
-                session.binary(bin).await.unwrap();
-            }
+        let response = match msg {
+            Ok(AggregatedMessage::Text(text)) => apply_command(subscriber, role, &text),
+            Ok(AggregatedMessage::Binary(_)) => Err("binary messages are not supported".to_string()),
             Ok(AggregatedMessage::Ping(msg)) => {
                 // respond to PING frame with PONG frame
-                session.pong(&msg).await.unwrap();
+                let _ = session.pong(&msg).await;
+                continue;
             }
-            _ => {}
+            _ => continue,
+        };
+        let reply = response.unwrap_or_else(|detail| {
+            log::warn!("Invalid command received: {}", detail);
+            serde_json::to_string(&CommandError { r#type: "error", detail }).unwrap()
+        });
+        if session.text(reply).await.is_err() {
+            break;
         }
     }
 }
 
-/// Handles the session by closing it after a certain duration.
+/// Handles the session by closing it, once the session or the API token it was opened with has expired or was revoked.
 ///
 /// # Arguments
 ///
 /// * `session` - A reference to the Actix web `Session` object.
-/// * `duration` - Duration in seconds to keep the session alive.
-async fn session_handler(session: actix_ws::Session, duration: i64) {
-    let session = session.clone();
+/// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `tokens` - API tokens for the machine clients.
+/// * `credential` - Session or API token the connection was opened with.
+///
+/// # See Also
+///
+/// The connection is also pinged on every check, so that the task ends along with a connection that is gone.
+async fn session_handler(mut session: actix_ws::Session,
+                         session_info: Arc<constant::Session>,
+                         tokens: Arc<squire::tokens::Tokens>,
+                         credential: squire::authenticator::Credential) {
     actix::spawn(async move {
-        rt::time::sleep(Duration::from_secs(duration as u64)).await;
-        let _ = session.close(None).await;
+        loop {
+            rt::time::sleep(SESSION_CHECK_INTERVAL).await;
+            if !credential.is_active(&session_info, &tokens, Utc::now().timestamp()) {
+                log::info!("Session is no longer active, closing the websocket connection");
+                let _ = session.close(None).await;
+                break;
+            }
+            if session.ping(b"").await.is_err() {
+                break;
+            }
+        }
     });
 }
 
@@ -99,23 +210,30 @@ async fn session_handler(session: actix_ws::Session, duration: i64) {
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session_info` - Session struct that holds the `session_mapping` to handle sessions.
 /// * `config` - Configuration data for the application.
+/// * `collector` - Shared background collector that fans out the sampled frames.
+/// * `history` - Time series store used to backfill the charts of a new client.
+/// * `tokens` - API tokens for the machine clients, which can connect with a bearer instead of the cookie.
 /// * `stream` - A stream of `Payload` objects.
 ///
 /// # Returns
 ///
 /// Returns an `HttpResponse` with the appropriate status code.
 #[route("/ws/system", method = "GET")]
+#[allow(clippy::too_many_arguments)]
 async fn echo(
     request: HttpRequest,
-    fernet: web::Data<Arc<Fernet>>,
+    fernet: web::Data<Arc<MultiFernet>>,
     session_info: web::Data<Arc<constant::Session>>,
     config: web::Data<Arc<squire::settings::Config>>,
+    collector: web::Data<Arc<resources::collector::Collector>>,
+    history: web::Data<Arc<resources::history::History>>,
+    tokens: web::Data<Arc<squire::tokens::Tokens>>,
     stream: web::Payload,
 ) -> Result<HttpResponse, Error> {
     log::info!("Websocket connection initiated");
     let auth_response = squire::authenticator::verify_token(&request, &config, &fernet, &session_info);
     if !auth_response.ok {
-        return Ok(routes::auth::failed_auth(auth_response));
+        return Ok(routes::auth::failed_auth(&request, auth_response));
     }
     let (response, session, stream) = match actix_ws::handle(&request, stream) {
         Ok(result) => result,
@@ -125,12 +243,22 @@ async fn echo(
     };
     let stream = stream
         .aggregate_continuations();
+    let role = auth_response.role.unwrap_or(squire::settings::Role::Viewer);
+    let credential = auth_response.credential();
+    let (subscriber, receiver) = collector.subscribe();
+    subscriber.remove_topics(&resources::collector::Topic::denied(role));
+    let backfill = Backfill { r#type: "history", metrics: history.recent(BACKFILL_POINTS) };
     rt::spawn(async move {
         log::warn!("Connection established");
-        let send_task = send_system_resources(request.clone(), session.clone(), config.clone());
-        let receive_task = receive_messages(session.clone(), stream);
-        let session_task = session_handler(session.clone(), config.session_duration);
-        future::join3(send_task, receive_task, session_task).await;
+        session_handler(session.clone(), session_info.get_ref().clone(), tokens.get_ref().clone(), credential).await;
+        // Recent history is sent before any frame, so that the charts are populated immediately
+        if session.clone().text(serde_json::to_string(&backfill).unwrap()).await.is_err() {
+            return;
+        }
+        let send_task = pin!(send_system_resources(request.clone(), session.clone(), receiver));
+        let receive_task = pin!(receive_messages(session.clone(), stream, &subscriber, role));
+        // A paused subscription doesn't receive frames, so the connection ends when either of the tasks is done
+        future::select(send_task, receive_task).await;
     });
     Ok(response)
 }
@@ -0,0 +1,258 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::squire;
+
+/// Struct for a network in CIDR notation, either IPv4 or IPv6.
+///
+/// ## Fields
+///
+/// * `address` - Address of the network.
+/// * `prefix` - Number of leading bits that an address has to share with the network to be a part of it.
+#[derive(Clone, Debug)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    /// Checks whether an IP address is a part of the network.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address to check, where an IPv4-mapped IPv6 address is checked as IPv4.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the address is within the network.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parses a network in CIDR notation, where an address without a prefix is a network of just that address.
+///
+/// # Arguments
+///
+/// * `cidr` - Network in CIDR notation, like `10.8.0.0/16` or `fd00::/8`
+///
+/// # Returns
+///
+/// Returns a `Result` with the `Network`, or an error message for an invalid address or prefix.
+pub fn parse(cidr: &str) -> Result<Network, String> {
+    let (address, prefix) = match cidr.trim().split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (cidr.trim(), None),
+    };
+    let parsed: IpAddr = address.parse().map_err(|_| format!("invalid IP address '{}'", address))?;
+    let max_prefix = if parsed.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.parse::<u8>().ok()
+            .filter(|prefix| *prefix <= max_prefix)
+            .ok_or(format!("prefix must be a number between 0 and {}", max_prefix))?,
+        None => max_prefix,
+    };
+    // IPv4-mapped IPv6 networks are checked as IPv4, like the addresses within them
+    match parsed.to_canonical() {
+        IpAddr::V4(address) if parsed.is_ipv6() => match prefix.checked_sub(96) {
+            Some(prefix) => Ok(Network { address: IpAddr::V4(address), prefix }),
+            None => Err("prefix of an IPv4-mapped network must be at least 96".to_string()),
+        },
+        address => Ok(Network { address, prefix }),
+    }
+}
+
+/// Struct for the networks that are allowed or denied access to the server.
+///
+/// ## Fields
+///
+/// * `allowed` - Networks that are allowed access, where an empty list allows every network.
+/// * `denied` - Networks that are denied access, which takes precedence over `allowed`.
+/// * `trusted_proxies` - Networks of the proxies whose `X-Forwarded-For` header is trusted.
+pub struct Access {
+    allowed: Vec<Network>,
+    denied: Vec<Network>,
+    trusted_proxies: Vec<Network>,
+}
+
+impl Access {
+    /// Checks whether an IP address belongs to a trusted proxy.
+    pub fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|network| network.contains(ip))
+    }
+
+    /// Resolves the IP address of the client, that made the request directly or via the trusted proxies.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer` - IP address of the connection.
+    /// * `forwarded_for` - Value of the `X-Forwarded-For` header, if present.
+    ///
+    /// # See Also
+    ///
+    /// The header is only read when the connection is from a trusted proxy, and is walked from the right,
+    /// since the addresses on the left are set by the client and can't be trusted.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` with the IP address of the client, which is `None` when the header is malformed.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let peer = peer.to_canonical();
+        let forwarded_for = match forwarded_for {
+            Some(forwarded_for) if self.is_trusted(&peer) => forwarded_for,
+            _ => return Some(peer),
+        };
+        let mut client = peer;
+        for hop in forwarded_for.split(',').rev() {
+            client = hop.trim().parse::<IpAddr>().ok()?.to_canonical();
+            if !self.is_trusted(&client) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    /// Checks whether an IP address is allowed access to the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address of the client.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the address is allowed.
+    pub fn permits(&self, ip: &IpAddr) -> bool {
+        if self.denied.iter().any(|network| network.contains(ip)) {
+            return false;
+        }
+        self.allowed.is_empty() || self.allowed.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// Instantiates the `Access` struct with the networks in the configuration.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # See Also
+///
+/// The networks are validated during startup, so the invalid ones are never present here.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Access` struct.
+pub fn rules(config: &squire::settings::Config) -> Arc<Access> {
+    let networks = |cidrs: &Vec<String>| cidrs.iter().filter_map(|cidr| parse(cidr).ok()).collect();
+    Arc::new(Access {
+        allowed: networks(&config.allowed_networks),
+        denied: networks(&config.denied_networks),
+        trusted_proxies: networks(&config.trusted_proxies),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn access(allowed: &[&str], denied: &[&str], trusted_proxies: &[&str]) -> Access {
+        let networks = |cidrs: &[&str]| cidrs.iter().map(|cidr| parse(cidr).unwrap()).collect();
+        Access {
+            allowed: networks(allowed),
+            denied: networks(denied),
+            trusted_proxies: networks(trusted_proxies),
+        }
+    }
+
+    #[test]
+    fn test_ipv4_network() {
+        let network = parse("10.8.0.0/16").unwrap();
+        assert!(network.contains(&ip("10.8.0.1")));
+        assert!(network.contains(&ip("10.8.255.255")));
+        assert!(!network.contains(&ip("10.9.0.1")));
+        assert!(!network.contains(&ip("fd00::1")));
+        // IPv4-mapped addresses are checked as IPv4
+        assert!(network.contains(&ip("::ffff:10.8.1.1")));
+        let single = parse("192.168.1.10").unwrap();
+        assert!(single.contains(&ip("192.168.1.10")));
+        assert!(!single.contains(&ip("192.168.1.11")));
+        let everything = parse("0.0.0.0/0").unwrap();
+        assert!(everything.contains(&ip("203.0.113.7")));
+        assert!(!everything.contains(&ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn test_ipv6_network() {
+        let network = parse("fd00::/8").unwrap();
+        assert!(network.contains(&ip("fd12:3456::1")));
+        assert!(!network.contains(&ip("fe80::1")));
+        assert!(!network.contains(&ip("10.0.0.1")));
+        let single = parse("2001:db8::1").unwrap();
+        assert!(single.contains(&ip("2001:db8::1")));
+        assert!(!single.contains(&ip("2001:db8::2")));
+        assert!(parse("::/0").unwrap().contains(&ip("2001:db8::1")));
+        let mapped = parse("::ffff:10.0.0.0/104").unwrap();
+        assert!(mapped.contains(&ip("10.1.2.3")));
+        assert!(!mapped.contains(&ip("11.1.2.3")));
+    }
+
+    #[test]
+    fn test_invalid_networks() {
+        for cidr in ["", "10.0.0.0/33", "fd00::/129", "10.0.0/8", "10.0.0.0/-1", "10.0.0.0/", "::ffff:10.0.0.0/95"] {
+            assert!(parse(cidr).is_err(), "{}", cidr);
+        }
+    }
+
+    #[test]
+    fn test_permits() {
+        let rules = access(&["10.0.0.0/8"], &["10.6.0.0/16"], &[]);
+        assert!(rules.permits(&ip("10.1.0.1")));
+        assert!(!rules.permits(&ip("10.6.0.1")));
+        assert!(!rules.permits(&ip("192.168.0.1")));
+        assert!(access(&[], &[], &[]).permits(&ip("192.168.0.1")));
+    }
+
+    #[test]
+    fn test_client_ip_without_trusted_proxy() {
+        let rules = access(&[], &[], &[]);
+        // The header is ignored, since anyone can set it
+        assert_eq!(rules.client_ip(ip("203.0.113.7"), Some("10.0.0.1")), Some(ip("203.0.113.7")));
+        assert_eq!(rules.client_ip(ip("::ffff:203.0.113.7"), None), Some(ip("203.0.113.7")));
+        let rules = access(&[], &[], &["10.0.0.1"]);
+        assert_eq!(rules.client_ip(ip("203.0.113.7"), Some("garbage")), Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn test_client_ip_via_trusted_proxies() {
+        let rules = access(&[], &[], &["10.0.0.0/24", "fd00::/8"]);
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), None), Some(ip("10.0.0.1")));
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("203.0.113.7")), Some(ip("203.0.113.7")));
+        // Walked from the right, so an address spoofed on the left is never reached
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("1.1.1.1, 203.0.113.7, 10.0.0.2")), Some(ip("203.0.113.7")));
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("1.1.1.1,203.0.113.7,fd00::2")), Some(ip("203.0.113.7")));
+        // Every hop being trusted resolves to the leftmost one
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("10.0.0.3, 10.0.0.2")), Some(ip("10.0.0.3")));
+        assert_eq!(rules.client_ip(ip("fd00::1"), Some("2001:db8::7")), Some(ip("2001:db8::7")));
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("::ffff:203.0.113.7")), Some(ip("203.0.113.7")));
+        // Malformed hops can't be checked, while the ones left of the client are never parsed
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("203.0.113.7, unknown")), None);
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("")), None);
+        assert_eq!(rules.client_ip(ip("10.0.0.1"), Some("unknown, 203.0.113.7")), Some(ip("203.0.113.7")));
+    }
+}
@@ -1,30 +1,89 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use actix_web::http::header::HeaderValue;
+use actix_web::http::header::{self, HeaderValue};
 use actix_web::{web, HttpRequest};
 use chrono::Utc;
-use fernet::Fernet;
+use fernet::MultiFernet;
 
 use crate::constant;
 use crate::squire;
 
+/// Time (in seconds) the timestamp of a login may differ from the server's clock.
+const LOGIN_WINDOW: i64 = 300;
+
 /// Represents user credentials extracted from an authorization header.
 ///
-/// Contains the username, signature, and timestamp obtained by decoding and parsing the authorization header.
+/// Contains the username, signature, timestamp and nonce obtained by decoding and parsing the authorization header.
 struct Credentials {
     username: String,
     signature: String,
     timestamp: String,
+    nonce: String,
+}
+
+/// Represents a failed login, along with the time to wait before trying again when the attempt was rejected.
+#[derive(Debug)]
+pub struct LoginError {
+    pub detail: String,
+    pub retry_after: Option<i64>,
 }
 
 /// Represents the result of authentication, indicating whether it was successful or not.
 ///
-/// If successful, it includes the username and a generated key for the session.
+/// If successful, it includes the username, the role of the user and the identifier of the session,
+/// or the identifier of the API token for a machine client.
 pub struct AuthToken {
     pub ok: bool,
     pub detail: String,
     pub username: String,
+    pub role: Option<squire::settings::Role>,
+    pub session: Option<String>,
+    pub token: Option<String>,
+}
+
+impl AuthToken {
+    /// Checks whether the authenticated user has at least the given role.
+    pub fn allows(&self, role: squire::settings::Role) -> bool {
+        self.ok && self.role.is_some_and(|granted| granted >= role)
+    }
+
+    /// Gets the credential that the request was authenticated with.
+    pub fn credential(&self) -> Credential {
+        match &self.token {
+            Some(id) => Credential::Token(id.clone()),
+            None => Credential::Session(self.session.clone().unwrap_or_default()),
+        }
+    }
+}
+
+/// Represents the credential that a long-lived connection was opened with, which ends the connection once it is gone.
+#[derive(Clone, Debug)]
+pub enum Credential {
+    /// Identifier of the session of a browser.
+    Session(String),
+    /// Identifier of the API token of a machine client.
+    Token(String),
+}
+
+impl Credential {
+    /// Checks whether the credential hasn't expired or been revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - Session struct that holds the `session_mapping` to handle sessions.
+    /// * `tokens` - API tokens for the machine clients.
+    /// * `now` - Epoch timestamp (in seconds) of the check.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the credential is still active.
+    pub fn is_active(&self, session: &constant::Session, tokens: &squire::tokens::Tokens, now: i64) -> bool {
+        match self {
+            Credential::Session(id) => session.is_active(id, now),
+            Credential::Token(id) => tokens.is_active(id, now),
+        }
+    }
 }
 
 
@@ -36,7 +95,7 @@ pub struct AuthToken {
 ///
 /// # See Also
 /// - Decodes the base64 encoded header
-/// - Splits it into 3 parts with first one being the username followed by the signature and timestamp
+/// - Splits it into 4 parts with first one being the username followed by the signature, timestamp and nonce
 /// - Converts the username from hex into a string.
 ///
 /// # Returns
@@ -58,6 +117,8 @@ fn extract_credentials(authorization: &HeaderValue) -> Result<Credentials, &'sta
                 username: squire::secure::hex_decode(vector.first().unwrap()),
                 signature: vector.get(1).unwrap().to_string(),
                 timestamp: vector.get(2).unwrap().to_string(),
+                // Missing for clients that didn't fetch the challenge, which fails like an invalid nonce
+                nonce: vector.get(3).map(|nonce| nonce.to_string()).unwrap_or_default(),
             })
         }
         Err(err) => {
@@ -71,40 +132,68 @@ fn extract_credentials(authorization: &HeaderValue) -> Result<Credentials, &'sta
 /// # Arguments
 ///
 /// * `request` - A reference to the Actix web `HttpRequest` object.
-/// * `config` - Configuration data for the application.
-/// * `session` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `credentials` - Verifiers of the passwords, that the logins are checked against.
+/// * `lockout` - Tracker for the failed login attempts per IP address and per username.
+/// * `nonces` - Nonces issued by the challenge endpoint, that haven't been used yet.
+///
+/// # See Also
+///
+/// - Attempts from an IP address or for a username that is backing off or locked out are rejected,
+///   before the credentials are verified.
+/// - The signature covers a nonce issued by the challenge endpoint, which is consumed by the attempt,
+///   so a captured header can't be replayed.
 ///
 /// # Returns
 ///
-/// Returns a `Result` containing a `HashMap` with session information if authentication is successful,
-/// otherwise returns an error message.
+/// Returns a `Result` containing the username if the credentials are valid, otherwise returns a `LoginError`.
 pub fn verify_login(
     request: &HttpRequest,
-    config: &web::Data<Arc<squire::settings::Config>>,
-    session: &web::Data<Arc<constant::Session>>,
-) -> Result<HashMap<&'static str, String>, String> {
+    credentials: &squire::credentials::Credentials,
+    lockout: &squire::lockout::Lockout,
+    nonces: &squire::nonces::Nonces,
+) -> Result<String, LoginError> {
     let err_response;
     if let Some(authorization) = request.headers().get("authorization") {
         let extracted_credentials = extract_credentials(authorization);
         match extracted_credentials {
-            Ok(credentials) => {
-                // Check if the username is present in HashMap as key
+            Ok(login) => {
+                let ip = squire::middleware::client_ip(request);
+                let now = Utc::now().timestamp();
+                if let Some(retry_after) = lockout.retry_after(&ip, &login.username, now) {
+                    log::warn!("Login for '{}' from '{}' was rejected for another {}s",
+                        login.username, ip, retry_after);
+                    return Err(LoginError {
+                        detail: format!("Too many failed attempts, try again in {}s", retry_after),
+                        retry_after: Some(retry_after),
+                    });
+                }
+                if !nonces.consume(&login.nonce, &login.username, now) {
+                    log::warn!("Login for '{}' from '{}' has an invalid or reused nonce", login.username, ip);
+                    return Err(LoginError {
+                        detail: "Login challenge is invalid or has expired, please try again".to_string(),
+                        retry_after: None,
+                    });
+                }
+                let fresh = login.timestamp.parse::<i64>()
+                    .is_ok_and(|timestamp| (now - timestamp).abs() <= LOGIN_WINDOW);
+                if !fresh {
+                    log::warn!("Login for '{}' from '{}' has a stale timestamp", login.username, ip);
+                    return Err(LoginError {
+                        detail: "Login request has expired, please check the clock of your device".to_string(),
+                        retry_after: None,
+                    });
+                }
                 let message = format!("{}{}{}",
-                                      squire::secure::hex_encode(&credentials.username),
-                                      squire::secure::hex_encode(&config.password),
-                                      credentials.timestamp);
-                // Create a new signature with hex encoded username and password stored in config file as plain text
-                let expected_signature = squire::secure::calculate_hash(message);
-                if expected_signature == credentials.signature {
-                    let key = squire::secure::keygen();
-                    session.mapping.lock().unwrap().insert(credentials.username.to_string(), key.to_string());
-                    let mut mapped = HashMap::new();
-                    mapped.insert("username", credentials.username.to_string());
-                    mapped.insert("key", key.to_string());
-                    mapped.insert("timestamp", credentials.timestamp.to_string());
-                    return Ok(mapped);
+                                      squire::secure::hex_encode(&login.username),
+                                      login.nonce,
+                                      login.timestamp);
+                // Unknown users fail like a bad password
+                if credentials.verify(&login.username, &message, &login.signature) {
+                    lockout.success(&login.username);
+                    return Ok(login.username);
                 } else {
-                    log::warn!("{} entered bad credentials", credentials.username);
+                    log::warn!("{} entered bad credentials from '{}'", login.username, ip);
+                    lockout.failure(&ip, &login.username, now);
                     err_response = "Incorrect username or password";
                 }
             }
@@ -116,7 +205,94 @@ pub fn verify_login(
         log::warn!("Authorization header was missing");
         err_response = "No credentials received";
     }
-    Err(err_response.to_string())
+    Err(LoginError { detail: err_response.to_string(), retry_after: None })
+}
+
+/// Creates a session for a user, whose login has been verified.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+/// * `session` - Session struct that holds the `session_mapping` to handle sessions.
+/// * `username` - Username of the authenticated user.
+///
+/// # Returns
+///
+/// Returns a `HashMap` with the session information, to be encrypted into the `session_token` cookie.
+pub fn create_session(
+    request: &HttpRequest,
+    config: &squire::settings::Config,
+    session: &constant::Session,
+    username: &str,
+) -> HashMap<&'static str, String> {
+    let ip = squire::middleware::client_ip(request);
+    let user_agent = request.headers().get("user-agent")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let now = Utc::now().timestamp();
+    let (key, record) = session.create(username, &ip, user_agent, now, config.session_duration);
+    let mut mapped = HashMap::new();
+    mapped.insert("username", username.to_string());
+    mapped.insert("session", record.id);
+    mapped.insert("key", key);
+    mapped.insert("timestamp", now.to_string());
+    mapped
+}
+
+/// Constructs the `AuthToken` for a failed authentication.
+fn denied(detail: &str, username: &str) -> AuthToken {
+    AuthToken {
+        ok: false,
+        detail: detail.to_string(),
+        username: username.to_string(),
+        role: None,
+        session: None,
+        token: None,
+    }
+}
+
+/// Verifies an API token received as a bearer, instead of the `session_token` cookie.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+/// * `config` - Configuration data for the application.
+/// * `bearer` - Token received in the `Authorization` header.
+///
+/// # See Also
+///
+/// The role of the token is capped by the current role of the admin who created it,
+/// so a token loses access along with the account it was created by.
+///
+/// # Returns
+///
+/// Returns an instance of the `AuthToken` struct, authenticated as `token:<name>`.
+pub fn verify_bearer(request: &HttpRequest, config: &squire::settings::Config, bearer: &str) -> AuthToken {
+    let tokens = match request.app_data::<web::Data<Arc<squire::tokens::Tokens>>>() {
+        Some(tokens) => tokens,
+        None => return denied("API tokens are not accepted here", "NA"),
+    };
+    let token = match tokens.verify(bearer.trim(), Utc::now().timestamp()) {
+        Some(token) => token,
+        None => {
+            log::warn!("Invalid or expired API token received from '{}'", squire::middleware::client_ip(request));
+            return denied("Invalid or expired API token", "NA");
+        }
+    };
+    let username = format!("token:{}", token.name);
+    let role = match config.user(&token.created_by) {
+        Some(user) => token.role.min(user.role),
+        None => return denied("User who created the API token no longer exists", &username),
+    };
+    AuthToken {
+        ok: true,
+        detail: format!("API token '{}' is valid", token.name),
+        username,
+        role: Some(role),
+        session: None,
+        token: Some(token.id),
+    }
 }
 
 /// Verifies a session token extracted from an HTTP request against stored session mappings and configuration.
@@ -128,64 +304,67 @@ pub fn verify_login(
 /// * `fernet` - Fernet object to encrypt the auth payload that will be set as `session_token` cookie.
 /// * `session` - Session struct that holds the `session_mapping` to handle sessions.
 ///
+/// # See Also
+///
+/// Machine clients authenticate with an API token in the `Authorization: Bearer` header instead,
+/// which takes precedence over the cookie.
+///
 /// # Returns
 ///
 /// Returns an instance of the `AuthToken` struct indicating the result of the token verification.
 pub fn verify_token(
     request: &HttpRequest,
     config: &squire::settings::Config,
-    fernet: &Fernet,
+    fernet: &MultiFernet,
     session: &constant::Session,
 ) -> AuthToken {
-    if session.mapping.lock().unwrap().is_empty() {
-        log::warn!("No stored sessions, no point in validating further");
-        return AuthToken {
-            ok: false,
-            detail: "Server doesn't recognize your session".to_string(),
-            username: "NA".to_string(),
-        };
+    let bearer = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(bearer) = bearer {
+        return verify_bearer(request, config, bearer);
     }
-    if let Some(cookie) = request.cookie("session_token") {
-        if let Ok(decrypted) = fernet.decrypt(cookie.value()) {
-            let payload: HashMap<String, String> = serde_json::from_str(&String::from_utf8_lossy(&decrypted)).unwrap();
-            let username = payload.get("username").unwrap().to_string();
-            let cookie_key = payload.get("key").unwrap().to_string();
-            let timestamp = payload.get("timestamp").unwrap().parse::<i64>().unwrap();
-            let stored_key = session.mapping.lock().unwrap().get(&username).unwrap().to_string();
-            let current_time = Utc::now().timestamp();
-            // Max time and expiry for session token is set in the Cookie, but this is a fallback mechanism
-            if stored_key != *cookie_key {
-                return AuthToken {
-                    ok: false,
-                    detail: "Invalid session token".to_string(),
-                    username,
-                };
-            }
-            if current_time - timestamp > config.session_duration {
-                return AuthToken {
-                    ok: false,
-                    detail: "Session Expired".to_string(),
-                    username,
-                };
-            }
-            let time_left = timestamp + config.session_duration - current_time;
-            AuthToken {
-                ok: true,
-                detail: format!("Session valid for {}s", time_left),
-                username,
-            }
-        } else {
-            AuthToken {
-                ok: false,
-                detail: "Invalid session token".to_string(),
-                username: "NA".to_string(),
-            }
-        }
-    } else {
-        AuthToken {
-            ok: false,
-            detail: "Session information not found".to_string(),
-            username: "NA".to_string(),
-        }
+    let cookie = match request.cookie("session_token") {
+        Some(cookie) => cookie,
+        None => return denied("Session information not found", "NA"),
+    };
+    let payload: HashMap<String, String> = match fernet.decrypt(cookie.value()) {
+        Ok(decrypted) => serde_json::from_slice(&decrypted).unwrap_or_default(),
+        Err(_) => return denied("Invalid session token", "NA"),
+    };
+    let claims = match (payload.get("session"), payload.get("key"), payload.get("username"), payload.get("timestamp")) {
+        (Some(id), Some(key), Some(username), Some(timestamp)) => match timestamp.parse() {
+            Ok(issued_at) => constant::Claims {
+                id: id.to_string(),
+                key: key.to_string(),
+                username: username.to_string(),
+                issued_at,
+            },
+            Err(_) => return denied("Invalid session token", "NA"),
+        },
+        _ => return denied("Invalid session token", "NA"),
+    };
+    let current_time = Utc::now().timestamp();
+    let ip = squire::middleware::client_ip(request);
+    let user_agent = request.headers().get("user-agent")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // Max time and expiry for session token is set in the Cookie, but the store is the source of truth
+    let record = match session.touch(&claims, &ip, user_agent, current_time) {
+        Ok(record) => record,
+        Err(detail) => return denied(detail, &claims.username),
+    };
+    // Role is looked up on every request, so that an account removed from the configuration loses access
+    let role = match config.user(&record.username) {
+        Some(user) => user.role,
+        None => return denied("User account no longer exists", &record.username),
+    };
+    AuthToken {
+        ok: true,
+        detail: format!("Session valid for {}s", record.expires_at - current_time),
+        username: record.username,
+        role: Some(role),
+        session: Some(record.id),
+        token: None,
     }
 }
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::squire;
+
+/// Struct for the verifiers of the passwords, that the logins are checked against.
+///
+/// ## Fields
+///
+/// * `verifiers` - Verifier of the password of each user, keyed by the username.
+/// * `decoy_key` - Secret key to derive the salts of the usernames that don't exist.
+pub struct Credentials {
+    verifiers: HashMap<String, squire::secure::Verifier>,
+    decoy_key: String,
+}
+
+impl Credentials {
+    /// Retrieves the parameters a client needs, to derive the key from the password of a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username received by the challenge endpoint.
+    ///
+    /// # See Also
+    ///
+    /// Every user gets the same shape of parameters, whether the password is stored as a verifier or as plain text,
+    /// and usernames that don't exist get the parameters of a fake hash, so none of them can be told apart.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `HashParams` struct.
+    pub fn params(&self, username: &str) -> squire::secure::HashParams {
+        match self.verifiers.get(username) {
+            Some(verifier) => verifier.params.clone(),
+            None => squire::secure::decoy_params(username, &self.decoy_key),
+        }
+    }
+
+    /// Verifies the proof sent by the client along with the login.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the login.
+    /// * `message` - Message the client signed.
+    /// * `proof` - Hex encoded proof of the knowledge of the client key.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the user exists and the proof is valid.
+    pub fn verify(&self, username: &str, message: &str, proof: &str) -> bool {
+        self.verifiers.get(username).is_some_and(|verifier| verifier.verify(message, proof))
+    }
+}
+
+/// Loads the verifiers of the users' passwords.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `keys` - Keys to encrypt the session tokens, which are persisted or configured to survive restarts.
+///
+/// # See Also
+///
+/// - Passwords stored as plain text are derived into a verifier once, with a salt that is derived like the salts
+///   for the usernames that don't exist.
+/// - The salts are derived from the last key, which stays the same while new keys are added first to rotate them.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Credentials` struct.
+pub fn load(config: &squire::settings::Config, keys: &[String]) -> Arc<Credentials> {
+    let decoy_key = keys.last().cloned().unwrap_or_else(squire::secure::keygen);
+    let mut verifiers = HashMap::new();
+    for user in &config.users {
+        // Verifiers and passwords are validated during startup
+        let verifier = if squire::secure::is_hashed(&user.password) {
+            squire::secure::parse_verifier(&user.password)
+        } else {
+            squire::secure::derive_verifier(&user.password, &squire::secure::decoy_salt(&user.username, &decoy_key))
+        };
+        match verifier {
+            Ok(verifier) => {
+                verifiers.insert(user.username.clone(), verifier);
+            }
+            Err(err) => panic!("\nFailed to load the password for '{}': {}\n", user.username, err),
+        }
+    }
+    Arc::new(Credentials { verifiers, decoy_key })
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::squire;
+
+/// Maximum time (in seconds) a subject can be locked out for, regardless of the number of failures.
+const MAX_LOCKOUT: i64 = 86400;
+
+/// Enum for the subject of the failed login attempts.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Ip,
+    Username,
+}
+
+/// Struct for the failed login attempts of a subject.
+///
+/// ## Fields
+///
+/// * `count` - Number of consecutive failures.
+/// * `blocked_until` - Epoch timestamp (in seconds) until which the login attempts are rejected.
+struct Failures {
+    count: usize,
+    blocked_until: i64,
+}
+
+/// Struct for a subject with failed login attempts, as it is listed for the admins.
+///
+/// ## Fields
+///
+/// * `kind` - Whether the subject is an IP address or a username.
+/// * `subject` - IP address or username.
+/// * `failures` - Number of consecutive failures.
+/// * `blocked_until` - Epoch timestamp (in seconds) until which the login attempts are rejected.
+/// * `locked` - Whether the subject has reached the threshold and is locked out, instead of just backing off.
+#[derive(Serialize, Debug)]
+pub struct Entry {
+    pub kind: Kind,
+    pub subject: String,
+    pub failures: usize,
+    pub blocked_until: i64,
+    pub locked: bool,
+}
+
+/// Struct for the counters of the login attempts, that are exposed as metrics.
+///
+/// ## Fields
+///
+/// * `failures` - Number of failed login attempts.
+/// * `rejected` - Number of login attempts rejected without verification, since the subject was blocked.
+/// * `lockouts` - Number of times a subject was locked out.
+/// * `locked` - Number of subjects that are locked out right now, per kind.
+pub struct Stats {
+    pub failures: u64,
+    pub rejected: u64,
+    pub lockouts: u64,
+    pub locked: Vec<(Kind, usize)>,
+}
+
+/// Struct to track the failed login attempts per IP address and per username.
+///
+/// ## Fields
+///
+/// * `entries` - Failed login attempts keyed by their subject.
+/// * `threshold` - Number of failures after which a subject is locked out.
+/// * `duration` - Time (in seconds) of the first lockout, which doubles with every failure after it.
+/// * `failures` - Number of failed login attempts.
+/// * `rejected` - Number of login attempts rejected since the subject was blocked.
+/// * `lockouts` - Number of times a subject was locked out.
+///
+/// ## See Also
+///
+/// Before the threshold, every failure blocks the subject for an exponential backoff of 1, 2, 4... seconds.
+pub struct Lockout {
+    entries: Mutex<HashMap<(Kind, String), Failures>>,
+    threshold: usize,
+    duration: i64,
+    failures: AtomicU64,
+    rejected: AtomicU64,
+    lockouts: AtomicU64,
+}
+
+impl Lockout {
+    /// Computes the time (in seconds) a subject is blocked for, after a number of consecutive failures.
+    fn backoff(&self, count: usize) -> i64 {
+        if count < self.threshold {
+            return 1_i64 << (count - 1).min(16);
+        }
+        self.duration.saturating_mul(1_i64 << (count - self.threshold).min(16)).min(MAX_LOCKOUT)
+    }
+
+    /// Drops the subjects that have been quiet for the lockout duration, since they were last blocked.
+    fn prune(&self, entries: &mut HashMap<(Kind, String), Failures>, now: i64) {
+        entries.retain(|_, failures| now < failures.blocked_until + self.duration);
+    }
+
+    /// Checks whether a login attempt should be rejected, without verifying the credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address the attempt was made from.
+    /// * `username` - Username the attempt was made for.
+    /// * `now` - Epoch timestamp (in seconds) of the attempt.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` with the time (in seconds) to wait before trying again, if the attempt is rejected.
+    pub fn retry_after(&self, ip: &str, username: &str, now: i64) -> Option<i64> {
+        let entries = self.entries.lock().unwrap();
+        let retry_after = [(Kind::Ip, ip), (Kind::Username, username)].iter()
+            .filter_map(|(kind, subject)| entries.get(&(*kind, subject.to_string())))
+            .map(|failures| failures.blocked_until - now)
+            .filter(|remaining| *remaining > 0)
+            .max();
+        if retry_after.is_some() {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        retry_after
+    }
+
+    /// Records a failed login attempt against the IP address and the username.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - IP address the attempt was made from.
+    /// * `username` - Username the attempt was made for.
+    /// * `now` - Epoch timestamp (in seconds) of the attempt.
+    pub fn failure(&self, ip: &str, username: &str, now: i64) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        let mut entries = self.entries.lock().unwrap();
+        self.prune(&mut entries, now);
+        for (kind, subject) in [(Kind::Ip, ip), (Kind::Username, username)] {
+            let failures = entries.entry((kind, subject.to_string()))
+                .or_insert(Failures { count: 0, blocked_until: now });
+            failures.count += 1;
+            let backoff = self.backoff(failures.count);
+            failures.blocked_until = now + backoff;
+            if failures.count >= self.threshold {
+                self.lockouts.fetch_add(1, Ordering::Relaxed);
+                log::warn!("Locked out {:?} '{}' for {}s after {} failed login attempts",
+                    kind, subject, backoff, failures.count);
+            }
+        }
+    }
+
+    /// Clears the failed login attempts of a username, after a successful login.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username that logged in.
+    ///
+    /// # See Also
+    ///
+    /// The IP address is left as is, so that a valid account can't be used to reset the count while spraying.
+    pub fn success(&self, username: &str) {
+        self.entries.lock().unwrap().remove(&(Kind::Username, username.to_string()));
+    }
+
+    /// Lists the subjects with failed login attempts, with the ones blocked the longest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Epoch timestamp (in seconds) of the request.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec` of `Entry`.
+    pub fn entries(&self, now: i64) -> Vec<Entry> {
+        let mut entries = self.entries.lock().unwrap();
+        self.prune(&mut entries, now);
+        let mut listed: Vec<Entry> = entries.iter()
+            .map(|((kind, subject), failures)| Entry {
+                kind: *kind,
+                subject: subject.clone(),
+                failures: failures.count,
+                blocked_until: failures.blocked_until,
+                locked: failures.count >= self.threshold && now < failures.blocked_until,
+            })
+            .collect();
+        listed.sort_by(|a, b| b.blocked_until.cmp(&a.blocked_until).then(a.subject.cmp(&b.subject)));
+        listed
+    }
+
+    /// Clears the failed login attempts of a subject, which unlocks it right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Whether the subject is an IP address or a username.
+    /// * `subject` - IP address or username.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the subject had any failed login attempts.
+    pub fn unlock(&self, kind: Kind, subject: &str) -> bool {
+        self.entries.lock().unwrap().remove(&(kind, subject.to_string())).is_some()
+    }
+
+    /// Gathers the counters of the login attempts.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Epoch timestamp (in seconds) of the request.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Stats` of the login attempts.
+    pub fn stats(&self, now: i64) -> Stats {
+        let entries = self.entries.lock().unwrap();
+        let locked = [Kind::Ip, Kind::Username].into_iter()
+            .map(|kind| (kind, entries.iter().filter(|((entry_kind, _), failures)| {
+                *entry_kind == kind && failures.count >= self.threshold && now < failures.blocked_until
+            }).count()))
+            .collect();
+        Stats {
+            failures: self.failures.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            lockouts: self.lockouts.load(Ordering::Relaxed),
+            locked,
+        }
+    }
+}
+
+/// Instantiates the `Lockout` struct to track the failed login attempts.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Lockout` struct.
+pub fn tracker(config: &squire::settings::Config) -> Arc<Lockout> {
+    Arc::new(Lockout {
+        entries: Mutex::new(HashMap::new()),
+        threshold: config.lockout_threshold,
+        duration: config.lockout_duration,
+        failures: AtomicU64::new(0),
+        rejected: AtomicU64::new(0),
+        lockouts: AtomicU64::new(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    fn open(threshold: usize, duration: i64) -> Lockout {
+        Lockout {
+            entries: Mutex::new(HashMap::new()),
+            threshold,
+            duration,
+            failures: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            lockouts: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let lockout = open(5, 300);
+        let backoffs: Vec<i64> = (1..=7).map(|count| lockout.backoff(count)).collect();
+        assert_eq!(backoffs, vec![1, 2, 4, 8, 300, 600, 1200]);
+        assert_eq!(lockout.backoff(13), 76800);
+        assert_eq!(lockout.backoff(14), MAX_LOCKOUT);
+        assert_eq!(lockout.backoff(1000), MAX_LOCKOUT);
+        // A duration beyond the cap is capped from the first lockout
+        assert_eq!(open(1, 2 * MAX_LOCKOUT).backoff(1), MAX_LOCKOUT);
+    }
+
+    #[test]
+    fn test_failure() {
+        let lockout = open(3, 300);
+        lockout.failure("10.0.0.1", "alice", 0);
+        assert_eq!(lockout.retry_after("10.0.0.1", "bob", 0), Some(1));
+        assert_eq!(lockout.retry_after("10.0.0.2", "alice", 0), Some(1));
+        assert_eq!(lockout.retry_after("10.0.0.2", "bob", 0), None);
+        assert_eq!(lockout.retry_after("10.0.0.1", "alice", 1), None);
+        lockout.failure("10.0.0.1", "alice", 1);
+        assert_eq!(lockout.retry_after("10.0.0.1", "alice", 1), Some(2));
+        lockout.failure("10.0.0.1", "alice", 3);
+        assert_eq!(lockout.retry_after("10.0.0.1", "alice", 3), Some(300));
+        lockout.failure("10.0.0.1", "alice", 303);
+        assert_eq!(lockout.retry_after("10.0.0.1", "alice", 303), Some(600));
+        let stats = lockout.stats(303);
+        assert_eq!((stats.failures, stats.rejected, stats.lockouts), (4, 5, 4));
+        assert_eq!(stats.locked, vec![(Kind::Ip, 1), (Kind::Username, 1)]);
+    }
+
+    #[test]
+    fn test_success_keeps_the_ip() {
+        let lockout = open(3, 300);
+        for now in [0, 1, 3] {
+            lockout.failure("10.0.0.1", "alice", now);
+        }
+        lockout.success("alice");
+        assert_eq!(lockout.retry_after("10.0.0.2", "alice", 3), None);
+        assert_eq!(lockout.retry_after("10.0.0.1", "bob", 3), Some(300));
+        let entries = lockout.entries(3);
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].kind, entries[0].failures, entries[0].locked), (Kind::Ip, 3, true));
+    }
+
+    #[test]
+    fn test_prune() {
+        let lockout = open(3, 300);
+        lockout.failure("10.0.0.1", "alice", 0);
+        // Quiet for less than the lockout duration since it was blocked, so the count carries on
+        assert_eq!(lockout.entries(300).len(), 2);
+        lockout.failure("10.0.0.2", "bob", 300);
+        let entries = lockout.entries(301);
+        assert_eq!(entries.iter().map(|entry| entry.subject.as_str()).collect::<Vec<_>>(), vec!["10.0.0.2", "bob"]);
+        lockout.failure("10.0.0.2", "bob", 301);
+        assert_eq!(lockout.entries(301)[0].failures, 2);
+        assert!(lockout.entries(303 + 300).is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use fernet::MultiFernet;
+use serde::{Deserialize, Serialize};
+
+use crate::squire;
+
+/// Name of the file within `storage_dir`, that the enrollments are persisted to.
+const MFA_FILE: &str = "mfa.json";
+
+/// Time (in seconds) to confirm an enrollment, or to enter the code after the password was verified.
+pub const PENDING_TTL: i64 = 300;
+
+/// Number of codes that can be entered for a login, before the password has to be verified again.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Struct for the two-factor authentication of a user, as it is persisted.
+///
+/// ## Fields
+///
+/// * `secret` - TOTP secret encrypted with the keys for the session tokens, so it is never stored in plain text.
+/// * `backup_codes` - Hashes of the backup codes that haven't been used.
+/// * `last_step` - Time step of the last TOTP code that was accepted, so a code can't be used twice.
+/// * `enrolled_at` - Epoch timestamp (in seconds) at which the enrollment was confirmed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Enrollment {
+    secret: String,
+    backup_codes: Vec<String>,
+    last_step: i64,
+    enrolled_at: i64,
+}
+
+/// Struct for a login whose password was verified, but is waiting for the code.
+///
+/// ## Fields
+///
+/// * `username` - Username of the login.
+/// * `expires_at` - Epoch timestamp (in seconds) until which the code can be entered.
+/// * `attempts` - Number of invalid codes entered.
+struct PendingLogin {
+    username: String,
+    expires_at: i64,
+    attempts: usize,
+}
+
+/// Struct for the two-factor authentication status of a user.
+///
+/// ## Fields
+///
+/// * `enabled` - Whether the user has enrolled.
+/// * `enrolled_at` - Epoch timestamp (in seconds) at which the enrollment was confirmed.
+/// * `backup_codes` - Number of backup codes that haven't been used.
+#[derive(Serialize, Debug)]
+pub struct Status {
+    pub enabled: bool,
+    pub enrolled_at: Option<i64>,
+    pub backup_codes: usize,
+}
+
+/// Struct to store the two-factor authentication of the users.
+///
+/// ## Fields
+///
+/// * `enrolled` - Enrollments keyed by the username.
+/// * `enrolling` - Secrets waiting for the first code to confirm the enrollment, along with their expiry.
+/// * `logins` - Logins waiting for the code, keyed by the token set in the `mfa_token` cookie.
+/// * `fernet` - Fernet object to encrypt the secrets at rest.
+/// * `path` - Path of the file that the enrollments are persisted to, if `storage_dir` is set.
+pub struct Mfa {
+    enrolled: Mutex<HashMap<String, Enrollment>>,
+    enrolling: Mutex<HashMap<String, (String, i64)>>,
+    logins: Mutex<HashMap<String, PendingLogin>>,
+    fernet: Arc<MultiFernet>,
+    path: Option<PathBuf>,
+}
+
+impl Mfa {
+    /// Writes the enrollments to the file, replacing it atomically.
+    fn save(&self, enrolled: &HashMap<String, Enrollment>) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let result = serde_json::to_vec_pretty(enrolled)
+            .map_err(|err| err.to_string())
+            .and_then(|content| squire::util::write_private(path, &content).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            log::error!("Failed to persist the two-factor enrollments to '{}': {}", path.display(), err);
+        }
+    }
+
+    /// Verifies a TOTP code or a backup code against an enrollment, consuming it when it is valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `enrollment` - Enrollment of the user.
+    /// * `code` - TOTP code or backup code entered by the user.
+    /// * `now` - Epoch timestamp (in seconds) of the verification.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the code is valid.
+    fn consume_code(&self, enrollment: &mut Enrollment, code: &str, now: i64) -> bool {
+        match self.fernet.decrypt(&enrollment.secret) {
+            Ok(secret) => {
+                let secret = String::from_utf8_lossy(&secret);
+                if let Some(step) = squire::totp::verify(&secret, code, now, enrollment.last_step) {
+                    enrollment.last_step = step;
+                    return true;
+                }
+            }
+            // Only the backup codes can be used, until the key that encrypted the secret is restored
+            Err(_) => log::error!("Failed to decrypt the two-factor secret, the key may have been rotated out"),
+        }
+        let hashed = squire::secure::calculate_hash(squire::totp::normalize_backup_code(code));
+        match enrollment.backup_codes.iter().position(|backup_code| *backup_code == hashed) {
+            Some(index) => {
+                enrollment.backup_codes.remove(index);
+                log::info!("Backup code used, {} remaining", enrollment.backup_codes.len());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks whether a user has enrolled in two-factor authentication.
+    pub fn is_enrolled(&self, username: &str) -> bool {
+        self.enrolled.lock().unwrap().contains_key(username)
+    }
+
+    /// Gets the two-factor authentication status of a user.
+    pub fn status(&self, username: &str) -> Status {
+        match self.enrolled.lock().unwrap().get(username) {
+            Some(enrollment) => Status {
+                enabled: true,
+                enrolled_at: Some(enrollment.enrolled_at),
+                backup_codes: enrollment.backup_codes.len(),
+            },
+            None => Status { enabled: false, enrolled_at: None, backup_codes: 0 },
+        }
+    }
+
+    /// Starts the enrollment of a user, with a new secret that has to be confirmed with a code.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    /// * `now` - Epoch timestamp (in seconds) of the enrollment.
+    ///
+    /// # Returns
+    ///
+    /// Returns the secret encoded as base32.
+    pub fn begin_enrollment(&self, username: &str, now: i64) -> String {
+        let secret = squire::totp::generate_secret();
+        let mut enrolling = self.enrolling.lock().unwrap();
+        enrolling.retain(|_, (_, expires_at)| now < *expires_at);
+        enrolling.insert(username.to_string(), (secret.clone(), now + PENDING_TTL));
+        secret
+    }
+
+    /// Confirms the enrollment of a user, with a code generated by the authenticator app.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    /// * `code` - TOTP code entered by the user.
+    /// * `now` - Epoch timestamp (in seconds) of the confirmation.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the backup codes, which are only ever shown once, or an error message.
+    pub fn confirm_enrollment(&self, username: &str, code: &str, now: i64) -> Result<Vec<String>, &'static str> {
+        let mut enrolling = self.enrolling.lock().unwrap();
+        let secret = match enrolling.get(username) {
+            Some((secret, expires_at)) if now < *expires_at => secret.clone(),
+            _ => return Err("enrollment has expired or was never started"),
+        };
+        let step = squire::totp::verify(&secret, code, now, 0).ok_or("invalid code")?;
+        enrolling.remove(username);
+        let backup_codes = squire::totp::generate_backup_codes();
+        let enrollment = Enrollment {
+            secret: self.fernet.encrypt(secret.as_bytes()),
+            backup_codes: backup_codes.iter()
+                .map(|code| squire::secure::calculate_hash(squire::totp::normalize_backup_code(code)))
+                .collect(),
+            last_step: step,
+            enrolled_at: now,
+        };
+        let mut enrolled = self.enrolled.lock().unwrap();
+        enrolled.insert(username.to_string(), enrollment);
+        self.save(&enrolled);
+        Ok(backup_codes)
+    }
+
+    /// Verifies a code for a user, consuming it when it is valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    /// * `code` - TOTP code or backup code entered by the user.
+    /// * `now` - Epoch timestamp (in seconds) of the verification.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the code is valid.
+    pub fn verify(&self, username: &str, code: &str, now: i64) -> bool {
+        let mut enrolled = self.enrolled.lock().unwrap();
+        let valid = match enrolled.get_mut(username) {
+            Some(enrollment) => self.consume_code(enrollment, code, now),
+            None => false,
+        };
+        if valid {
+            self.save(&enrolled);
+        }
+        valid
+    }
+
+    /// Removes the enrollment of a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the user had enrolled.
+    pub fn remove(&self, username: &str) -> bool {
+        let mut enrolled = self.enrolled.lock().unwrap();
+        let removed = enrolled.remove(username).is_some();
+        if removed {
+            self.save(&enrolled);
+        }
+        removed
+    }
+
+    /// Holds a login whose password was verified, until the code is entered.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username of the login.
+    /// * `now` - Epoch timestamp (in seconds) of the login.
+    ///
+    /// # Returns
+    ///
+    /// Returns the token to set in the `mfa_token` cookie.
+    pub fn begin_login(&self, username: &str, now: i64) -> String {
+        let token = squire::secure::keygen();
+        let mut logins = self.logins.lock().unwrap();
+        logins.retain(|_, login| now < login.expires_at);
+        logins.insert(token.clone(), PendingLogin {
+            username: username.to_string(),
+            expires_at: now + PENDING_TTL,
+            attempts: 0,
+        });
+        token
+    }
+
+    /// Looks up the username of a login that is waiting for the code.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Token from the `mfa_token` cookie.
+    /// * `now` - Epoch timestamp (in seconds) of the request.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` with the username, if the login hasn't expired.
+    pub fn pending_login(&self, token: &str, now: i64) -> Option<String> {
+        self.logins.lock().unwrap().get(token)
+            .filter(|login| now < login.expires_at)
+            .map(|login| login.username.clone())
+    }
+
+    /// Records an invalid code for a login, dropping it once there are too many.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Token from the `mfa_token` cookie.
+    pub fn fail_login(&self, token: &str) {
+        let mut logins = self.logins.lock().unwrap();
+        let exhausted = logins.get_mut(token).is_some_and(|login| {
+            login.attempts += 1;
+            login.attempts >= MAX_ATTEMPTS
+        });
+        if exhausted {
+            logins.remove(token);
+        }
+    }
+
+    /// Completes a login, so its token can't be used again.
+    pub fn finish_login(&self, token: &str) {
+        self.logins.lock().unwrap().remove(token);
+    }
+}
+
+/// Loads the two-factor enrollments persisted in `storage_dir`.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+/// * `fernet` - Fernet object to encrypt the secrets at rest.
+///
+/// # See Also
+///
+/// Without a `storage_dir`, the enrollments are only retained in memory, and are lost on a restart.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Mfa` struct.
+pub fn load(config: &squire::settings::Config, fernet: Arc<MultiFernet>) -> Arc<Mfa> {
+    let path = config.storage_dir.as_ref().map(|directory| PathBuf::from(directory).join(MFA_FILE));
+    let mut enrolled = HashMap::new();
+    if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+        match fs::read(path).map_err(|err| err.to_string())
+            .and_then(|content| serde_json::from_slice(&content).map_err(|err| err.to_string())) {
+            Ok(loaded) => {
+                enrolled = loaded;
+                log::info!("Loaded {} two-factor enrollments from '{}'", enrolled.len(), path.display());
+            }
+            // Starting without the enrollments would silently turn off the second factor for everyone
+            Err(err) => panic!("\nFailed to load the two-factor enrollments from '{}': {}\n", path.display(), err),
+        }
+    }
+    Arc::new(Mfa {
+        enrolled: Mutex::new(enrolled),
+        enrolling: Mutex::new(HashMap::new()),
+        logins: Mutex::new(HashMap::new()),
+        fernet,
+        path,
+    })
+}
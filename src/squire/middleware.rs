@@ -1,5 +1,22 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
 use actix_cors::Cors;
-use actix_web::http::header;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+
+use crate::squire;
+
+/// Struct for the nonce of a request, that the inline scripts of the rendered templates are allowed with.
+#[derive(Clone)]
+struct CspNonce(String);
+
+/// Struct for the IP address of the client, as resolved by the `access_control` middleware.
+#[derive(Clone)]
+struct ClientIp(IpAddr);
 
 /// Configures and returns a CORS middleware based on provided website origins.
 ///
@@ -28,3 +45,154 @@ pub fn get_cors(websites: Vec<String>) -> Cors {
     }
     cors
 }
+
+/// Checks whether a request was made over HTTPS, either directly or via a proxy that terminates TLS.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+///
+/// # See Also
+///
+/// The scheme forwarded in the `Forwarded` or `X-Forwarded-Proto` header is only followed when the connection
+/// is from a trusted proxy, since anyone can set it. Otherwise, the scheme is the one the server listens on.
+///
+/// # Returns
+///
+/// Returns a boolean flag to indicate whether the cookies should be marked `Secure`.
+pub fn is_secure(request: &HttpRequest) -> bool {
+    let from_proxy = request.app_data::<web::Data<Arc<squire::access::Access>>>()
+        .zip(request.peer_addr())
+        .is_some_and(|(access, peer)| access.is_trusted(&peer.ip()));
+    if from_proxy {
+        request.connection_info().scheme() == "https"
+    } else {
+        request.app_config().secure()
+    }
+}
+
+/// Gets the nonce generated for a request by the `security_headers` middleware.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+///
+/// # Returns
+///
+/// Returns the nonce as a `String`, to be set on the `<script>` tags of the rendered template.
+pub fn csp_nonce(request: &HttpRequest) -> String {
+    request.extensions().get::<CspNonce>().map(|nonce| nonce.0.clone()).unwrap_or_default()
+}
+
+/// Middleware that sets the security headers on every response, with a new CSP nonce for each request.
+///
+/// # Arguments
+///
+/// * `request` - Request received by the server, which the nonce is stored in before calling the handler.
+/// * `next` - Next service in the chain of middlewares.
+///
+/// # See Also
+///
+/// - Headers that are configured as an empty string are not set.
+/// - `Strict-Transport-Security` is only set for requests made over HTTPS, since browsers ignore it otherwise.
+///
+/// # Returns
+///
+/// Returns a `Result` with the response of the handler, along with the security headers.
+pub async fn security_headers(request: ServiceRequest,
+                              next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let nonce = squire::secure::keygen();
+    request.extensions_mut().insert(CspNonce(nonce.clone()));
+    let config = request.app_data::<web::Data<Arc<squire::settings::Config>>>().cloned();
+    let mut response = next.call(request).await?;
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(response),
+    };
+    let secure = is_secure(response.request());
+    let hsts = if secure && config.hsts_max_age > 0 {
+        format!("max-age={}; includeSubDomains", config.hsts_max_age)
+    } else {
+        String::new()
+    };
+    let headers = [
+        (header::CONTENT_SECURITY_POLICY, config.content_security_policy.replace("{nonce}", &nonce)),
+        (header::X_FRAME_OPTIONS, config.frame_options.clone()),
+        (header::REFERRER_POLICY, config.referrer_policy.clone()),
+        (header::STRICT_TRANSPORT_SECURITY, hsts),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        (HeaderName::from_static("cross-origin-opener-policy"), "same-origin".to_string()),
+    ];
+    let response_headers = response.headers_mut();
+    for (name, value) in headers {
+        if value.is_empty() || response_headers.contains_key(&name) {
+            continue;
+        }
+        // Values are validated during startup, except for the nonce which is always a valid header value
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response_headers.insert(name, value);
+        }
+    }
+    Ok(response)
+}
+
+/// Gets the IP address of the client that made a request, resolved via the trusted proxies.
+///
+/// # Arguments
+///
+/// * `request` - A reference to the Actix web `HttpRequest` object.
+///
+/// # Returns
+///
+/// Returns the IP address as a `String`, which falls back to the address of the connection.
+pub fn client_ip(request: &HttpRequest) -> String {
+    match request.extensions().get::<ClientIp>() {
+        Some(client_ip) => client_ip.0.to_string(),
+        None => request.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default(),
+    }
+}
+
+/// Middleware that only lets the requests from the allowed networks through, on every route.
+///
+/// # Arguments
+///
+/// * `request` - Request received by the server, which the IP address of the client is stored in.
+/// * `next` - Next service in the chain of middlewares.
+///
+/// # See Also
+///
+/// Requests are denied when the IP address can't be resolved, like for a malformed `X-Forwarded-For` header
+/// sent by a trusted proxy, since the client can't be checked against the networks.
+///
+/// # Returns
+///
+/// Returns a `Result` with the response of the handler, or a `403` response if the client is denied.
+pub async fn access_control(request: ServiceRequest,
+                            next: Next<impl MessageBody>) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let access = match request.app_data::<web::Data<Arc<squire::access::Access>>>() {
+        Some(access) => access.clone(),
+        None => return next.call(request).await.map(ServiceResponse::map_into_left_body),
+    };
+    // Each proxy may append its own header instead of extending the last one, so they are read in order
+    let forwarded_for: Vec<&str> = request.headers().get_all("x-forwarded-for")
+        .map(|value| value.to_str().unwrap_or_default())
+        .collect();
+    let forwarded_for = (!forwarded_for.is_empty()).then(|| forwarded_for.join(","));
+    let client_ip = request.peer_addr()
+        .and_then(|addr| access.client_ip(addr.ip(), forwarded_for.as_deref()));
+    match client_ip {
+        Some(client_ip) if access.permits(&client_ip) => {
+            request.extensions_mut().insert(ClientIp(client_ip));
+            next.call(request).await.map(ServiceResponse::map_into_left_body)
+        }
+        _ => {
+            squire::custom::log_connection(request.request());
+            log::warn!("Denied access to '{}' from '{}' [X-Forwarded-For: {}]",
+                request.path(),
+                client_ip.map(|ip| ip.to_string()).unwrap_or("unknown".to_string()),
+                forwarded_for.unwrap_or_default());
+            let response = HttpResponse::Forbidden().body("Access denied");
+            Ok(request.into_response(response).map_into_right_body())
+        }
+    }
+}
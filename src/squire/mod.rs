@@ -12,6 +12,22 @@ pub mod parser;
 pub mod custom;
 /// Module that handles the authentication and
 pub mod authenticator;
+/// Module that tracks the failed login attempts, to back off and lock out brute-force attempts.
+pub mod lockout;
+/// Module for the verifiers of the passwords, that the logins are checked against.
+pub mod credentials;
+/// Module that tracks the nonces issued for the login challenge, to reject replayed logins.
+pub mod nonces;
+/// Module for the RFC 6238 time-based one-time passwords, used as the second factor.
+pub mod totp;
+/// Module that stores the two-factor enrollments and the logins waiting for a code.
+pub mod mfa;
+/// Module that stores the hashed API tokens, that machine clients authenticate with as a bearer.
+pub mod tokens;
+/// Module for the networks that are allowed or denied access, and the trusted proxies.
+pub mod access;
+/// Module for the certificates served over HTTPS, which are reloaded on `SIGHUP`.
+pub mod tls;
 /// Module for the functions that handle encryption/encoding and decryption/decoding.
 pub mod secure;
 /// Module for utility functions.
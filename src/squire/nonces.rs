@@ -0,0 +1,211 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::squire;
+
+/// Time (in seconds) a nonce issued by the challenge endpoint can be used to login.
+pub const NONCE_TTL: i64 = 120;
+
+/// Maximum number of nonces that are outstanding at once, after which the oldest ones are dropped.
+const MAX_NONCES: usize = 10000;
+
+/// Maximum number of nonces that are outstanding at once for an IP address, after which the challenge is refused.
+const MAX_NONCES_PER_IP: usize = 32;
+
+/// Struct for a nonce that has been issued, but not used yet.
+///
+/// ## Fields
+///
+/// * `username` - Username that the nonce is issued for.
+/// * `ip` - IP address of the client that requested the challenge.
+/// * `expires_at` - Epoch timestamp (in seconds) at which the nonce expires.
+struct Issued {
+    username: String,
+    ip: String,
+    expires_at: i64,
+}
+
+/// Struct for the nonces that are outstanding, along with the order they expire in.
+///
+/// ## Fields
+///
+/// * `issued` - Nonces that haven't been used, keyed by the nonce.
+/// * `queue` - Nonces in the order they were issued, which is also the order they expire in.
+/// * `per_ip` - Outstanding nonces of each IP address, in the order they were issued.
+///
+/// ## See Also
+///
+/// Nonces that are consumed are left in the `queue`, and skipped once they reach the front of it.
+#[derive(Default)]
+struct Outstanding {
+    issued: HashMap<String, Issued>,
+    queue: VecDeque<String>,
+    per_ip: HashMap<String, VecDeque<String>>,
+}
+
+impl Outstanding {
+    /// Removes a nonce, along with its entry for the IP address.
+    fn remove(&mut self, nonce: &str) -> Option<Issued> {
+        let issued = self.issued.remove(nonce)?;
+        if let Some(nonces) = self.per_ip.get_mut(&issued.ip) {
+            nonces.retain(|outstanding| outstanding != nonce);
+            if nonces.is_empty() {
+                self.per_ip.remove(&issued.ip);
+            }
+        }
+        Some(issued)
+    }
+
+    /// Drops the nonces from the front of the queue, while they have expired or `evict` holds.
+    fn drop_front(&mut self, now: i64, evict: impl Fn(&Self) -> bool) {
+        while let Some(nonce) = self.queue.front() {
+            let expired = self.issued.get(nonce).map_or(true, |issued| now >= issued.expires_at);
+            if !expired && !evict(self) {
+                break;
+            }
+            let nonce = self.queue.pop_front().unwrap();
+            self.remove(&nonce);
+        }
+    }
+}
+
+/// Struct to track the nonces issued for the login challenge, so that each one is accepted only once.
+///
+/// ## Fields
+///
+/// * `outstanding` - Nonces that haven't been used, along with the order they expire in.
+pub struct Nonces {
+    outstanding: Mutex<Outstanding>,
+}
+
+impl Nonces {
+    /// Issues a nonce for a username, which has to be signed along with the login.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Username that the nonce is issued for.
+    /// * `ip` - IP address of the client that requested the challenge.
+    /// * `now` - Epoch timestamp (in seconds) of the challenge.
+    ///
+    /// # See Also
+    ///
+    /// Nonces are issued to unauthenticated clients, so the outstanding ones are capped for each IP address,
+    /// and in total by dropping the oldest first. Every nonce lasts as long, so the oldest is always at the front.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the nonce, or the time (in seconds) until the IP address can request another one.
+    pub fn issue(&self, username: &str, ip: &str, now: i64) -> Result<String, i64> {
+        let mut outstanding = self.outstanding.lock().unwrap();
+        outstanding.drop_front(now, |outstanding| outstanding.issued.len() >= MAX_NONCES);
+        if let Some(nonces) = outstanding.per_ip.get(ip).filter(|nonces| nonces.len() >= MAX_NONCES_PER_IP) {
+            let expires_at = nonces.front().and_then(|nonce| outstanding.issued.get(nonce))
+                .map_or(now + NONCE_TTL, |issued| issued.expires_at);
+            return Err((expires_at - now).max(1));
+        }
+        let nonce = squire::secure::keygen();
+        outstanding.issued.insert(nonce.clone(), Issued {
+            username: username.to_string(),
+            ip: ip.to_string(),
+            expires_at: now + NONCE_TTL,
+        });
+        outstanding.queue.push_back(nonce.clone());
+        outstanding.per_ip.entry(ip.to_string()).or_default().push_back(nonce.clone());
+        // Consumed nonces are only skipped at the front, so the queue is compacted once they pile up
+        if outstanding.queue.len() > 2 * outstanding.issued.len() + MAX_NONCES_PER_IP {
+            let Outstanding { issued, queue, .. } = &mut *outstanding;
+            queue.retain(|nonce| issued.contains_key(nonce));
+        }
+        Ok(nonce)
+    }
+
+    /// Consumes a nonce, which is removed whether it is valid or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `nonce` - Nonce received along with the login.
+    /// * `username` - Username of the login.
+    /// * `now` - Epoch timestamp (in seconds) of the login.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the nonce was issued for the username and hasn't expired.
+    pub fn consume(&self, nonce: &str, username: &str, now: i64) -> bool {
+        match self.outstanding.lock().unwrap().remove(nonce) {
+            Some(issued) => issued.username == username && now < issued.expires_at,
+            None => false,
+        }
+    }
+}
+
+/// Instantiates the `Nonces` struct without any outstanding nonces.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Nonces` struct.
+pub fn store() -> Arc<Nonces> {
+    Arc::new(Nonces {
+        outstanding: Mutex::new(Outstanding::default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    #[test]
+    fn test_consume_once() {
+        let nonces = store();
+        let nonce = nonces.issue("alice", "10.0.0.1", 0).unwrap();
+        assert!(!nonces.consume(&nonce, "bob", 1));
+        let nonce = nonces.issue("alice", "10.0.0.1", 0).unwrap();
+        assert!(nonces.consume(&nonce, "alice", 1));
+        assert!(!nonces.consume(&nonce, "alice", 1));
+        let nonce = nonces.issue("alice", "10.0.0.1", 0).unwrap();
+        assert!(!nonces.consume(&nonce, "alice", NONCE_TTL));
+    }
+
+    #[test]
+    fn test_capped_per_ip() {
+        let nonces = store();
+        for second in 0..MAX_NONCES_PER_IP as i64 {
+            nonces.issue("alice", "10.0.0.1", second).unwrap();
+        }
+        let last = MAX_NONCES_PER_IP as i64 - 1;
+        assert_eq!(nonces.issue("alice", "10.0.0.1", last), Err(NONCE_TTL - last));
+        // Other clients are not affected by the flood
+        let nonce = nonces.issue("bob", "10.0.0.2", last).unwrap();
+        assert!(nonces.consume(&nonce, "bob", last));
+        // The oldest nonce expiring frees up a slot
+        assert!(nonces.issue("alice", "10.0.0.1", NONCE_TTL).is_ok());
+        assert!(nonces.issue("alice", "10.0.0.1", NONCE_TTL).is_err());
+    }
+
+    #[test]
+    fn test_consumed_nonces_free_the_ip() {
+        let nonces = store();
+        for _ in 0..MAX_NONCES_PER_IP * 4 {
+            let nonce = nonces.issue("alice", "10.0.0.1", 0).unwrap();
+            assert!(nonces.consume(&nonce, "alice", 0));
+        }
+        let outstanding = nonces.outstanding.lock().unwrap();
+        assert!(outstanding.issued.is_empty() && outstanding.per_ip.is_empty());
+        // Consumed nonces are compacted out of the queue, instead of piling up until they expire
+        assert!(outstanding.queue.len() <= MAX_NONCES_PER_IP + 1);
+    }
+
+    #[test]
+    fn test_capped_in_total() {
+        let nonces = store();
+        let first = nonces.issue("alice", "10.0.0.0", 0).unwrap();
+        for index in 1..MAX_NONCES {
+            nonces.issue("alice", &format!("10.0.{}.{}", index / 256, index % 256), 0).unwrap();
+        }
+        let nonce = nonces.issue("alice", "10.1.0.0", 0).unwrap();
+        assert_eq!(nonces.outstanding.lock().unwrap().issued.len(), MAX_NONCES);
+        assert!(!nonces.consume(&first, "alice", 0));
+        assert!(nonces.consume(&nonce, "alice", 0));
+    }
+}
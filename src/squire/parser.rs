@@ -1,7 +1,44 @@
 use std::env;
 use std::process::exit;
 
-use crate::constant;
+use crate::{constant, squire};
+
+/// Prompts for a password twice, and prints its verifier to be stored in the configuration.
+///
+/// # See Also
+///
+/// The password is read without echoing it, and must meet the same complexity as a password in plain text.
+fn hash_password() -> ! {
+    let password = rpassword::prompt_password("Password: ").unwrap_or_else(|err| {
+        eprintln!("Failed to read the password: {}", err);
+        exit(1)
+    });
+    if let Err(err) = squire::startup::complexity_checker("password", &password) {
+        eprintln!("{}", err);
+        exit(1)
+    }
+    let confirmation = rpassword::prompt_password("Confirm password: ").unwrap_or_default();
+    if password != confirmation {
+        eprintln!("Passwords do not match.");
+        exit(1)
+    }
+    match squire::secure::hash_password(&password) {
+        Ok(hash) => {
+            println!("{}", hash);
+            exit(0)
+        }
+        Err(err) => {
+            eprintln!("Failed to hash the password: {}", err);
+            exit(1)
+        }
+    }
+}
+
+/// Prints a new key to encrypt the session tokens, to be added first in `secret_keys` or `secret_key_file`.
+fn generate_key() -> ! {
+    println!("{}", fernet::Fernet::generate_key());
+    exit(0)
+}
 
 /// Parses and returns the command-line arguments.
 ///
@@ -21,10 +58,15 @@ pub fn arguments(metadata: &constant::MetaData) -> String {
             "-h" | "--help" => {
                 let helper = "SysMonk takes the arguments, --env_file and --version/-v\n\n\
                 --env_file: Custom filename to load the environment variables. Defaults to '.env'\n\
-                --version: Get the package version.\n".to_string();
+                --version: Get the package version.\n\n\
+                Commands:\n\n\
+                hash-password: Generate a verifier to store as the password, instead of plain text.\n\
+                generate-key: Generate a key to encrypt the session tokens, to set or rotate the secret keys.\n".to_string();
                 println!("Usage: {} [OPTIONS]\n\n{}", args[0], helper);
                 exit(0)
             }
+            "hash-password" => hash_password(),
+            "generate-key" => generate_key(),
             "-V" | "-v" | "--version" => {
                 version = true;
             }
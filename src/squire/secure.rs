@@ -1,8 +1,15 @@
 extern crate base64;
 extern crate sha2;
 
-use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use argon2::password_hash::PasswordHash;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD_NO_PAD, engine::general_purpose::URL_SAFE, Engine as _};
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
 use rand::{thread_rng, Rng};
+use serde::Serialize;
 use sha2::{Digest, Sha512};
 
 /// Calculates the SHA-512 hash value for the given payload.
@@ -127,3 +134,294 @@ pub fn keygen() -> String {
         .collect();
     token
 }
+
+/// Identifier of the algorithm in a stored verifier, which is an Argon2id hash that is derived further like SCRAM.
+const VERIFIER_ALGORITHM: &str = "scram-argon2id";
+
+/// Length (in bytes) of the Argon2id hash, that the client derives from the password.
+const KEY_LENGTH: usize = Params::DEFAULT_OUTPUT_LEN;
+
+/// Represents the parameters of an Argon2id hash, that a client needs to derive the same key from a password.
+///
+/// ## Fields
+///
+/// * `algorithm` - Always `argon2id`, which tells the client how to derive the key.
+/// * `salt` - Salt of the hash, encoded as base64 without padding.
+/// * `memory` - Memory cost in KiB.
+/// * `iterations` - Number of iterations.
+/// * `parallelism` - Degree of parallelism.
+/// * `length` - Length of the hash in bytes.
+#[derive(Serialize, Clone, Debug)]
+pub struct HashParams {
+    pub algorithm: &'static str,
+    pub salt: String,
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub length: usize,
+}
+
+impl HashParams {
+    /// Creates the parameters for a salt, with the recommended costs of Argon2id.
+    fn new(salt: &[u8]) -> HashParams {
+        let params = Params::default();
+        HashParams {
+            algorithm: "argon2id",
+            salt: STANDARD_NO_PAD.encode(salt),
+            memory: params.m_cost(),
+            iterations: params.t_cost(),
+            parallelism: params.p_cost(),
+            length: KEY_LENGTH,
+        }
+    }
+}
+
+/// Represents what the server stores for a password, to verify a login without being able to perform one.
+///
+/// ## Fields
+///
+/// * `params` - Parameters the client derives the Argon2id hash of the password with.
+/// * `stored_key` - Hash of the client key, which is derived from the Argon2id hash.
+///
+/// # See Also
+///
+/// Like `StoredKey` in SCRAM, the client key is `HMAC(hash, "client")` and the stored key is `H(client key)`.
+/// The login proves the knowledge of the client key, which can't be recovered from the stored key.
+///
+/// ## References
+/// - [RFC 5802](https://datatracker.ietf.org/doc/html/rfc5802#section-3)
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    pub params: HashParams,
+    stored_key: Vec<u8>,
+}
+
+/// Computes the HMAC-SHA512 of the data with the key.
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // Neither of these can fail with an HMAC key and a digest that are supported by every build of OpenSSL
+    let key = PKey::hmac(key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha512(), &key).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+/// Derives the stored key from the Argon2id hash of a password.
+fn stored_key(hash: &[u8]) -> Vec<u8> {
+    Sha512::digest(hmac_sha512(hash, b"client")).to_vec()
+}
+
+/// Derives the Argon2id hash of a password, like the client does during the login.
+fn derive_key(password: &str, params: &HashParams) -> Result<Vec<u8>, String> {
+    let salt = STANDARD_NO_PAD.decode(&params.salt).map_err(|err| err.to_string())?;
+    let costs = Params::new(params.memory, params.iterations, params.parallelism, Some(params.length))
+        .map_err(|err| err.to_string())?;
+    let mut key = vec![0u8; params.length];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, costs)
+        .hash_password_into(password.as_bytes(), &salt, &mut key)
+        .map_err(|err| err.to_string())?;
+    Ok(key)
+}
+
+/// Derives the verifier of a password stored as plain text, with a salt that is unique to the user.
+///
+/// # Arguments
+///
+/// * `password` - Password in plain text.
+/// * `salt` - Salt derived with `decoy_salt`, so the salt is unique to the user but stable across restarts.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `Verifier` struct, or the reason the hash couldn't be derived.
+pub fn derive_verifier(password: &str, salt: &[u8]) -> Result<Verifier, String> {
+    let params = HashParams::new(salt);
+    let key = derive_key(password, &params)?;
+    Ok(Verifier { params, stored_key: stored_key(&key) })
+}
+
+/// Generates a verifier for a password, using a random salt and the recommended parameters.
+///
+/// # Arguments
+///
+/// * `password` - Password to generate the verifier for.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the verifier as a PHC string, like
+/// `$scram-argon2id$v=19$m=19456,t=2,p=1$<salt>$<stored key>`.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt: [u8; 16] = thread_rng().gen();
+    let verifier = derive_verifier(password, &salt)?;
+    Ok(format!("${}$v={}$m={},t={},p={}${}${}",
+               VERIFIER_ALGORITHM, Version::V0x13 as u32,
+               verifier.params.memory, verifier.params.iterations, verifier.params.parallelism,
+               verifier.params.salt, STANDARD_NO_PAD.encode(&verifier.stored_key)))
+}
+
+/// Checks whether the password is stored as a hash, instead of plain text.
+pub fn is_hashed(password: &str) -> bool {
+    password.starts_with("$argon2") || password.starts_with(&format!("${}$", VERIFIER_ALGORITHM))
+}
+
+/// Parses a verifier in PHC string format, as it is generated by `hash_password`.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `Verifier` struct, or the reason it is invalid.
+pub fn parse_verifier(password: &str) -> Result<Verifier, String> {
+    let hash = PasswordHash::new(password).map_err(|err| err.to_string())?;
+    if hash.algorithm == Algorithm::Argon2id.ident() {
+        // Earlier versions stored the hash itself, which is enough to log in without the password
+        return Err("Argon2id hashes are no longer accepted, since they can be used to log in as they are, \
+                    generate a verifier with 'hash-password' instead".to_string());
+    }
+    if hash.algorithm.as_str() != VERIFIER_ALGORITHM {
+        return Err(format!("algorithm '{}' is not supported, expected '{}'", hash.algorithm, VERIFIER_ALGORITHM));
+    }
+    if hash.version.is_some_and(|version| version != Version::V0x13 as u32) {
+        return Err("only version 19 of Argon2id is supported".to_string());
+    }
+    let costs = Params::try_from(&hash).map_err(|err| err.to_string())?;
+    let salt = hash.salt.ok_or("verifier is missing the salt".to_string())?;
+    let mut buffer = [0u8; 64];
+    let salt = salt.decode_b64(&mut buffer).map_err(|err| err.to_string())?;
+    let stored_key = hash.hash.ok_or("verifier is missing the stored key".to_string())?;
+    if stored_key.len() != 64 {
+        return Err("stored key should be 64 bytes".to_string());
+    }
+    let mut params = HashParams::new(salt);
+    params.memory = costs.m_cost();
+    params.iterations = costs.t_cost();
+    params.parallelism = costs.p_cost();
+    Params::new(params.memory, params.iterations, params.parallelism, Some(params.length))
+        .map_err(|err| err.to_string())?;
+    Ok(Verifier { params, stored_key: stored_key.as_bytes().to_vec() })
+}
+
+/// Derives the salt for a username, from a secret key that is unique to the server.
+///
+/// # Arguments
+///
+/// * `username` - Username to derive the salt for.
+/// * `key` - Secret key that is unique to the server, so that the salt can't be predicted.
+///
+/// # See Also
+///
+/// The salt is stable for a username, so that a user that doesn't exist looks the same as one that does,
+/// and a password stored as plain text gets a salt that can't be told apart from a fake one either.
+pub fn decoy_salt(username: &str, key: &str) -> Vec<u8> {
+    hmac_sha512(key.as_bytes(), username.as_bytes())[..16].to_vec()
+}
+
+/// Generates the parameters of a fake Argon2id hash for a username that doesn't exist.
+pub fn decoy_params(username: &str, key: &str) -> HashParams {
+    HashParams::new(&decoy_salt(username, key))
+}
+
+impl Verifier {
+    /// Verifies the proof sent by the client, that it knows the client key.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Message the client signed, which covers the username, the nonce and the timestamp.
+    /// * `proof` - Hex encoded proof, which is the client key XOR'ed with `HMAC(stored key, message)`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the proof is valid.
+    pub fn verify(&self, message: &str, proof: &str) -> bool {
+        let proof: Option<Vec<u8>> = (proof.len() == 128 && proof.is_ascii())
+            .then(|| (0..proof.len()).step_by(2).map(|i| u8::from_str_radix(&proof[i..i + 2], 16).ok()).collect())
+            .flatten();
+        let proof = match proof {
+            Some(proof) => proof,
+            None => return false,
+        };
+        let signature = hmac_sha512(&self.stored_key, message.as_bytes());
+        let client_key: Vec<u8> = proof.iter().zip(signature).map(|(byte, mask)| byte ^ mask).collect();
+        memcmp::eq(&Sha512::digest(client_key), &self.stored_key)
+    }
+}
+
+/// Parses the keys to encrypt the session tokens from the content of a file, with one key per line.
+///
+/// # Arguments
+///
+/// * `content` - Content of the file, where blank lines and lines starting with `#` are ignored.
+///
+/// # Returns
+///
+/// Returns a `Vec` of the keys, in the order they appear in the file.
+pub fn parse_keys(content: &str) -> Vec<String> {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Signs a message like the login page does, with the Argon2id hash of the password.
+    fn prove(hash: &[u8], message: &str) -> String {
+        let client_key = hmac_sha512(hash, b"client");
+        let signature = hmac_sha512(&Sha512::digest(&client_key), message.as_bytes());
+        client_key.iter().zip(signature).map(|(byte, mask)| format!("{:02x}", byte ^ mask)).collect()
+    }
+
+    #[test]
+    fn test_derive_key_matches_the_login_page() {
+        // Derived by the script in `templates::argon2`, with the parameters it receives from `/challenge`
+        let params = HashParams::new(b"somesaltsomesalt");
+        let key = derive_key("password", &params).unwrap();
+        assert_eq!(key.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(),
+                   "2b5dc4054886ec957ef59c73b661c54dd6fb274590b278f657c6d96aac8fa6d1");
+    }
+
+    #[test]
+    fn test_verifier_round_trip() {
+        let verifier = hash_password("hunter2").unwrap();
+        assert!(verifier.starts_with("$scram-argon2id$v=19$m=19456,t=2,p=1$"));
+        assert!(is_hashed(&verifier));
+        let parsed = parse_verifier(&verifier).unwrap();
+        let key = derive_key("hunter2", &parsed.params).unwrap();
+        assert!(parsed.verify("message", &prove(&key, "message")));
+        assert!(!parsed.verify("other message", &prove(&key, "message")));
+        let wrong = derive_key("hunter3", &parsed.params).unwrap();
+        assert!(!parsed.verify("message", &prove(&wrong, "message")));
+    }
+
+    #[test]
+    fn test_stored_key_is_not_a_proof() {
+        let verifier = hash_password("hunter2").unwrap();
+        let parsed = parse_verifier(&verifier).unwrap();
+        // Whoever reads the stored key can't sign a message with it, in place of the hash
+        assert!(!parsed.verify("message", &prove(&parsed.stored_key, "message")));
+        let encoded: String = parsed.stored_key.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert!(!parsed.verify("message", &encoded));
+        assert!(!parsed.verify("message", "zz"));
+    }
+
+    #[test]
+    fn test_parse_verifier_rejects_hashes() {
+        let hash = "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHRzb21lc2FsdA$K13EBUiG7JV+9ZxztmHFTdb7J0WQsnj2V8bZaqyPptE";
+        assert!(is_hashed(hash));
+        assert!(parse_verifier(hash).unwrap_err().contains("hash-password"));
+        assert!(parse_verifier("$scram-argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHRzb21lc2FsdA$c2hvcnQ").is_err());
+        assert!(parse_verifier("plain text").is_err());
+    }
+
+    #[test]
+    fn test_decoy_params_are_stable() {
+        let params = decoy_params("alice", "key");
+        assert_eq!(params.salt, decoy_params("alice", "key").salt);
+        assert_ne!(params.salt, decoy_params("bob", "key").salt);
+        assert_ne!(params.salt, decoy_params("alice", "other key").salt);
+        let verifier = derive_verifier("hunter2", &decoy_salt("alice", "key")).unwrap();
+        assert_eq!(verifier.params.salt, params.salt);
+        assert_eq!((verifier.params.memory, verifier.params.iterations), (params.memory, params.iterations));
+    }
+}
@@ -1,12 +1,11 @@
+use serde::{Deserialize, Serialize};
 use std::net::ToSocketAddrs;
 use std::thread;
 
 /// Represents the configuration parameters for SysMonk.
 pub struct Config {
-    /// Username for login.
-    pub username: String,
-    /// Password for login.
-    pub password: String,
+    /// User accounts that are allowed to login, along with their roles.
+    pub users: Vec<User>,
 
     /// Debug flag to enable debug level logging.
     pub debug: bool,
@@ -18,6 +17,35 @@ pub struct Config {
     pub port: u16,
     /// Duration of a session in seconds.
     pub session_duration: i64,
+    /// Number of consecutive failed logins after which an IP address or a username is locked out.
+    pub lockout_threshold: usize,
+    /// Duration in seconds of the first lockout, which doubles with every failed login after it.
+    pub lockout_duration: i64,
+    /// Keys to encrypt the session tokens, with the newest first. Older keys are only used for decryption.
+    pub secret_keys: Vec<String>,
+    /// Path to the PEM encoded certificate chain, to serve over HTTPS.
+    pub cert_file: Option<String>,
+    /// Path to the PEM encoded private key of the certificate.
+    pub key_file: Option<String>,
+    /// Boolean flag to generate a self-signed certificate on the first run, when the files don't exist.
+    pub self_signed: bool,
+    /// Port number for a plain HTTP listener that redirects to HTTPS.
+    pub redirect_port: Option<u16>,
+    /// Value of the `Content-Security-Policy` header, where `{nonce}` is replaced with the nonce of each request.
+    pub content_security_policy: String,
+    /// Value of the `X-Frame-Options` header.
+    pub frame_options: String,
+    /// Value of the `Referrer-Policy` header.
+    pub referrer_policy: String,
+    /// Duration in seconds for the `Strict-Transport-Security` header, which is only sent over HTTPS.
+    pub hsts_max_age: i64,
+
+    /// List of networks (CIDR) that are allowed access. Every network is allowed when empty.
+    pub allowed_networks: Vec<String>,
+    /// List of networks (CIDR) that are denied access, which takes precedence over the allowed networks.
+    pub denied_networks: Vec<String>,
+    /// List of networks (CIDR) of the proxies, whose `X-Forwarded-For` header is trusted.
+    pub trusted_proxies: Vec<String>,
 
     /// Number of worker threads to spin up the server.
     pub workers: usize,
@@ -29,6 +57,129 @@ pub struct Config {
     pub services: Vec<String>,
     /// List of processes to monitor.
     pub processes: Vec<String>,
+    /// List of patterns (regex) for the filesystem types or mount points to include in disk usage.
+    pub disk_include: Vec<String>,
+    /// List of patterns (regex) for the filesystem types or mount points to exclude from disk usage.
+    pub disk_exclude: Vec<String>,
+
+    /// Directory to persist the metrics history. Metrics are only retained in memory when not set.
+    pub storage_dir: Option<String>,
+    /// Duration in seconds to retain metrics at full resolution in memory.
+    pub history_raw: i64,
+    /// Duration in seconds to retain metrics at full resolution on disk.
+    pub retention_raw: i64,
+    /// Duration in seconds to retain 1-minute averages on disk.
+    pub retention_minute: i64,
+    /// Duration in seconds to retain hourly averages on disk.
+    pub retention_hour: i64,
+    /// Bearer token required to scrape the `/metrics` endpoint. Scrapes are not authenticated when not set.
+    pub metrics_token: Option<String>,
+
+    /// List of alert rules to evaluate against the sampled metrics.
+    pub alert_rules: Vec<String>,
+    /// Interval in seconds between two evaluations of the alert rules.
+    pub alert_interval: i64,
+    /// List of notifiers that alerts are sent to, when they fire or resolve.
+    pub notifiers: Vec<Notifier>,
+    /// List of recurring maintenance windows, during which the matching alerts are not notified.
+    pub maintenance_windows: Vec<String>,
+}
+
+impl Config {
+    /// Looks up a user account by its username.
+    pub fn user(&self, username: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.username == username)
+    }
+}
+
+/// Represents the role of a user, where each role is allowed everything the previous one is.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Allowed to view the system resources, history and alerts.
+    Viewer,
+    /// Allowed to view the services, processes and containers, and to silence alerts.
+    Operator,
+    /// Allowed everything, including the administration of the server.
+    Admin,
+}
+
+/// Represents a user account that is allowed to login.
+#[derive(Deserialize, Debug, Clone)]
+pub struct User {
+    /// Username for login.
+    pub username: String,
+    /// Password for login.
+    pub password: String,
+    /// Role of the user, which decides the routes and topics the user can access.
+    #[serde(default = "default_role")]
+    pub role: Role,
+}
+
+/// Represents the configuration of a notifier for alerts.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Notifier {
+    /// Channel that the notifications are sent through.
+    #[serde(flatten)]
+    pub channel: Channel,
+    /// List of patterns (regex) for the alert rules to notify. Notifies all the rules when empty.
+    #[serde(default)]
+    pub rules: Vec<String>,
+    /// List of states to notify, either `firing` or `resolved`.
+    #[serde(default = "default_notify_states")]
+    pub states: Vec<String>,
+    /// Maximum number of notifications to send within the rate period. Unlimited when set to zero.
+    #[serde(default)]
+    pub rate_limit: usize,
+    /// Duration in seconds for the rate limit.
+    #[serde(default = "default_rate_period")]
+    pub rate_period: i64,
+}
+
+/// Represents the channel of a notifier, along with its settings.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Channel {
+    /// Generic webhook that receives the alert as JSON.
+    Webhook {
+        url: String,
+        #[serde(default = "default_retries")]
+        retries: u32,
+        #[serde(default = "default_backoff")]
+        backoff: u64,
+    },
+    /// Slack or Microsoft Teams compatible webhook, that receives the alert as text.
+    Slack {
+        url: String,
+        #[serde(default = "default_retries")]
+        retries: u32,
+        #[serde(default = "default_backoff")]
+        backoff: u64,
+    },
+    /// Email sent via SMTP.
+    Email {
+        host: String,
+        #[serde(default = "default_smtp_port")]
+        port: u16,
+        /// Either `starttls`, `tls` or `none`.
+        #[serde(default = "default_smtp_tls")]
+        tls: String,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+    /// RFC 5424 syslog message sent over UDP or TCP.
+    Syslog {
+        host: String,
+        #[serde(default = "default_syslog_port")]
+        port: u16,
+        /// Either `udp` or `tcp`.
+        #[serde(default = "default_syslog_protocol")]
+        protocol: String,
+        #[serde(default = "default_syslog_facility")]
+        facility: u8,
+    },
 }
 
 /// Returns the default value for debug flag.
@@ -59,6 +210,42 @@ pub fn default_port() -> u16 { 8000 }
 /// Returns the default session duration (900 seconds)
 pub fn default_session_duration() -> i64 { 900 }
 
+/// Returns the default number of failed logins before a lockout (5)
+pub fn default_lockout_threshold() -> usize { 5 }
+
+/// Returns the default lockout duration (300 seconds)
+pub fn default_lockout_duration() -> i64 { 300 }
+
+/// Returns the default value for generating a self-signed certificate.
+pub fn default_self_signed() -> bool { false }
+
+/// Returns the default content security policy, which only allows the scripts with the nonce of the request.
+///
+/// Scripts loaded by the allowed ones are trusted as well.
+pub fn default_content_security_policy() -> String {
+    [
+        "default-src 'self'",
+        "script-src 'nonce-{nonce}' 'strict-dynamic'",
+        "style-src 'self' 'unsafe-inline' https:",
+        "img-src 'self' data: https:",
+        "font-src 'self' data: https:",
+        "connect-src 'self'",
+        "object-src 'none'",
+        "base-uri 'none'",
+        "form-action 'self'",
+        "frame-ancestors 'none'",
+    ].join("; ")
+}
+
+/// Returns the default value for the `X-Frame-Options` header (DENY)
+pub fn default_frame_options() -> String { "DENY".to_string() }
+
+/// Returns the default value for the `Referrer-Policy` header (no-referrer)
+pub fn default_referrer_policy() -> String { "no-referrer".to_string() }
+
+/// Returns the default duration for the `Strict-Transport-Security` header (365 days)
+pub fn default_hsts_max_age() -> i64 { 31_536_000 }
+
 /// Returns the default number of worker threads (half of logical cores)
 pub fn default_workers() -> usize {
     let logical_cores = thread::available_parallelism();
@@ -76,3 +263,57 @@ pub fn default_max_connections() -> usize { 3 }
 
 /// Returns an empty vec
 pub fn default_vec() -> Vec<String> { Vec::new() }
+
+/// Returns the default time span to retain metrics at full resolution in memory (1 hour)
+pub fn default_history_raw() -> i64 { 3_600 }
+
+/// Returns the default retention for metrics at full resolution (2 days)
+pub fn default_retention_raw() -> i64 { 172_800 }
+
+/// Returns the default retention for 1-minute averages (30 days)
+pub fn default_retention_minute() -> i64 { 2_592_000 }
+
+/// Returns the default retention for hourly averages (365 days)
+pub fn default_retention_hour() -> i64 { 31_536_000 }
+
+/// Returns the default interval between two evaluations of the alert rules (10 seconds)
+pub fn default_alert_interval() -> i64 { 10 }
+
+/// Returns the default role for a user account (viewer)
+pub fn default_role() -> Role { Role::Viewer }
+
+/// Returns the default states that notifiers are sent for
+pub fn default_notify_states() -> Vec<String> { vec!["firing".to_string(), "resolved".to_string()] }
+
+/// Returns the default period for the notification rate limit (3600 seconds)
+pub fn default_rate_period() -> i64 { 3600 }
+
+/// Returns the default number of retries for webhooks (3)
+pub fn default_retries() -> u32 { 3 }
+
+/// Returns the default backoff in seconds before the first retry for webhooks (1 second)
+pub fn default_backoff() -> u64 { 1 }
+
+/// Returns the default SMTP port (587)
+pub fn default_smtp_port() -> u16 { 587 }
+
+/// Returns the default SMTP encryption (starttls)
+pub fn default_smtp_tls() -> String { "starttls".to_string() }
+
+/// Returns the default syslog port (514)
+pub fn default_syslog_port() -> u16 { 514 }
+
+/// Returns the default syslog protocol (udp)
+pub fn default_syslog_protocol() -> String { "udp".to_string() }
+
+/// Returns the default syslog facility (1 - user-level messages)
+pub fn default_syslog_facility() -> u8 { 1 }
+
+/// Returns the default patterns to exclude pseudo and duplicate filesystems from disk usage.
+pub fn default_disk_exclude() -> Vec<String> {
+    vec![
+        r"^(autofs|binfmt_misc|bpf|cgroup2?|configfs|debugfs|devfs|devpts|devtmpfs|fusectl|hugetlbfs|mqueue|nsfs|overlay|proc|pstore|securityfs|squashfs|sysfs|tmpfs|tracefs)$".to_string(),
+        r"^/(dev|proc|run|sys|snap)(/|$)".to_string(),
+        r"^/System/Volumes/(Hardware|iSCPreboot|Preboot|Update|VM|xarts)$".to_string(),
+    ]
+}
@@ -2,7 +2,7 @@ use std;
 use std::io::Write;
 
 use crate::squire::settings;
-use crate::{constant, squire};
+use crate::{alerting, constant, resources, squire};
 use chrono::{DateTime, Local};
 use regex::Regex;
 
@@ -49,39 +49,66 @@ pub fn init_logger(debug: bool, utc: bool, crate_name: &String) {
         .init();
 }
 
-/// Extracts the mandatory env vars by key and parses it as `HashMap<String, String>` and `PathBuf`
+/// Parses a list of user accounts in JSON format.
+///
+/// # Arguments
+///
+/// * `key` - Key of the environment variable or the file, that the value was loaded from.
+/// * `value` - List of user accounts in JSON format.
+///
+/// # Panics
+///
+/// If the value is not a valid list of user accounts.
+fn parse_users(key: &str, value: &str) -> Vec<settings::User> {
+    match serde_json::from_str::<Vec<settings::User>>(value) {
+        Ok(users) => users,
+        Err(err) => {
+            panic!("\n{}\n\texpected a list of users, received an invalid value: {} [value=invalid]\n", key, err);
+        }
+    }
+}
+
+/// Extracts the user accounts from the `username` and `password` env vars, the `users` env var and the `users_file`
+///
+/// # See Also
+///
+/// - The account set with `username` and `password` is an `admin`, to remain compatible with a single user setup.
+/// - The accounts set in `users` or `users_file` are `viewer`s, unless a role is specified.
 ///
 /// # Returns
 ///
-/// Returns a tuple of `HashMap<String, String>` and `PathBuf`.
+/// Returns a `Vec` of user accounts.
 ///
 /// # Panics
 ///
-/// If the value is missing or if there is an error parsing the `HashMap`
-fn mandatory_vars() -> (String, String) {
+/// If there are no user accounts, or if there is an error parsing them.
+fn mandatory_vars() -> Vec<settings::User> {
     let mut errors = "".to_owned();
-    let username = match std::env::var("username") {
-        Ok(val) => val,
-        Err(_) => {
-            errors.push_str(
-                "\nusername\n\texpected a string, received null [value=missing]\n",
-            );
-            "".to_string()
-        }
-    };
-    let password = match std::env::var("password") {
-        Ok(val) => val,
-        Err(_) => {
-            errors.push_str(
-                "\npassword\n\texpected a string, received null [value=missing]\n",
-            );
-            "".to_string()
+    let mut users = Vec::new();
+    match (std::env::var("username"), std::env::var("password")) {
+        (Ok(username), Ok(password)) => users.push(settings::User { username, password, role: settings::Role::Admin }),
+        (Ok(_), Err(_)) => errors.push_str("\npassword\n\texpected a string, received null [value=missing]\n"),
+        (Err(_), Ok(_)) => errors.push_str("\nusername\n\texpected a string, received null [value=missing]\n"),
+        (Err(_), Err(_)) => (),
+    }
+    if let Ok(val) = std::env::var("users") {
+        users.extend(parse_users("users", &val));
+    }
+    if let Ok(path) = std::env::var("users_file") {
+        match std::fs::read_to_string(&path) {
+            Ok(content) => users.extend(parse_users("users_file", &content)),
+            Err(err) => errors.push_str(&format!("\nusers_file\n\t[{}] {} [value=invalid]\n", path, err)),
         }
-    };
+    }
+    if users.is_empty() && errors.is_empty() {
+        errors.push_str(
+            "\nusername\n\texpected 'username' and 'password', or 'users' or 'users_file' [value=missing]\n"
+        );
+    }
     if !errors.is_empty() {
         panic!("{}", errors);
     }
-    (username, password)
+    users
 }
 
 /// Extracts the env var by key and parses it as a `bool`
@@ -209,36 +236,137 @@ fn parse_vec(key: &str) -> Option<Vec<String>> {
     }
 }
 
+/// Extracts the env var by key and parses it as a `Vec<settings::Notifier>`
+///
+/// # Arguments
+///
+/// * `key` - Key for the environment variable.
+///
+/// # Returns
+///
+/// Returns an `Option<Vec<settings::Notifier>>` if the value is available.
+///
+/// # Panics
+///
+/// If the value is present, but it is an invalid data-type.
+fn parse_notifiers(key: &str) -> Option<Vec<settings::Notifier>> {
+    match std::env::var(key) {
+        Ok(val) => match serde_json::from_str::<Vec<settings::Notifier>>(&val) {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                panic!("\n{}\n\texpected a list of notifiers, received '{}': {} [value=invalid]\n", key, val, err);
+            }
+        },
+        Err(_) => None,
+    }
+}
+
+/// Extracts the keys to encrypt the session tokens from the `secret_keys` env var and the `secret_key_file`
+///
+/// # See Also
+///
+/// The keys in `secret_keys` take precedence over the ones in `secret_key_file`, so the newest key should be first.
+///
+/// # Returns
+///
+/// Returns a `Vec` of the keys, which is empty when neither is set.
+///
+/// # Panics
+///
+/// If the `secret_key_file` can't be read.
+fn secret_keys() -> Vec<String> {
+    let mut keys = parse_vec("secret_keys").unwrap_or_default();
+    if let Ok(path) = std::env::var("secret_key_file") {
+        match std::fs::read_to_string(&path) {
+            Ok(content) => keys.extend(squire::secure::parse_keys(&content)),
+            Err(err) => panic!("\nsecret_key_file\n\t[{}] {} [value=invalid]\n", path, err),
+        }
+    }
+    keys
+}
+
 /// Handler that's responsible to parse all the env vars.
 ///
 /// # Returns
 ///
 /// Instantiates the `Config` struct with the required parameters.
 fn load_env_vars() -> settings::Config {
-    let (username, password) = mandatory_vars();
+    let users = mandatory_vars();
     let debug = parse_bool("debug").unwrap_or(settings::default_debug());
     let utc_logging = parse_bool("utc_logging").unwrap_or(settings::default_utc_logging());
     let host = std::env::var("host").unwrap_or(settings::default_host());
     let port = parse_u16("port").unwrap_or(settings::default_port());
     let session_duration = parse_i64("session_duration").unwrap_or(settings::default_session_duration());
+    let lockout_threshold = parse_usize("lockout_threshold").unwrap_or(settings::default_lockout_threshold());
+    let lockout_duration = parse_i64("lockout_duration").unwrap_or(settings::default_lockout_duration());
+    let secret_keys = secret_keys();
+    let cert_file = std::env::var("cert_file").ok();
+    let key_file = std::env::var("key_file").ok();
+    let self_signed = parse_bool("self_signed").unwrap_or(settings::default_self_signed());
+    let redirect_port = parse_u16("redirect_port");
+    let content_security_policy = std::env::var("content_security_policy")
+        .unwrap_or(settings::default_content_security_policy());
+    let frame_options = std::env::var("frame_options").unwrap_or(settings::default_frame_options());
+    let referrer_policy = std::env::var("referrer_policy").unwrap_or(settings::default_referrer_policy());
+    let hsts_max_age = parse_i64("hsts_max_age").unwrap_or(settings::default_hsts_max_age());
+    let allowed_networks = parse_vec("allowed_networks").unwrap_or(settings::default_vec());
+    let denied_networks = parse_vec("denied_networks").unwrap_or(settings::default_vec());
+    let trusted_proxies = parse_vec("trusted_proxies").unwrap_or(settings::default_vec());
     let workers = parse_usize("workers").unwrap_or(settings::default_workers());
     let max_connections = parse_usize("max_connections").unwrap_or(settings::default_max_connections());
     let websites = parse_vec("websites").unwrap_or(settings::default_vec());
     let services = parse_vec("services").unwrap_or(settings::default_vec());
     let processes = parse_vec("processes").unwrap_or(settings::default_vec());
+    let disk_include = parse_vec("disk_include").unwrap_or(settings::default_vec());
+    let disk_exclude = parse_vec("disk_exclude").unwrap_or(settings::default_disk_exclude());
+    let storage_dir = std::env::var("storage_dir").ok();
+    let history_raw = parse_i64("history_raw").unwrap_or(settings::default_history_raw());
+    let retention_raw = parse_i64("retention_raw").unwrap_or(settings::default_retention_raw());
+    let retention_minute = parse_i64("retention_minute").unwrap_or(settings::default_retention_minute());
+    let retention_hour = parse_i64("retention_hour").unwrap_or(settings::default_retention_hour());
+    let metrics_token = std::env::var("metrics_token").ok();
+    let alert_rules = parse_vec("alert_rules").unwrap_or(settings::default_vec());
+    let alert_interval = parse_i64("alert_interval").unwrap_or(settings::default_alert_interval());
+    let notifiers = parse_notifiers("notifiers").unwrap_or_default();
+    let maintenance_windows = parse_vec("maintenance_windows").unwrap_or_default();
     settings::Config {
-        username,
-        password,
+        users,
         debug,
         utc_logging,
         host,
         port,
         session_duration,
+        lockout_threshold,
+        lockout_duration,
+        secret_keys,
+        cert_file,
+        key_file,
+        self_signed,
+        redirect_port,
+        content_security_policy,
+        frame_options,
+        referrer_policy,
+        hsts_max_age,
+        allowed_networks,
+        denied_networks,
+        trusted_proxies,
         workers,
         max_connections,
         websites,
         services,
-        processes
+        processes,
+        disk_include,
+        disk_exclude,
+        storage_dir,
+        history_raw,
+        retention_raw,
+        retention_minute,
+        retention_hour,
+        metrics_token,
+        alert_rules,
+        alert_interval,
+        notifiers,
+        maintenance_windows,
     }
 }
 
@@ -253,6 +381,7 @@ fn load_env_vars() -> settings::Config {
 ///
 /// # Arguments
 ///
+/// * `key` - Name of the setting that the password belongs to, used in the error message.
 /// * `password` - A reference to a string slice (`&str`) that represents the password to check.
 ///
 /// # Returns
@@ -260,14 +389,14 @@ fn load_env_vars() -> settings::Config {
 /// This function returns a `Result<(), String>`.
 /// - `Ok(())` is returned if all conditions are met.
 /// - `Err(String)` is returned with an error message if any condition fails.
-pub fn complexity_checker(password: &str) -> Result<(), String> {
+pub fn complexity_checker(key: &str, password: &str) -> Result<(), String> {
     let mock_password = "*".repeat(password.len());
 
     // Check minimum length
     if password.len() < 8 {
         return Err(
             format!(
-                "\npassword\n\t[{}] password must be at least 8 or more characters [value=invalid]\n", mock_password
+                "\n{}\n\t[{}] password must be at least 8 or more characters [value=invalid]\n", key, mock_password
             )
         );
     }
@@ -277,7 +406,7 @@ pub fn complexity_checker(password: &str) -> Result<(), String> {
     if !has_digit.is_match(password) {
         return Err(
             format!(
-                "\npassword\n\t[{}] password must include at least one digit [value=invalid]\n", mock_password
+                "\n{}\n\t[{}] password must include at least one digit [value=invalid]\n", key, mock_password
             )
         );
     }
@@ -287,7 +416,7 @@ pub fn complexity_checker(password: &str) -> Result<(), String> {
     if !has_uppercase.is_match(password) {
         return Err(
             format!(
-                "\npassword\n\t[{}] password must include at least one uppercase letter [value=invalid]\n", mock_password
+                "\n{}\n\t[{}] password must include at least one uppercase letter [value=invalid]\n", key, mock_password
             )
         );
     }
@@ -297,7 +426,7 @@ pub fn complexity_checker(password: &str) -> Result<(), String> {
     if !has_lowercase.is_match(password) {
         return Err(
             format!(
-                "\npassword\n\t[{}] password must include at least one lowercase letter [value=invalid]\n", mock_password
+                "\n{}\n\t[{}] password must include at least one lowercase letter [value=invalid]\n", key, mock_password
             )
         );
     }
@@ -307,7 +436,7 @@ pub fn complexity_checker(password: &str) -> Result<(), String> {
     if !has_special_char.is_match(password) {
         return Err(
             format!(
-                "\npassword\n\t[{}] password must contain at least one special character [value=invalid]\n", mock_password
+                "\n{}\n\t[{}] password must contain at least one special character [value=invalid]\n", key, mock_password
             )
         );
     }
@@ -322,17 +451,157 @@ pub fn complexity_checker(password: &str) -> Result<(), String> {
 fn validate_vars() -> settings::Config {
     let config = load_env_vars();
     let mut errors = "".to_owned();
-    if config.username.len() < 4 {
-        let err1 = format!(
-            "\nusername\n\t[{}] username should be at least 4 or more characters [value=invalid]\n",
-            config.username
-        );
-        errors.push_str(&err1);
+    for (index, user) in config.users.iter().enumerate() {
+        if user.username.len() < 4 {
+            let err1 = format!(
+                "\nusername\n\t[{}] username should be at least 4 or more characters [value=invalid]\n",
+                user.username
+            );
+            errors.push_str(&err1);
+        }
+        if config.users[..index].iter().any(|other| other.username == user.username) {
+            errors.push_str(&format!("\nusername\n\t[{}] username is declared more than once [value=invalid]\n",
+                                     user.username));
+        }
+        let key = format!("password for '{}'", user.username);
+        if squire::secure::is_hashed(&user.password) {
+            if let Err(err) = squire::secure::parse_verifier(&user.password) {
+                errors.push_str(&format!("\n{}\n\tinvalid verifier: {} [value=invalid]\n", key, err));
+            }
+            continue;
+        }
+        match complexity_checker(&key, &user.password) {
+            Ok(_) => (),
+            Err(err) => {
+                errors.push_str(&err);
+            }
+        }
     }
-    match complexity_checker(&config.password) {
-        Ok(_) => (),
-        Err(err) => {
-            errors.push_str(&err);
+    if config.lockout_threshold < 1 {
+        errors.push_str(&format!(
+            "\nlockout_threshold\n\t[{}] threshold must be at least 1 [value=invalid]\n", config.lockout_threshold
+        ));
+    }
+    if config.lockout_duration <= 0 {
+        errors.push_str(&format!(
+            "\nlockout_duration\n\t[{}] duration must be a positive number of seconds [value=invalid]\n",
+            config.lockout_duration
+        ));
+    }
+    for (index, key) in config.secret_keys.iter().enumerate() {
+        // Keys are secrets, so only their position is reported
+        if fernet::Fernet::new(key).is_none() {
+            errors.push_str(&format!(
+                "\nsecret_keys\n\t[key #{}] expected 32 url-safe base64 encoded bytes [value=invalid]\n", index + 1
+            ));
+        }
+    }
+    match (&config.cert_file, &config.key_file) {
+        (Some(_), None) => errors.push_str("\nkey_file\n\texpected along with 'cert_file' [value=missing]\n"),
+        (None, Some(_)) => errors.push_str("\ncert_file\n\texpected along with 'key_file' [value=missing]\n"),
+        _ => (),
+    }
+    match squire::tls::paths(&config) {
+        Some((cert_file, key_file)) if !config.self_signed => {
+            for (key, path) in [("cert_file", cert_file), ("key_file", key_file)] {
+                if !path.is_file() {
+                    errors.push_str(&format!(
+                        "\n{}\n\t[{}] file doesn't exist, set 'self_signed' to generate one [value=invalid]\n",
+                        key, path.display()
+                    ));
+                }
+            }
+        }
+        Some(_) => (),
+        None if config.self_signed => errors.push_str(
+            "\nself_signed\n\texpected 'cert_file' and 'key_file', or 'storage_dir' to generate them in [value=missing]\n"
+        ),
+        None => (),
+    }
+    if let Some(redirect_port) = config.redirect_port {
+        if squire::tls::paths(&config).is_none() {
+            errors.push_str(&format!(
+                "\nredirect_port\n\t[{}] redirecting requires HTTPS to be enabled [value=invalid]\n", redirect_port
+            ));
+        } else if redirect_port == config.port {
+            errors.push_str(&format!(
+                "\nredirect_port\n\t[{}] port must differ from the HTTPS port [value=invalid]\n", redirect_port
+            ));
+        }
+    }
+    let headers = [
+        ("content_security_policy", &config.content_security_policy),
+        ("frame_options", &config.frame_options),
+        ("referrer_policy", &config.referrer_policy),
+    ];
+    for (key, value) in headers {
+        if actix_web::http::header::HeaderValue::from_str(value).is_err() {
+            errors.push_str(&format!("\n{}\n\t[{}] invalid header value [value=invalid]\n", key, value));
+        }
+    }
+    if config.hsts_max_age < 0 {
+        errors.push_str(&format!(
+            "\nhsts_max_age\n\t[{}] duration must not be negative [value=invalid]\n", config.hsts_max_age
+        ));
+    }
+    let networks = [
+        ("allowed_networks", &config.allowed_networks),
+        ("denied_networks", &config.denied_networks),
+        ("trusted_proxies", &config.trusted_proxies),
+    ];
+    for (key, cidrs) in networks {
+        for cidr in cidrs {
+            if let Err(err) = squire::access::parse(cidr) {
+                errors.push_str(&format!("\n{}\n\t[{}] {} [value=invalid]\n", key, cidr, err));
+            }
+        }
+    }
+    for (key, patterns) in [("disk_include", &config.disk_include), ("disk_exclude", &config.disk_exclude)] {
+        for pattern in patterns {
+            if let Err(err) = Regex::new(pattern) {
+                errors.push_str(&format!(
+                    "\n{}\n\t[{}] invalid pattern: {} [value=invalid]\n", key, pattern, err
+                ));
+            }
+        }
+    }
+    if config.history_raw < 60 || config.history_raw > resources::history::MAX_HISTORY_RAW {
+        errors.push_str(&format!(
+            "\nhistory_raw\n\t[{}] history must be between 60 and {} seconds [value=invalid]\n",
+            config.history_raw, resources::history::MAX_HISTORY_RAW
+        ));
+    }
+    let retention = [
+        ("retention_raw", config.retention_raw),
+        ("retention_minute", config.retention_minute),
+        ("retention_hour", config.retention_hour),
+    ];
+    for (key, value) in retention {
+        if value <= 0 {
+            errors.push_str(&format!(
+                "\n{}\n\t[{}] retention must be a positive number of seconds [value=invalid]\n", key, value
+            ));
+        }
+    }
+    for rule in &config.alert_rules {
+        if let Err(err) = alerting::rules::parse(rule) {
+            errors.push_str(&format!("\nalert_rules\n\t[{}] {} [value=invalid]\n", rule, err));
+        }
+    }
+    if config.alert_interval < 1 || config.alert_interval as u64 > resources::collector::MAX_INTERVAL {
+        errors.push_str(&format!(
+            "\nalert_interval\n\t[{}] interval must be between 1 and {} seconds [value=invalid]\n",
+            config.alert_interval, resources::collector::MAX_INTERVAL
+        ));
+    }
+    for window in &config.maintenance_windows {
+        if let Err(err) = alerting::silences::parse_window(window) {
+            errors.push_str(&format!("\nmaintenance_windows\n\t[{}] {} [value=invalid]\n", window, err));
+        }
+    }
+    for notifier in &config.notifiers {
+        if let Err(err) = alerting::notifiers::validate(notifier) {
+            errors.push_str(&format!("\nnotifiers\n\t{} [value=invalid]\n", err));
         }
     }
     if !errors.is_empty() {
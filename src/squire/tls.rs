@@ -0,0 +1,262 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{self, AlpnError, SniError, SslAcceptor, SslAcceptorBuilder, SslContext, SslFiletype, SslMethod};
+use openssl::x509::extension::{BasicConstraints, ExtendedKeyUsage, SubjectAlternativeName};
+use openssl::x509::{X509Builder, X509NameBuilder};
+
+use crate::squire;
+
+/// Names of the certificate and the private key generated within `storage_dir`, when they aren't set.
+const SELF_SIGNED_FILES: (&str, &str) = ("cert.pem", "key.pem");
+
+/// Number of days a self-signed certificate is valid for.
+const SELF_SIGNED_DAYS: u32 = 365;
+
+/// Protocols offered via ALPN, in the wire format and order of preference.
+const ALPN_PROTOCOLS: &[u8] = b"\x02h2\x08http/1.1";
+
+/// Struct for the certificate served over HTTPS, which can be reloaded without restarting the server.
+///
+/// ## Fields
+///
+/// * `context` - SSL context with the certificate and the private key that are currently served.
+/// * `cert_file` - Path to the PEM encoded certificate chain.
+/// * `key_file` - Path to the PEM encoded private key.
+pub struct Certificates {
+    context: RwLock<SslContext>,
+    cert_file: PathBuf,
+    key_file: PathBuf,
+}
+
+impl Certificates {
+    /// Reads the certificate and the private key again, which are served to the connections made after it.
+    ///
+    /// # See Also
+    ///
+    /// The current certificate remains in use if the new one can't be loaded, so a bad renewal doesn't take
+    /// the server down.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with an error message if the files can't be loaded.
+    pub fn reload(&self) -> Result<(), String> {
+        let context = build_context(&self.cert_file, &self.key_file)?;
+        *self.context.write().unwrap() = context;
+        Ok(())
+    }
+
+    /// Creates the acceptor for the server, which picks up the current certificate for every handshake.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the `SslAcceptorBuilder`, or an error message if the files can't be loaded.
+    pub fn acceptor(self: &Arc<Self>) -> Result<SslAcceptorBuilder, String> {
+        let mut builder = acceptor_builder(&self.cert_file, &self.key_file)?;
+        let certificates = self.clone();
+        // Servername callback runs during every handshake, even without SNI, so it can swap in the reloaded context
+        builder.set_servername_callback(move |ssl, _| {
+            let context = certificates.context.read().unwrap();
+            ssl.set_ssl_context(&context).map_err(|_| SniError::ALERT_FATAL)
+        });
+        Ok(builder)
+    }
+}
+
+/// Resolves the paths of the certificate and the private key to serve over HTTPS.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns an `Option` with the paths of the certificate and the private key, if HTTPS is enabled.
+pub fn paths(config: &squire::settings::Config) -> Option<(PathBuf, PathBuf)> {
+    match (&config.cert_file, &config.key_file, &config.storage_dir) {
+        (Some(cert_file), Some(key_file), _) => Some((PathBuf::from(cert_file), PathBuf::from(key_file))),
+        (None, None, Some(storage_dir)) if config.self_signed => Some((
+            Path::new(storage_dir).join(SELF_SIGNED_FILES.0),
+            Path::new(storage_dir).join(SELF_SIGNED_FILES.1)
+        )),
+        _ => None,
+    }
+}
+
+/// Creates an acceptor builder with the certificate and the private key, along with the ALPN protocols.
+///
+/// # Arguments
+///
+/// * `cert_file` - Path to the PEM encoded certificate chain.
+/// * `key_file` - Path to the PEM encoded private key.
+///
+/// # Returns
+///
+/// Returns a `Result` with the `SslAcceptorBuilder`, or an error message if the files can't be loaded.
+fn acceptor_builder(cert_file: &Path, key_file: &Path) -> Result<SslAcceptorBuilder, String> {
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).map_err(|err| err.to_string())?;
+    builder.set_certificate_chain_file(cert_file)
+        .map_err(|err| format!("failed to load '{}': {}", cert_file.display(), err))?;
+    builder.set_private_key_file(key_file, SslFiletype::PEM)
+        .map_err(|err| format!("failed to load '{}': {}", key_file.display(), err))?;
+    builder.check_private_key()
+        .map_err(|err| format!("private key doesn't match the certificate: {}", err))?;
+    // ALPN is negotiated with the swapped context, so it has to offer the same protocols as the server
+    builder.set_alpn_select_callback(|_, client| {
+        ssl::select_next_proto(ALPN_PROTOCOLS, client).ok_or(AlpnError::NOACK)
+    });
+    Ok(builder)
+}
+
+/// Builds the SSL context with the certificate and the private key.
+fn build_context(cert_file: &Path, key_file: &Path) -> Result<SslContext, String> {
+    Ok(acceptor_builder(cert_file, key_file)?.build().into_context())
+}
+
+/// Creates a self-signed certificate and its private key, valid for the host and `localhost`.
+///
+/// # Arguments
+///
+/// * `host` - Host that the server is running on, which is added as a subject alternative name.
+///
+/// # Returns
+///
+/// Returns a `Result` with the PEM encoded certificate and private key, or the `ErrorStack` from openssl.
+fn self_signed(host: &str) -> Result<(Vec<u8>, Vec<u8>), ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, host)?;
+    name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "SysMonk")?;
+    let name = name.build();
+
+    let mut serial = BigNum::new()?;
+    serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+    let serial = serial.to_asn1_integer()?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(SELF_SIGNED_DAYS)?;
+
+    let mut builder = X509Builder::new()?;
+    builder.set_version(2)?;
+    builder.set_serial_number(&serial)?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(&key)?;
+    builder.set_not_before(&not_before)?;
+    builder.set_not_after(&not_after)?;
+
+    let mut alternative_names = SubjectAlternativeName::new();
+    alternative_names.dns("localhost").ip("127.0.0.1").ip("::1");
+    match host.parse::<IpAddr>() {
+        Ok(ip) if !ip.is_loopback() => { alternative_names.ip(host); }
+        Err(_) if host != "localhost" => { alternative_names.dns(host); }
+        _ => (),
+    }
+    let alternative_names = alternative_names.build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(alternative_names)?;
+    builder.append_extension(BasicConstraints::new().critical().build()?)?;
+    builder.append_extension(ExtendedKeyUsage::new().server_auth().build()?)?;
+    builder.sign(&key, MessageDigest::sha256())?;
+
+    Ok((builder.build().to_pem()?, key.private_key_to_pem_pkcs8()?))
+}
+
+/// Generates a self-signed certificate and writes it along with its private key.
+///
+/// # Arguments
+///
+/// * `cert_file` - Path to write the PEM encoded certificate to.
+/// * `key_file` - Path to write the PEM encoded private key to, which is only readable by the owner.
+/// * `host` - Host that the server is running on, which is added as a subject alternative name.
+///
+/// # Returns
+///
+/// Returns a `Result` with an error message if the certificate can't be generated or written.
+fn generate_self_signed(cert_file: &Path, key_file: &Path, host: &str) -> Result<(), String> {
+    let (certificate, private_key) = self_signed(host).map_err(|err| err.to_string())?;
+    for path in [cert_file, key_file] {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|err| format!("failed to create '{}': {}", parent.display(), err))?;
+        }
+    }
+    squire::util::write_private(key_file, &private_key)
+        .map_err(|err| format!("failed to write '{}': {}", key_file.display(), err))?;
+    fs::write(cert_file, certificate)
+        .map_err(|err| format!("failed to write '{}': {}", cert_file.display(), err))?;
+    Ok(())
+}
+
+/// Loads the certificate to serve over HTTPS, generating a self-signed one on the first run if enabled.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # Returns
+///
+/// Returns an `Option` with the constructed `Arc` for the `Certificates` struct, if HTTPS is enabled.
+///
+/// # Panics
+///
+/// If the certificate can't be generated or loaded, since serving over plain HTTP instead isn't acceptable.
+pub fn load(config: &squire::settings::Config) -> Option<Arc<Certificates>> {
+    let (cert_file, key_file) = paths(config)?;
+    if config.self_signed && !(cert_file.exists() && key_file.exists()) {
+        if let Err(err) = generate_self_signed(&cert_file, &key_file, &config.host) {
+            panic!("\nself_signed\n\tfailed to generate a certificate: {}\n", err);
+        }
+        log::warn!("Generated a self-signed certificate at '{}', which browsers will warn about",
+            cert_file.display());
+    }
+    match build_context(&cert_file, &key_file) {
+        Ok(context) => Some(Arc::new(Certificates {
+            context: RwLock::new(context),
+            cert_file,
+            key_file,
+        })),
+        Err(err) => panic!("\ncert_file\n\t{} [value=invalid]\n", err),
+    }
+}
+
+/// Reloads the certificate whenever the process receives a `SIGHUP`, so that a renewal doesn't need a restart.
+///
+/// # Arguments
+///
+/// * `certificates` - Certificate served over HTTPS.
+///
+/// # See Also
+///
+/// This has to be called within the runtime of the server, since the signal is awaited on a spawned task.
+#[cfg(unix)]
+pub fn reload_on_hangup(certificates: Arc<Certificates>) {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            log::error!("Failed to listen for SIGHUP, the certificate can't be reloaded: {}", err);
+            return;
+        }
+    };
+    actix_web::rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match certificates.reload() {
+                Ok(_) => log::info!("Reloaded the certificate from '{}'", certificates.cert_file.display()),
+                Err(err) => log::error!("Failed to reload the certificate, serving the previous one: {}", err),
+            }
+        }
+    });
+}
+
+/// Reloading on a signal is only supported on unix, so the certificate is loaded once on other platforms.
+#[cfg(not(unix))]
+pub fn reload_on_hangup(_: Arc<Certificates>) {}
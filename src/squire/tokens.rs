@@ -0,0 +1,352 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::squire;
+
+/// Name of the file within `storage_dir`, that the tokens are persisted to.
+const TOKENS_FILE: &str = "tokens.json";
+
+/// Prefix of every token, so that a leaked one is easy to recognize.
+const PREFIX: &str = "smk_";
+
+/// Maximum length of the name of a token.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Time (in seconds) the last used time has to advance by, before it is persisted again.
+const LAST_USED_PRECISION: i64 = 60;
+
+/// Struct for an API token, as it is listed.
+///
+/// ## Fields
+///
+/// * `id` - Identifier of the token, which is also the part of the token before the secret.
+/// * `name` - Name of the token, to tell the clients apart.
+/// * `role` - Role granted to the token, which is capped by the current role of the user who created it.
+/// * `created_by` - Username of the admin who created the token.
+/// * `created_at` - Epoch timestamp (in seconds) at which the token was created.
+/// * `expires_at` - Epoch timestamp (in seconds) at which the token expires, if it ever does.
+/// * `last_used` - Epoch timestamp (in seconds) at which the token was last used, if it has been.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub role: squire::settings::Role,
+    pub created_by: String,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used: Option<i64>,
+}
+
+impl ApiToken {
+    /// Checks whether the token has expired.
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+/// Struct for an API token, as it is persisted.
+///
+/// ## Fields
+///
+/// * `token` - Token as it is listed.
+/// * `hash` - Hash of the secret, so the token itself is never stored.
+/// * `persisted_use` - Last used time that was persisted, to avoid writing the file on every request.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct StoredToken {
+    #[serde(flatten)]
+    token: ApiToken,
+    hash: String,
+    #[serde(skip)]
+    persisted_use: Option<i64>,
+}
+
+/// Struct to store the API tokens for the machine clients.
+///
+/// ## Fields
+///
+/// * `tokens` - Tokens keyed by their identifier.
+/// * `path` - Path of the file that the tokens are persisted to, if `storage_dir` is set.
+pub struct Tokens {
+    tokens: Mutex<HashMap<String, StoredToken>>,
+    path: Option<PathBuf>,
+}
+
+impl Tokens {
+    /// Writes the tokens to the file, replacing it atomically.
+    fn save(&self, tokens: &HashMap<String, StoredToken>) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let result = serde_json::to_vec_pretty(tokens)
+            .map_err(|err| err.to_string())
+            .and_then(|content| squire::util::write_private(path, &content).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            log::error!("Failed to persist the API tokens to '{}': {}", path.display(), err);
+        }
+    }
+
+    /// Creates a token, which is only ever returned once.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the token, which has to be unique.
+    /// * `role` - Role granted to the token.
+    /// * `created_by` - Username of the admin who created the token.
+    /// * `expires_in` - Time (in seconds) until the token expires. Never expires when not specified.
+    /// * `now` - Epoch timestamp (in seconds) of the creation.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the token and its details, or an error message for an invalid name or expiry.
+    pub fn create(&self,
+                  name: &str,
+                  role: squire::settings::Role,
+                  created_by: &str,
+                  expires_in: Option<i64>,
+                  now: i64) -> Result<(String, ApiToken), String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!("name must be between 1 and {} characters", MAX_NAME_LENGTH));
+        }
+        if expires_in.is_some_and(|expires_in| expires_in <= 0) {
+            return Err("expires_in must be a positive number of seconds".to_string());
+        }
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, stored| !stored.token.is_expired(now));
+        if tokens.values().any(|stored| stored.token.name == name) {
+            return Err(format!("a token named '{}' already exists", name));
+        }
+        let id = format!("{:012x}", thread_rng().gen::<u64>() >> 16);
+        let secret = squire::secure::keygen();
+        let token = ApiToken {
+            id: id.clone(),
+            name: name.to_string(),
+            role,
+            created_by: created_by.to_string(),
+            created_at: now,
+            expires_at: expires_in.map(|expires_in| now + expires_in),
+            last_used: None,
+        };
+        tokens.insert(id.clone(), StoredToken {
+            token: token.clone(),
+            hash: squire::secure::calculate_hash(secret.clone()),
+            persisted_use: None,
+        });
+        self.save(&tokens);
+        Ok((format!("{}{}.{}", PREFIX, id, secret), token))
+    }
+
+    /// Lists the tokens that haven't expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Epoch timestamp (in seconds) of the request.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec` of the tokens, with the most recently created first.
+    pub fn list(&self, now: i64) -> Vec<ApiToken> {
+        let mut tokens: Vec<ApiToken> = self.tokens.lock().unwrap().values()
+            .filter(|stored| !stored.token.is_expired(now))
+            .map(|stored| stored.token.clone())
+            .collect();
+        tokens.sort_by_key(|token| Reverse(token.created_at));
+        tokens
+    }
+
+    /// Revokes a token, which is rejected from the next request onwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the token.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the token existed.
+    pub fn revoke(&self, id: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        let revoked = tokens.remove(id).is_some();
+        if revoked {
+            self.save(&tokens);
+        }
+        revoked
+    }
+
+    /// Checks whether a token still exists and hasn't expired, for the connections opened with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the token.
+    /// * `now` - Epoch timestamp (in seconds) of the check.
+    ///
+    /// # Returns
+    ///
+    /// Returns a boolean flag to indicate whether the token is still active.
+    pub fn is_active(&self, id: &str, now: i64) -> bool {
+        self.tokens.lock().unwrap().get(id).is_some_and(|stored| !stored.token.is_expired(now))
+    }
+
+    /// Verifies a token received as a bearer, and marks it as used.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Token received in the `Authorization` header.
+    /// * `now` - Epoch timestamp (in seconds) of the request.
+    ///
+    /// # See Also
+    ///
+    /// The last used time is always updated in memory, but only persisted once it advanced by a minute,
+    /// so that a client polling the APIs doesn't rewrite the file on every request.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option` with the token, if it is valid and hasn't expired.
+    pub fn verify(&self, token: &str, now: i64) -> Option<ApiToken> {
+        let (id, secret) = token.strip_prefix(PREFIX)?.split_once('.')?;
+        let mut tokens = self.tokens.lock().unwrap();
+        let stored = tokens.get_mut(id)?;
+        if stored.hash != squire::secure::calculate_hash(secret.to_string()) || stored.token.is_expired(now) {
+            return None;
+        }
+        stored.token.last_used = Some(now);
+        let token = stored.token.clone();
+        if stored.persisted_use.map_or(true, |last_used| now - last_used >= LAST_USED_PRECISION) {
+            stored.persisted_use = Some(now);
+            self.save(&tokens);
+        }
+        Some(token)
+    }
+}
+
+/// Loads the API tokens persisted in `storage_dir`.
+///
+/// # Arguments
+///
+/// * `config` - Configuration data for the application.
+///
+/// # See Also
+///
+/// Without a `storage_dir`, the tokens are only retained in memory, and are lost on a restart.
+///
+/// # Returns
+///
+/// Returns the constructed `Arc` for the `Tokens` struct.
+pub fn load(config: &squire::settings::Config) -> Arc<Tokens> {
+    let path = config.storage_dir.as_ref().map(|directory| PathBuf::from(directory).join(TOKENS_FILE));
+    let mut tokens: HashMap<String, StoredToken> = HashMap::new();
+    if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+        match fs::read(path).map_err(|err| err.to_string())
+            .and_then(|content| serde_json::from_slice(&content).map_err(|err| err.to_string())) {
+            Ok(loaded) => {
+                tokens = loaded;
+                log::info!("Loaded {} API tokens from '{}'", tokens.len(), path.display());
+            }
+            // Starting without the tokens would break every client, and revoked ones can't come back this way
+            Err(err) => panic!("\nFailed to load the API tokens from '{}': {}\n", path.display(), err),
+        }
+    }
+    for stored in tokens.values_mut() {
+        stored.persisted_use = stored.token.last_used;
+    }
+    Arc::new(Tokens {
+        tokens: Mutex::new(tokens),
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::squire::settings::Role;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    fn open(path: Option<PathBuf>) -> Tokens {
+        Tokens {
+            tokens: Mutex::new(HashMap::new()),
+            path,
+        }
+    }
+
+    /// Reads the last used time of a token, as it was persisted.
+    fn persisted_use(path: &PathBuf, id: &str) -> Option<i64> {
+        let tokens: HashMap<String, StoredToken> = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+        tokens[id].token.last_used
+    }
+
+    #[test]
+    fn test_verify() {
+        let tokens = open(None);
+        let (token, created) = tokens.create("ci", Role::Viewer, "admin", None, 0).unwrap();
+        assert!(token.starts_with(PREFIX));
+        let verified = tokens.verify(&token, 10).unwrap();
+        assert_eq!((verified.id.as_str(), verified.last_used), (created.id.as_str(), Some(10)));
+        let (id, secret) = token.strip_prefix(PREFIX).unwrap().split_once('.').unwrap();
+        assert!(tokens.verify(&format!("{}{}.{}", PREFIX, id, squire::secure::keygen()), 10).is_none());
+        assert!(tokens.verify(&format!("{}.{}", id, secret), 10).is_none());
+        assert!(tokens.verify(&format!("{}{}{}", PREFIX, id, secret), 10).is_none());
+        assert!(tokens.verify(&format!("{}{}.{}", PREFIX, "0".repeat(12), secret), 10).is_none());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let tokens = open(None);
+        assert!(tokens.create("ci", Role::Viewer, "admin", Some(0), 0).is_err());
+        let (token, created) = tokens.create("ci", Role::Viewer, "admin", Some(60), 0).unwrap();
+        assert_eq!(created.expires_at, Some(60));
+        assert!(tokens.verify(&token, 59).is_some());
+        assert!(tokens.is_active(&created.id, 59));
+        assert!(tokens.verify(&token, 60).is_none());
+        assert!(!tokens.is_active(&created.id, 60));
+        assert!(tokens.list(60).is_empty());
+    }
+
+    #[test]
+    fn test_revoke() {
+        let tokens = open(None);
+        let (token, created) = tokens.create("ci", Role::Operator, "admin", None, 0).unwrap();
+        assert!(tokens.revoke(&created.id));
+        assert!(!tokens.revoke(&created.id));
+        assert!(tokens.verify(&token, 1).is_none());
+        assert!(!tokens.is_active(&created.id, 1));
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let tokens = open(None);
+        tokens.create("ci", Role::Viewer, "admin", Some(60), 0).unwrap();
+        assert!(tokens.create(" ci ", Role::Admin, "admin", None, 30).is_err());
+        assert!(tokens.create("", Role::Viewer, "admin", None, 30).is_err());
+        assert!(tokens.create(&"a".repeat(MAX_NAME_LENGTH + 1), Role::Viewer, "admin", None, 30).is_err());
+        // The name of an expired token can be used again
+        assert!(tokens.create("ci", Role::Viewer, "admin", None, 60).is_ok());
+        assert_eq!(tokens.list(60).len(), 1);
+    }
+
+    #[test]
+    fn test_persisted_use_is_throttled() {
+        let directory = std::env::temp_dir().join(format!("sysmonk-tokens-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(TOKENS_FILE);
+        let tokens = open(Some(path.clone()));
+        let (token, created) = tokens.create("ci", Role::Viewer, "admin", None, 0).unwrap();
+        assert_eq!(persisted_use(&path, &created.id), None);
+        tokens.verify(&token, 100).unwrap();
+        assert_eq!(persisted_use(&path, &created.id), Some(100));
+        // Used again within the precision, which is only updated in memory
+        tokens.verify(&token, 100 + LAST_USED_PRECISION - 1).unwrap();
+        assert_eq!(persisted_use(&path, &created.id), Some(100));
+        assert_eq!(tokens.list(0)[0].last_used, Some(100 + LAST_USED_PRECISION - 1));
+        tokens.verify(&token, 100 + LAST_USED_PRECISION).unwrap();
+        assert_eq!(persisted_use(&path, &created.id), Some(100 + LAST_USED_PRECISION));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
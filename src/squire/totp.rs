@@ -0,0 +1,249 @@
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::{thread_rng, Rng};
+
+/// Alphabet for the base32 encoding of the secrets, as defined in RFC 4648.
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Time step (in seconds) of a code.
+const STEP: i64 = 30;
+
+/// Number of digits in a code.
+const DIGITS: u32 = 6;
+
+/// Number of steps before and after the current one, that are accepted to allow for clock drift.
+const DRIFT: i64 = 1;
+
+/// Number of backup codes generated for an enrollment.
+pub const BACKUP_CODES: usize = 10;
+
+/// Encodes bytes as base32 without padding, which is how authenticator apps expect the secrets.
+///
+/// # Arguments
+///
+/// * `bytes` - Bytes to encode.
+///
+/// # Returns
+///
+/// Returns the encoded `String`.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+/// Decodes a base32 string, ignoring the case, spaces and padding.
+///
+/// # Arguments
+///
+/// * `encoded` - Base32 encoded string.
+///
+/// # Returns
+///
+/// Returns a `Result` with the decoded bytes, or an error message for an invalid character.
+pub fn base32_decode(encoded: &str) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for char in encoded.chars().filter(|char| !char.is_whitespace() && *char != '=') {
+        let value = BASE32_ALPHABET.iter()
+            .position(|letter| *letter as char == char.to_ascii_uppercase())
+            .ok_or(format!("invalid base32 character '{}'", char))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(decoded)
+}
+
+/// Generates a random secret of 160 bits, as recommended for HMAC-SHA1.
+///
+/// # Returns
+///
+/// Returns the secret encoded as base32.
+pub fn generate_secret() -> String {
+    let bytes: [u8; 20] = thread_rng().gen();
+    base32_encode(&bytes)
+}
+
+/// Computes the HOTP code for a counter, as defined in RFC 4226.
+///
+/// # Arguments
+///
+/// * `secret` - Secret shared with the authenticator app.
+/// * `counter` - Counter, which is the time step for TOTP.
+///
+/// # Returns
+///
+/// Returns a `Result` with the code, or an error message when the HMAC can't be computed.
+fn hotp(secret: &[u8], counter: u64) -> Result<u32, String> {
+    let key = PKey::hmac(secret).map_err(|err| err.to_string())?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key).map_err(|err| err.to_string())?;
+    signer.update(&counter.to_be_bytes()).map_err(|err| err.to_string())?;
+    let digest = signer.sign_to_vec().map_err(|err| err.to_string())?;
+    // Dynamic truncation picks 4 bytes at the offset given by the low nibble of the last byte
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]);
+    Ok((binary & 0x7fff_ffff) % 10u32.pow(DIGITS))
+}
+
+/// Verifies a TOTP code, as defined in RFC 6238, allowing for a step of clock drift on either side.
+///
+/// # Arguments
+///
+/// * `secret` - Secret shared with the authenticator app, encoded as base32.
+/// * `code` - Code entered by the user.
+/// * `now` - Epoch timestamp (in seconds) of the verification.
+/// * `last_step` - Time step of the last code that was accepted, which can't be used again.
+///
+/// # Returns
+///
+/// Returns an `Option` with the time step of the code, if it is valid.
+pub fn verify(secret: &str, code: &str, now: i64, last_step: i64) -> Option<i64> {
+    // Parsing alone would accept a sign and the codes with the leading zeros left out
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let secret = base32_decode(secret).ok()?;
+    let current = now / STEP;
+    (current - DRIFT..=current + DRIFT)
+        .filter(|step| *step > last_step)
+        .find(|step| hotp(&secret, *step as u64).is_ok_and(|expected| expected == code))
+}
+
+/// Builds the URI that authenticator apps scan as a QR code, to enroll the secret.
+///
+/// # Arguments
+///
+/// * `issuer` - Name of the service shown in the authenticator app.
+/// * `account` - Name of the account shown in the authenticator app.
+/// * `secret` - Secret encoded as base32.
+///
+/// # Returns
+///
+/// Returns the `otpauth://` URI as a `String`.
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let mut uri = url::Url::parse("otpauth://totp/").unwrap();
+    uri.set_path(&format!("{}:{}", issuer, account));
+    uri.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &STEP.to_string());
+    uri.to_string()
+}
+
+/// Generates the backup codes, that can be used once each in place of a TOTP code.
+///
+/// # Returns
+///
+/// Returns a `Vec` of codes formatted as `xxxxx-xxxxx`.
+pub fn generate_backup_codes() -> Vec<String> {
+    const CHARSET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = thread_rng();
+    (0..BACKUP_CODES)
+        .map(|_| {
+            let code: String = (0..10).map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char).collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Normalizes a backup code entered by the user, so that the case and separators don't matter.
+///
+/// # Arguments
+///
+/// * `code` - Backup code entered by the user.
+///
+/// # Returns
+///
+/// Returns the normalized code as a `String`.
+pub fn normalize_backup_code(code: &str) -> String {
+    code.chars().filter(|char| char.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // The test attribute of actix_web is in scope through `macro_use`, and only takes async functions
+    use std::prelude::v1::test;
+
+    /// Secret of the test vectors in RFC 4226 and RFC 6238 for HMAC-SHA1.
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_hotp_vectors() {
+        // RFC 4226, Appendix D
+        let expected = [755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(SECRET, counter as u64).unwrap(), *code);
+        }
+    }
+
+    #[test]
+    fn test_verify_vectors() {
+        // RFC 6238, Appendix B, with the 8 digit codes truncated to the last 6 digits
+        let secret = base32_encode(SECRET);
+        let vectors = [(59, "287082"), (1111111109, "081804"), (1111111111, "050471"),
+            (1234567890, "005924"), (2000000000, "279037"), (20000000000, "353130")];
+        for (now, code) in vectors {
+            assert_eq!(verify(&secret, code, now, 0), Some(now / STEP));
+            assert_eq!(verify(&secret, &format!(" {} ", code), now, 0), Some(now / STEP));
+        }
+    }
+
+    #[test]
+    fn test_verify_drift_and_replay() {
+        let secret = base32_encode(SECRET);
+        assert_eq!(verify(&secret, "287082", 59 + STEP, 0), Some(1));
+        assert_eq!(verify(&secret, "287082", 59 + 2 * STEP, 0), None);
+        // A code can't be used again, once its step was accepted
+        assert_eq!(verify(&secret, "287082", 59, 1), None);
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed_codes() {
+        let secret = base32_encode(SECRET);
+        for code in ["+005924", "5924", "05924", "0005924", "-05924", "００５９２４", "005 924", ""] {
+            assert_eq!(verify(&secret, code, 1234567890, 0), None, "{}", code);
+        }
+        assert_eq!(verify("not base32!", "005924", 1234567890, 0), None);
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        for length in 0..=20 {
+            let bytes: Vec<u8> = (0..length).map(|index| (index * 37 + 11) as u8).collect();
+            assert_eq!(base32_decode(&base32_encode(&bytes)).unwrap(), bytes);
+        }
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(base32_encode(&base32_decode(&secret).unwrap()), secret);
+    }
+
+    #[test]
+    fn test_base32_decode() {
+        // RFC 4648, Section 10
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw 6ytb oi").unwrap(), b"foobar");
+        assert!(base32_decode("MZXW6YTB0I").is_err());
+    }
+}
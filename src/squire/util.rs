@@ -1,6 +1,8 @@
 use regex::Regex;
 use std::collections::HashMap;
+use std::path::Path;
 use std::process::Command;
+use std::{fs, io};
 
 /// Function to retrieve the REGEX object for an IPv4 address format
 ///
@@ -150,3 +152,27 @@ pub fn capwords(string: &str, sep: Option<&str>) -> String {
 
     result.join(separator)
 }
+
+/// Writes a file that holds secrets, replacing it atomically and making it readable only by the owner.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file.
+/// * `content` - Content to write.
+///
+/// # Returns
+///
+/// Returns an `io::Result` to indicate whether the file was written.
+pub fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temporary, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::rename(&temporary, path)
+}
@@ -0,0 +1,299 @@
+/// Get the script that derives an Argon2id hash in the browser, for the login page.
+///
+/// # See Also
+///
+/// - This script is served as a response for the `/static/argon2.js` entry point.
+/// - It is served from the binary instead of a CDN, since the password is handed over to it.
+/// - Implements Argon2id version 1.3, and Blake2b that it is built on, without any dependency.
+///
+/// ## References
+/// - [RFC 9106](https://datatracker.ietf.org/doc/html/rfc9106)
+/// - [RFC 7693](https://datatracker.ietf.org/doc/html/rfc7693)
+///
+/// # Returns
+///
+/// A `&str` of the JS content, which exposes `argon2id({password, salt, memory, iterations, parallelism, length})`.
+pub fn get_content() -> &'static str {
+    r###""use strict";
+(function (root) {
+    const IV = new Uint32Array([
+        0xf3bcc908, 0x6a09e667, 0x84caa73b, 0xbb67ae85, 0xfe94f82b, 0x3c6ef372, 0x5f1d36f1, 0xa54ff53a,
+        0xade682d1, 0x510e527f, 0x2b3e6c1f, 0x9b05688c, 0xfb41bd6b, 0x1f83d9ab, 0x137e2179, 0x5be0cd19
+    ]);
+    const SIGMA = [
+        [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+        [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+        [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+        [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+        [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+        [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+        [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+        [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+        [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0]
+    ];
+
+    // 64-bit words are stored as pairs of 32-bit words, with the low word first
+
+    // Adds the 64-bit word (lo, hi) to the word at index a of v
+    function add(v, a, lo, hi) {
+        const sum = v[a] + lo;
+        v[a + 1] = v[a + 1] + hi + (sum >= 0x100000000 ? 1 : 0);
+        v[a] = sum;
+    }
+
+    // Mixing function of Blake2b, over the words at the even indices a, b, c and d of v
+    function mixBlake(v, a, b, c, d, x0, x1, y0, y1) {
+        add(v, a, v[b], v[b + 1]); add(v, a, x0, x1);
+        let xl = v[d] ^ v[a], xh = v[d + 1] ^ v[a + 1];
+        v[d] = xh; v[d + 1] = xl;
+        add(v, c, v[d], v[d + 1]);
+        xl = v[b] ^ v[c]; xh = v[b + 1] ^ v[c + 1];
+        v[b] = (xl >>> 24) ^ (xh << 8); v[b + 1] = (xh >>> 24) ^ (xl << 8);
+        add(v, a, v[b], v[b + 1]); add(v, a, y0, y1);
+        xl = v[d] ^ v[a]; xh = v[d + 1] ^ v[a + 1];
+        v[d] = (xl >>> 16) ^ (xh << 16); v[d + 1] = (xh >>> 16) ^ (xl << 16);
+        add(v, c, v[d], v[d + 1]);
+        xl = v[b] ^ v[c]; xh = v[b + 1] ^ v[c + 1];
+        v[b] = (xh >>> 31) ^ (xl << 1); v[b + 1] = (xl >>> 31) ^ (xh << 1);
+    }
+
+    function compress(state, block, counter, last) {
+        const v = new Uint32Array(32), m = new Uint32Array(32);
+        v.set(state); v.set(IV, 16);
+        v[24] ^= counter; v[25] ^= counter / 0x100000000;
+        if (last) { v[28] = ~v[28]; v[29] = ~v[29]; }
+        for (let i = 0; i < 32; i++) {
+            m[i] = block[i * 4] | (block[i * 4 + 1] << 8) | (block[i * 4 + 2] << 16) | (block[i * 4 + 3] << 24);
+        }
+        for (let round = 0; round < 12; round++) {
+            const s = SIGMA[round % 10];
+            const word = (index) => [m[s[index] * 2], m[s[index] * 2 + 1]];
+            const pairs = [[0, 8, 16, 24], [2, 10, 18, 26], [4, 12, 20, 28], [6, 14, 22, 30],
+                           [0, 10, 20, 30], [2, 12, 22, 24], [4, 14, 16, 26], [6, 8, 18, 28]];
+            for (let i = 0; i < 8; i++) {
+                const [x0, x1] = word(i * 2), [y0, y1] = word(i * 2 + 1);
+                mixBlake(v, pairs[i][0], pairs[i][1], pairs[i][2], pairs[i][3], x0, x1, y0, y1);
+            }
+        }
+        for (let i = 0; i < 16; i++) {
+            state[i] ^= v[i] ^ v[i + 16];
+        }
+    }
+
+    // Blake2b without a key, with a digest of 1 to 64 bytes
+    function blake2b(input, length) {
+        const state = new Uint32Array(IV);
+        state[0] ^= 0x01010000 ^ length;
+        const block = new Uint8Array(128);
+        let counter = 0, offset = 0;
+        while (input.length - offset > 128) {
+            block.set(input.subarray(offset, offset + 128));
+            counter += 128; offset += 128;
+            compress(state, block, counter, false);
+        }
+        block.fill(0);
+        block.set(input.subarray(offset));
+        compress(state, block, counter + input.length - offset, true);
+        const digest = new Uint8Array(length);
+        for (let i = 0; i < length; i++) {
+            digest[i] = state[i >> 2] >>> (8 * (i & 3));
+        }
+        return digest;
+    }
+
+    function le32(value) {
+        return new Uint8Array([value, value >>> 8, value >>> 16, value >>> 24]);
+    }
+
+    function concat(...arrays) {
+        const result = new Uint8Array(arrays.reduce((total, array) => total + array.length, 0));
+        let offset = 0;
+        for (const array of arrays) {
+            result.set(array, offset);
+            offset += array.length;
+        }
+        return result;
+    }
+
+    // Variable length hash function H' of Argon2
+    function blake2bLong(input, length) {
+        const prefixed = concat(le32(length), input);
+        if (length <= 64) {
+            return blake2b(prefixed, length);
+        }
+        const output = new Uint8Array(length);
+        let v = blake2b(prefixed, 64), offset = 0;
+        while (length - offset > 64) {
+            output.set(v.subarray(0, 32), offset);
+            offset += 32;
+            v = blake2b(v, Math.min(64, length - offset));
+        }
+        output.set(v, offset);
+        return output;
+    }
+
+    // Adds twice the product of the low words of the words at x and y, along with the word at y, to the word at x
+    function multiplyAdd(v, x, y) {
+        const xl = v[x], yl = v[y];
+        const ll = (xl & 0xffff) * (yl & 0xffff), lh = (xl & 0xffff) * (yl >>> 16);
+        const hl = (xl >>> 16) * (yl & 0xffff), hh = (xl >>> 16) * (yl >>> 16);
+        const middle = (ll >>> 16) + (lh & 0xffff) + (hl & 0xffff);
+        let productLo = ((middle & 0xffff) << 16 | (ll & 0xffff)) >>> 0;
+        let productHi = hh + (lh >>> 16) + (hl >>> 16) + Math.floor(middle / 0x10000);
+        productHi = ((productHi << 1) | (productLo >>> 31)) >>> 0;
+        productLo = (productLo << 1) >>> 0;
+        let lo = xl + yl, carry = lo >= 0x100000000 ? 1 : 0;
+        lo = (lo >>> 0) + productLo;
+        carry += lo >= 0x100000000 ? 1 : 0;
+        v[x] = lo;
+        v[x + 1] = v[x + 1] + v[y + 1] + productHi + carry;
+    }
+
+    // Rotates the XOR of the words at x and y to the right, by 16, 24, 32 or 63 bits, into the word at x
+    function rotate(v, x, y, bits) {
+        const xl = v[x] ^ v[y], xh = v[x + 1] ^ v[y + 1];
+        if (bits === 32) {
+            v[x] = xh; v[x + 1] = xl;
+        } else if (bits === 63) {
+            v[x] = (xh >>> 31) ^ (xl << 1); v[x + 1] = (xl >>> 31) ^ (xh << 1);
+        } else {
+            v[x] = (xl >>> bits) ^ (xh << (32 - bits)); v[x + 1] = (xh >>> bits) ^ (xl << (32 - bits));
+        }
+    }
+
+    // Mixing function of Argon2, which multiplies the low words on top of the additions of Blake2b
+    function mixArgon(v, a, b, c, d) {
+        multiplyAdd(v, a, b); rotate(v, d, a, 32);
+        multiplyAdd(v, c, d); rotate(v, b, c, 24);
+        multiplyAdd(v, a, b); rotate(v, d, a, 16);
+        multiplyAdd(v, c, d); rotate(v, b, c, 63);
+    }
+
+    // Permutation P over 16 words of the block, whose positions (as indices of 32-bit words) are in p
+    function permute(v, p) {
+        mixArgon(v, p[0], p[4], p[8], p[12]);
+        mixArgon(v, p[1], p[5], p[9], p[13]);
+        mixArgon(v, p[2], p[6], p[10], p[14]);
+        mixArgon(v, p[3], p[7], p[11], p[15]);
+        mixArgon(v, p[0], p[5], p[10], p[15]);
+        mixArgon(v, p[1], p[6], p[11], p[12]);
+        mixArgon(v, p[2], p[7], p[8], p[13]);
+        mixArgon(v, p[3], p[4], p[9], p[14]);
+    }
+
+    const ROWS = [], COLUMNS = [];
+    for (let i = 0; i < 8; i++) {
+        ROWS.push(Array.from({length: 16}, (_, j) => (i * 16 + j) * 2));
+        COLUMNS.push(Array.from({length: 16}, (_, j) => (2 * i + 16 * (j >> 1) + (j & 1)) * 2));
+    }
+
+    // Compression function G, which writes G(x, y) into the output, or XORs it when the flag is set
+    const r = new Uint32Array(256), z = new Uint32Array(256);
+    function fillBlock(memory, x, y, output, xor) {
+        for (let i = 0; i < 256; i++) {
+            r[i] = memory[x + i] ^ memory[y + i];
+        }
+        z.set(r);
+        for (const row of ROWS) permute(z, row);
+        for (const column of COLUMNS) permute(z, column);
+        for (let i = 0; i < 256; i++) {
+            memory[output + i] = (xor ? memory[output + i] : 0) ^ r[i] ^ z[i];
+        }
+    }
+
+    // High 32 bits of the product of two unsigned 32-bit integers
+    function mulHi(x, y) {
+        const xl = x & 0xffff, xh = x >>> 16, yl = y & 0xffff, yh = y >>> 16;
+        const middle = ((xl * yl) >>> 16) + (xh * yl & 0xffff) + (xl * yh & 0xffff);
+        return xh * yh + ((xh * yl) >>> 16) + ((xl * yh) >>> 16) + Math.floor(middle / 0x10000);
+    }
+
+    function argon2id({password, salt, memory, iterations, parallelism, length, secret, data}) {
+        secret = secret || new Uint8Array(0);
+        data = data || new Uint8Array(0);
+        const h0 = blake2b(concat(
+            le32(parallelism), le32(length), le32(memory), le32(iterations), le32(0x13), le32(2),
+            le32(password.length), password, le32(salt.length), salt,
+            le32(secret.length), secret, le32(data.length), data
+        ), 64);
+        const segmentLength = Math.floor(memory / (4 * parallelism));
+        const laneLength = segmentLength * 4;
+        const blocks = new Uint32Array(laneLength * parallelism * 256 + 3 * 256);
+        // Scratch blocks after the memory, to generate the addresses of the data independent passes
+        const zero = laneLength * parallelism * 256, input = zero + 256, address = input + 256;
+        for (let lane = 0; lane < parallelism; lane++) {
+            for (let i = 0; i < 2; i++) {
+                const block = blake2bLong(concat(h0, le32(i), le32(lane)), 1024);
+                const offset = (lane * laneLength + i) * 256;
+                for (let j = 0; j < 256; j++) {
+                    blocks[offset + j] = block[j * 4] | (block[j * 4 + 1] << 8) | (block[j * 4 + 2] << 16) |
+                        (block[j * 4 + 3] << 24);
+                }
+            }
+        }
+        for (let pass = 0; pass < iterations; pass++) {
+            for (let slice = 0; slice < 4; slice++) {
+                for (let lane = 0; lane < parallelism; lane++) {
+                    const independent = pass === 0 && slice < 2;
+                    if (independent) {
+                        blocks.fill(0, zero, address + 256);
+                        const values = [pass, lane, slice, laneLength * parallelism, iterations, 2];
+                        values.forEach((value, index) => blocks[input + index * 2] = value);
+                    }
+                    const start = pass === 0 && slice === 0 ? 2 : 0;
+                    for (let index = start; index < segmentLength; index++) {
+                        const position = slice * segmentLength + index;
+                        const current = lane * laneLength + position;
+                        const previous = position === 0 ? current + laneLength - 1 : current - 1;
+                        let j1, j2;
+                        if (independent) {
+                            // Each address block holds 128 pairs of 32-bit words
+                            if (index % 128 === 0 || index === start) {
+                                blocks[input + 12]++;
+                                fillBlock(blocks, zero, input, address, false);
+                                fillBlock(blocks, zero, address, address, false);
+                            }
+                            j1 = blocks[address + (index % 128) * 2];
+                            j2 = blocks[address + (index % 128) * 2 + 1];
+                        } else {
+                            j1 = blocks[previous * 256];
+                            j2 = blocks[previous * 256 + 1];
+                        }
+                        const referenceLane = pass === 0 && slice === 0 ? lane : j2 % parallelism;
+                        const sameLane = referenceLane === lane;
+                        let area;
+                        if (pass === 0) {
+                            area = slice === 0 ? index - 1 :
+                                sameLane ? slice * segmentLength + index - 1 :
+                                    slice * segmentLength + (index === 0 ? -1 : 0);
+                        } else {
+                            area = sameLane ? laneLength - segmentLength + index - 1 :
+                                laneLength - segmentLength + (index === 0 ? -1 : 0);
+                        }
+                        const relative = area - 1 - mulHi(area, mulHi(j1, j1));
+                        const begin = pass === 0 || slice === 3 ? 0 : (slice + 1) * segmentLength;
+                        const reference = referenceLane * laneLength + (begin + relative) % laneLength;
+                        fillBlock(blocks, previous * 256, reference * 256, current * 256, pass > 0);
+                    }
+                }
+            }
+        }
+        const last = new Uint32Array(256);
+        for (let lane = 0; lane < parallelism; lane++) {
+            const offset = (lane * laneLength + laneLength - 1) * 256;
+            for (let j = 0; j < 256; j++) {
+                last[j] ^= blocks[offset + j];
+            }
+        }
+        const bytes = new Uint8Array(1024);
+        for (let j = 0; j < 256; j++) {
+            bytes.set(le32(last[j]), j * 4);
+        }
+        return blake2bLong(bytes, length);
+    }
+
+    root.argon2id = argon2id;
+})(typeof window === "undefined" ? globalThis : window);
+"###
+}
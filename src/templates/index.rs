@@ -18,8 +18,9 @@ pub fn get_content() -> String {
     <meta name="keywords" content="Rust, Monitor, actix, JavaScript, HTML, CSS">
     <meta name="author" content="Vignesh Rao">
     <meta content="width=device-width, initial-scale=1" name="viewport">
-    <script src="https://code.jquery.com/jquery-3.6.4.min.js"></script>
-    <script src="https://thevickypedia.github.io/open-source/crypto/crypto.js"></script>
+    <script nonce="{{ nonce }}" src="https://code.jquery.com/jquery-3.6.4.min.js"></script>
+    <script nonce="{{ nonce }}" src="https://thevickypedia.github.io/open-source/crypto/crypto.js"></script>
+    <script nonce="{{ nonce }}" src="/static/argon2.js"></script>
     <!-- Favicon.ico and Apple Touch Icon -->
     <link rel="icon" href="https://thevickypedia.github.io/open-source/images/logo/actix.ico">
     <link rel="apple-touch-icon" href="https://thevickypedia.github.io/open-source/images/logo/actix.png">
@@ -137,7 +138,7 @@ pub fn get_content() -> String {
                 Please refer <a href="https://www.enable-javascript.com/">enable-javascript</a> for how to.
             </h2>
             <form>
-                <button type="submit" onClick="<meta httpEquiv='refresh' content='0'>">RETRY</button>
+                <button type="submit">RETRY</button>
             </form>
         </div>
     </noscript>
@@ -149,7 +150,7 @@ pub fn get_content() -> String {
     </div>
     <div class="content">
         <!-- <form action="{ url_for('signin') }" method="post"> -->
-        <form>
+        <form id="loginForm">
             <label for="username">Username:</label>
             <input type="text" id="username" name="username" required>
             <label for="password">Password:</label>
@@ -157,7 +158,7 @@ pub fn get_content() -> String {
                 <input type="password" id="password" name="password" required>
                 <i class="fa-regular fa-eye" id="eye"></i>
             </div>
-            <button type="submit" onclick="submitToAPI(event)">Sign In</button>
+            <button type="submit">Sign In</button>
         </form>
     </div>
 </div>
@@ -166,7 +167,7 @@ pub fn get_content() -> String {
     Generated by <a href="https://github.com/thevickypedia/SysMonk/releases/tag/v{{ version }}">SysMonk - v{{ version }}</a>
 </footer>
 <!-- control the behavior of the browser's navigation without triggering a full page reload -->
-<script>
+<script nonce="{{ nonce }}">
     document.addEventListener('DOMContentLoaded', function() {
         history.pushState(null, document.title, location.href);
         window.addEventListener('popstate', function (event) {
@@ -175,7 +176,7 @@ pub fn get_content() -> String {
     });
 </script>
 <!-- handle authentication from login page -->
-<script>
+<script nonce="{{ nonce }}">
     async function submitToAPI(event) {
         event.preventDefault();
         const username = $("#username").val();
@@ -191,27 +192,68 @@ pub fn get_content() -> String {
             }
             return "\\u" + arr.join("\\u");
         }
-        async function CalculateHash(username, password, timestamp) {
-            const message = username + password + timestamp;
-            const encoder = new TextEncoder();
-            const data = encoder.encode(message);
+        async function Digest(bytes) {
             if (crypto.subtle === undefined) {
-                const wordArray = CryptoJS.lib.WordArray.create(data);
-                const hash = CryptoJS.SHA512(wordArray);
-                // Convert the hash to a hexadecimal string and return it
-                return hash.toString(CryptoJS.enc.Hex);
-            } else {
-                const hashBuffer = await crypto.subtle.digest('SHA-512', data);
-                const hashArray = Array.from(new Uint8Array(hashBuffer));
-                // Convert each byte to a hexadecimal string, pad with zeros, and join them to form the final hash
-                return hashArray.map(byte => byte.toString(16).padStart(2, '0')).join('');
+                const hash = CryptoJS.SHA512(CryptoJS.lib.WordArray.create(bytes));
+                // Convert the big-endian words of the hash to bytes
+                return Uint8Array.from({length: hash.sigBytes}, (_, i) => (hash.words[i >>> 2] >>> (24 - (i % 4) * 8)) & 0xff);
             }
+            return new Uint8Array(await crypto.subtle.digest('SHA-512', bytes));
+        }
+        async function Hmac(key, message) {
+            // HMAC is built on the digest, since the fallback for plain HTTP only provides the digest
+            const block = new Uint8Array(128);
+            block.set(key.length > 128 ? await Digest(key) : key);
+            const inner = new Uint8Array(128 + message.length), outer = new Uint8Array(128 + 64);
+            for (let i = 0; i < 128; i++) {
+                inner[i] = block[i] ^ 0x36;
+                outer[i] = block[i] ^ 0x5c;
+            }
+            inner.set(message, 128);
+            outer.set(await Digest(inner), 128);
+            return await Digest(outer);
+        }
+        async function DeriveKey(username, password) {
+            const challenge = await $.ajax({
+                method: "GET",
+                url: window.location.origin.concat("/challenge"),
+                data: {username: username}
+            });
+            if (challenge.algorithm !== "argon2id") {
+                throw new Error(`Unsupported algorithm '${challenge.algorithm}'`);
+            }
+            // Password is hashed with the salt and parameters of the stored verifier, so it never leaves the browser
+            const padded = challenge.salt.padEnd(Math.ceil(challenge.salt.length / 4) * 4, "=");
+            const key = argon2id({
+                password: new TextEncoder().encode(password),
+                salt: Uint8Array.from(atob(padded), char => char.charCodeAt(0)),
+                memory: challenge.memory,
+                iterations: challenge.iterations,
+                parallelism: challenge.parallelism,
+                length: challenge.length
+            });
+            return {key: key, nonce: challenge.nonce};
+        }
+        let key, nonce;
+        try {
+            // Nonce is issued for every challenge, and signed along with the login so it can't be replayed
+            ({key, nonce} = await DeriveKey(username, password));
+        } catch (error) {
+            console.error(error);
+            // Challenges are refused with a detail when the client has too many of them outstanding
+            alert(error.responseJSON && error.responseJSON.detail
+                ? "ERROR: " + error.responseJSON.detail
+                : "ERROR: Unable to process the password for authentication!");
+            return false;
         }
         let hex_user = await ConvertStringToHex(username);
-        let hex_pass = await ConvertStringToHex(password);
         let timestamp = Math.round(new Date().getTime() / 1000);
-        let hash = await CalculateHash(hex_user, hex_pass, timestamp)
-        let authHeaderValue = hex_user + ',' + hash + ',' + timestamp;
+        // Like SCRAM, the client key is sent masked with a signature, that can only be made with the stored key
+        const encoder = new TextEncoder();
+        const clientKey = await Hmac(key, encoder.encode("client"));
+        const signature = await Hmac(await Digest(clientKey), encoder.encode(hex_user + nonce + timestamp));
+        let hash = Array.from(clientKey, (byte, i) => (byte ^ signature[i]).toString(16).padStart(2, '0')).join('');
+        let authHeaderValue = hex_user + ',' + hash + ',' + timestamp + ',' + nonce;
         let origin = window.location.origin
         $.ajax({
             method: "POST",
@@ -223,6 +265,11 @@ pub fn get_content() -> String {
             crossDomain: "true",
             contentType: "application/json; charset=utf-8",
             success: function (data) {
+                // Password is verified, but the session is only issued once the second factor is verified too
+                if (data.mfa_required) {
+                    VerifyCode(origin);
+                    return;
+                }
                 // Check if the response contains a redirect URL
                 if (data.redirect_url) {
                     // Manually handle the redirect
@@ -244,8 +291,43 @@ pub fn get_content() -> String {
         });
     }
 
+    document.getElementById("loginForm").addEventListener("submit", submitToAPI);
+
+    function VerifyCode(origin) {
+        let code = prompt("Enter the code from your authenticator app, or a backup code");
+        if (!code) {
+            return;
+        }
+        $.ajax({
+            method: "POST",
+            url: origin.concat("/login/mfa"),
+            headers: {
+                'accept': 'application/json'
+            },
+            data: JSON.stringify({code: code}),
+            contentType: "application/json; charset=utf-8",
+            success: function (data) {
+                if (data.redirect_url) {
+                    window.location.href = data.redirect_url;
+                }
+            },
+            error: function(jqXHR, textStatus, errorThrown) {
+                console.error(`Status: ${textStatus}, Error: ${errorThrown}`);
+                if (jqXHR.hasOwnProperty("responseJSON")) {
+                    alert(jqXHR.responseJSON.detail);
+                } else {
+                    alert(errorThrown);
+                }
+                // Invalid codes can be retried, until the login expires or there are too many of them
+                if (jqXHR.status === 401 && jqXHR.responseJSON && jqXHR.responseJSON.detail === "Invalid two-factor code") {
+                    VerifyCode(origin);
+                }
+            }
+        });
+    }
+
 </script>
-<script>
+<script nonce="{{ nonce }}">
     const passwordInput = document.querySelector("#password")
     const eye = document.querySelector("#eye")
     eye.addEventListener("click", function() {
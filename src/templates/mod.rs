@@ -12,6 +12,8 @@ mod monitor;
 mod unauthorized;
 /// Error page template that is served as HTML response for any error message to be conveyed.
 mod error;
+/// Script that derives the Argon2id hash of the password in the browser, which is served along with the index page.
+pub mod argon2;
 
 /// Loads all the HTML templates' content into a Jinja Environment
 ///
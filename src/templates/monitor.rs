@@ -20,10 +20,11 @@ pub fn get_content() -> String {
     <link rel="icon" href="https://thevickypedia.github.io/open-source/images/logo/actix.ico">
     <link rel="apple-touch-icon" href="https://thevickypedia.github.io/open-source/images/logo/actix.png">
     <meta content="width=device-width, initial-scale=1" name="viewport">
-    <script src="https://cdn.jsdelivr.net/npm/chart.js"></script>
+    <script nonce="{{ nonce }}" src="https://cdn.jsdelivr.net/npm/chart.js"></script>
     <!-- CSS and JS for night mode -->
-    <script src="https://cdnjs.cloudflare.com/ajax/libs/jquery/2.2.2/jquery.min.js"></script>
-    <script type="text/javascript" src="https://thevickypedia.github.io/open-source/nightmode/night.js" defer></script>
+    <script nonce="{{ nonce }}" src="https://cdnjs.cloudflare.com/ajax/libs/jquery/2.2.2/jquery.min.js"></script>
+    <script nonce="{{ nonce }}" src="https://cdnjs.cloudflare.com/ajax/libs/qrcodejs/1.0.0/qrcode.min.js"></script>
+    <script nonce="{{ nonce }}" type="text/javascript" src="https://thevickypedia.github.io/open-source/nightmode/night.js" defer></script>
     <link rel="stylesheet" type="text/css" href="https://thevickypedia.github.io/open-source/nightmode/night.css">
     <!-- Font Awesome icons -->
     <link rel="stylesheet" href="https://cdnjs.cloudflare.com/ajax/libs/font-awesome/6.6.0/css/font-awesome.min.css">
@@ -34,6 +35,68 @@ pub fn get_content() -> String {
             overflow-x: hidden;
         }
 
+        .alert-firing {
+            color: #e74c3c;
+            font-weight: bold;
+        }
+
+        .alert-pending {
+            color: #f39c12;
+        }
+
+        .alert-resolved {
+            color: #2ecc71;
+        }
+
+        .alert-silenced {
+            color: #7f8c8d;
+            font-style: italic;
+        }
+
+        .silences, .sessions, .lockouts, .mfa, .tokens {
+            margin: 2%;
+            display: flex;
+            align-items: center;
+            justify-content: center;
+            flex-direction: column;  /* Ensure vertical alignment */
+        }
+
+        .silences h3 {
+            text-align: center;
+            margin-bottom: 20px;
+        }
+
+        .silence-form {
+            display: flex;
+            gap: 10px;
+            margin-top: 15px;
+        }
+
+        .silence-form input, .silence-form select {
+            padding: 5px;
+        }
+
+        .alerts, .network-stats, .disk-io-stats {
+            height: 100%;
+            margin: 2%;
+            display: none;  /* Hide the container initially */
+            align-items: center;
+            justify-content: center;
+            flex-direction: column;  /* Ensure vertical alignment */
+        }
+
+        .alerts h3, .network-stats h3, .disk-io-stats h3 {
+            text-align: center;
+            margin-bottom: 20px;
+        }
+
+        .line-chart-container {
+            position: relative;
+            height: 250px;
+            width: 80%;
+            margin-bottom: 20px;
+        }
+
         .docker-stats {
             height: 100%;
             margin: 2%;
@@ -194,10 +257,6 @@ pub fn get_content() -> String {
             font-size: 14px;
             font-style: italic;
         }
-
-        .graph-canvas {
-            max-width: 600px;
-        }
     </style>
     <noscript>
         <style>
@@ -214,14 +273,14 @@ pub fn get_content() -> String {
                 Please refer <a href="https://www.enable-javascript.com/">enable-javascript</a> for how to.
             </h2>
             <form>
-                <button type="submit" onClick="<meta httpEquiv='refresh' content='0'>">RETRY</button>
+                <button type="submit">RETRY</button>
             </form>
         </div>
     </noscript>
 </head>
 <body translate="no">
 <div class="toggler fa fa-moon-o"></div>
-<button class="logout" onclick="logOut()"><i class="fa fa-sign-out"></i> Logout</button>
+<button id="logout" class="logout"><i class="fa fa-sign-out"></i> Logout</button>
 <h1>SysMonk - System Monitor</h1>
 <div class="center-container">
     <details>
@@ -260,6 +319,156 @@ pub fn get_content() -> String {
     </details>
     {% endif %}
 </div>
+<div id="alerts" class="alerts">
+    <h3>Alerts</h3>
+    <table id="alertsTable">
+        <thead>
+            <tr>
+                <th>State</th>
+                <th>Rule</th>
+                <th>Value</th>
+                <th>Since</th>
+            </tr>
+        </thead>
+        <tbody>
+        </tbody>
+    </table>
+</div>
+<div id="silences" class="silences">
+    <details>
+        <summary><strong>Silences</strong></summary>
+        <table id="silencesTable">
+            <thead>
+                <tr>
+                    <th>Matcher</th>
+                    <th>Starts</th>
+                    <th>Ends</th>
+                    <th>Created By</th>
+                    <th>Comment</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+            </tbody>
+        </table>
+        <p id="maintenanceWindows"></p>
+        {% if can_silence %}
+        <form id="silenceForm" class="silence-form">
+            <input type="text" id="silenceMatcher" placeholder="service:nginx" title="host, rule:<regex>, service:<name>, process:<name> or docker:<name>" required>
+            <select id="silenceDuration">
+                <option value="1800">30 minutes</option>
+                <option value="3600" selected>1 hour</option>
+                <option value="7200">2 hours</option>
+                <option value="14400">4 hours</option>
+                <option value="86400">1 day</option>
+            </select>
+            <input type="text" id="silenceComment" placeholder="Reason" required>
+            <button type="submit" class="tooltip-button">Silence</button>
+        </form>
+        {% endif %}
+    </details>
+</div>
+<div id="sessions" class="sessions">
+    <details>
+        <summary><strong>Sessions</strong></summary>
+        <table id="sessionsTable">
+            <thead>
+                <tr>
+                    <th>User</th>
+                    <th>IP Address</th>
+                    <th>User Agent</th>
+                    <th>Logged In</th>
+                    <th>Last Seen</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+            </tbody>
+        </table>
+        <button id="logoutEverywhere" class="tooltip-button">Log out everywhere</button>
+    </details>
+</div>
+<div id="mfa" class="mfa">
+    <details>
+        <summary><strong>Two-factor Authentication</strong></summary>
+        <p id="mfaStatus"></p>
+        <button id="mfaEnroll" class="tooltip-button" style="display: none">Enable</button>
+        <div id="mfaEnrollment" style="display: none">
+            <p>Scan the QR code with an authenticator app, or enter the secret manually.</p>
+            <div id="mfaQrCode"></div>
+            <p><code id="mfaSecret"></code></p>
+            <form id="mfaConfirmForm" class="silence-form">
+                <input type="text" id="mfaConfirmCode" placeholder="Code" inputmode="numeric" autocomplete="one-time-code" required>
+                <button type="submit" class="tooltip-button">Confirm</button>
+            </form>
+        </div>
+        <div id="mfaBackupCodes" style="display: none">
+            <p>Store these backup codes somewhere safe. Each one can be used once in place of a code, and they won't be shown again.</p>
+            <pre id="mfaBackupCodesList"></pre>
+        </div>
+        <button id="mfaDisable" class="tooltip-button" style="display: none">Disable</button>
+    </details>
+</div>
+{% if is_admin %}
+<div id="lockouts" class="lockouts">
+    <details>
+        <summary><strong>Failed Logins</strong></summary>
+        <table id="lockoutsTable">
+            <thead>
+                <tr>
+                    <th>Kind</th>
+                    <th>Subject</th>
+                    <th>Failures</th>
+                    <th>Blocked Until</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+            </tbody>
+        </table>
+    </details>
+</div>
+<div id="tokens" class="tokens">
+    <details>
+        <summary><strong>API Tokens</strong></summary>
+        <table id="tokensTable">
+            <thead>
+                <tr>
+                    <th>Name</th>
+                    <th>Role</th>
+                    <th>Created By</th>
+                    <th>Created</th>
+                    <th>Expires</th>
+                    <th>Last Used</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+            </tbody>
+        </table>
+        <form id="tokenForm" class="silence-form">
+            <input type="text" id="tokenName" placeholder="Name" maxlength="64" required>
+            <select id="tokenRole">
+                <option value="viewer" selected>viewer</option>
+                <option value="operator">operator</option>
+                <option value="admin">admin</option>
+            </select>
+            <select id="tokenExpiry">
+                <option value="86400">1 day</option>
+                <option value="2592000" selected>30 days</option>
+                <option value="7776000">90 days</option>
+                <option value="31536000">1 year</option>
+                <option value="">Never</option>
+            </select>
+            <button type="submit" class="tooltip-button">Create</button>
+        </form>
+        <div id="tokenCreated" style="display: none">
+            <p>Send this token as <code>Authorization: Bearer &lt;token&gt;</code>. Copy it now, since it won't be shown again.</p>
+            <pre id="tokenValue"></pre>
+        </div>
+    </details>
+</div>
+{% endif %}
 <div class="container">
     <!-- Box to display utilization per CPU -->
     <div class="box">
@@ -285,14 +494,15 @@ pub fn get_content() -> String {
         {% endif %}
 
         <h3>Disk Usage</h3>
-        <div class="progress">
-            <div id="diskUsage" class="progress-bar"></div>
+        <div id="diskUsageContainer">
+            <!-- Disk usage per mount will be dynamically added here -->
         </div>
-        <p id="diskUsageText">Disk: 0%</p>
 
         <div class="graph">
             <h3>CPU Load Averages</h3>
-            <canvas class="graph-canvas" id="loadChart" width="400" height="200"></canvas>
+            <div class="line-chart-container">
+                <canvas id="loadChart"></canvas>
+            </div>
         </div>
     </div>
     <!-- Box to display Memory, Swap and Disk usage as Pie charts -->
@@ -316,6 +526,47 @@ pub fn get_content() -> String {
         </div>
     </div>
 </div>
+<div id="disk-io-stats" class="disk-io-stats">
+    <h3>Disk I/O</h3>
+    <div class="line-chart-container">
+        <canvas id="diskIOChart"></canvas>
+    </div>
+    <table id="diskIOStatsTable">
+        <thead>
+            <tr>
+                <th>Device</th>
+                <th>Read</th>
+                <th>Write</th>
+                <th>IOPS (R/W)</th>
+                <th>Await</th>
+                <th>Utilization</th>
+            </tr>
+        </thead>
+        <tbody>
+        </tbody>
+    </table>
+</div>
+<div id="network-stats" class="network-stats">
+    <h3>Network Throughput</h3>
+    <div class="line-chart-container">
+        <canvas id="networkChart"></canvas>
+    </div>
+    <table id="networkStatsTable">
+        <thead>
+            <tr>
+                <th>Interface</th>
+                <th>State</th>
+                <th>Received</th>
+                <th>Transmitted</th>
+                <th>Packets (RX/TX)</th>
+                <th>Errors (RX/TX)</th>
+                <th>Drops (RX/TX)</th>
+            </tr>
+        </thead>
+        <tbody>
+        </tbody>
+    </table>
+</div>
 <div id="docker-stats" class="docker-stats">
     <h3>Docker Stats</h3>
     <table id="dockerStatsTable">
@@ -371,28 +622,404 @@ pub fn get_content() -> String {
         </tbody>
     </table>
 </div>
-<script>
+<script nonce="{{ nonce }}">
     document.addEventListener('DOMContentLoaded', function () {
         const wsProtocol = window.location.protocol === "https:" ? "wss" : "ws";
         const wsHost = window.location.host;
         const ws = new WebSocket(`${wsProtocol}://${wsHost}/ws/system`);
 
+        const allTopics = [{% for topic in topics %}'{{ topic }}', {% endfor %}];
+        let wsOpened = false;
         ws.onopen = () => {
+            wsOpened = true;
             console.log('WebSocket connection established');
+            // Dashboards can limit the topics and the interval, eg: /monitor?topics=cpu,memory&interval=5
+            const params = new URLSearchParams(window.location.search);
+            if (params.has('topics')) {
+                const topics = params.get('topics').split(',');
+                const unwanted = allTopics.filter(topic => !topics.includes(topic));
+                ws.send(JSON.stringify({action: 'unsubscribe', topics: unwanted}));
+            }
+            if (params.has('interval')) {
+                ws.send(JSON.stringify({action: 'interval', seconds: parseInt(params.get('interval'))}));
+            }
         };
         ws.onclose = () => {
+            if (!wsOpened) {
+                // Proxies that strip websocket upgrades fail the handshake, so fall back to Server-Sent Events
+                console.warn('WebSocket handshake failed, falling back to Server-Sent Events');
+                startEventSource();
+                return;
+            }
             console.log('WebSocket connection closed');
             alert('WebSocket connection closed by the server!');
             logOut();
             return;
         };
 
+        function startEventSource() {
+            const source = new EventSource('/sse/system');
+            source.onopen = () => {
+                console.log('Event stream established');
+            };
+            source.onerror = () => {
+                // EventSource reconnects by itself (with Last-Event-ID), unless the server refused the stream
+                if (source.readyState === EventSource.CLOSED) {
+                    console.log('Event stream closed');
+                    alert('Event stream closed by the server!');
+                    logOut();
+                }
+            };
+            source.onmessage = handleMessage;
+        }
+
+        // Alerts are polled, since they are evaluated independently of the stream
+        const alertsInterval = 10000;
+
+        function refreshAlerts() {
+            fetch('/api/alerts')
+                .then(response => response.ok ? response.json() : [])
+                .then(alerts => {
+                    const container = document.getElementById("alerts");
+                    if (alerts.length === 0) {
+                        // Hide the container if there are no alerts
+                        container.style.display = "none";
+                        return;
+                    }
+                    container.style.display = "flex";
+                    const tableBody = document.querySelector('#alertsTable tbody');
+                    tableBody.innerHTML = '';
+                    alerts.forEach(alert => {
+                        const row = document.createElement('tr');
+                        const silenced = alert.silenced ? ' <span class="alert-silenced">(silenced)</span>' : '';
+                        row.innerHTML = `
+                            <td class="alert-${alert.state}">${alert.state.toUpperCase()}${silenced}</td>
+                            <td>${alert.rule}</td>
+                            <td>${alert.value === null ? 'missing' : alert.value.toFixed(2)}</td>
+                            <td>${new Date(alert.since * 1000).toLocaleString()}</td>
+                        `;
+                        tableBody.appendChild(row);
+                    });
+                })
+                .catch(error => console.warn('Error fetching alerts:', error));
+        }
+
+        refreshAlerts();
+        setInterval(refreshAlerts, alertsInterval);
+
+        // Silences can only be created and expired by operators and admins
+        const canSilence = {% if can_silence %}true{% else %}false{% endif %};
+
+        function refreshSilences() {
+            fetch('/api/silences')
+                .then(response => response.ok ? response.json() : {silences: [], maintenance_windows: []})
+                .then(data => {
+                    const table = document.getElementById('silencesTable');
+                    const tableBody = table.querySelector('tbody');
+                    tableBody.innerHTML = '';
+                    table.style.display = data.silences.length === 0 ? 'none' : 'table';
+                    data.silences.forEach(silence => {
+                        const row = document.createElement('tr');
+                        // Values are set as text, since the matcher and comment are entered by users
+                        [
+                            silence.matcher,
+                            new Date(silence.starts_at * 1000).toLocaleString(),
+                            new Date(silence.ends_at * 1000).toLocaleString(),
+                            silence.created_by,
+                            silence.comment,
+                        ].forEach(value => {
+                            const cell = document.createElement('td');
+                            cell.textContent = value;
+                            row.appendChild(cell);
+                        });
+                        const action = document.createElement('td');
+                        row.appendChild(action);
+                        tableBody.appendChild(row);
+                        if (!canSilence) {
+                            return;
+                        }
+                        const button = document.createElement('button');
+                        button.className = 'tooltip-button';
+                        button.textContent = 'Expire';
+                        button.addEventListener('click', () => {
+                            fetch(`/api/silences/${encodeURIComponent(silence.id)}`, {method: 'DELETE'})
+                                .then(() => {
+                                    refreshSilences();
+                                    refreshAlerts();
+                                });
+                        });
+                        action.appendChild(button);
+                    });
+                    const windows = data.maintenance_windows.map(window =>
+                        `${window.expression}${window.active ? ' (active)' : ''}`
+                    );
+                    document.getElementById('maintenanceWindows').textContent =
+                        windows.length === 0 ? '' : `Maintenance windows: ${windows.join(', ')}`;
+                })
+                .catch(error => console.warn('Error fetching silences:', error));
+        }
+
+        document.getElementById('silenceForm')?.addEventListener('submit', event => {
+            event.preventDefault();
+            fetch('/api/silences', {
+                method: 'POST',
+                headers: {'Content-Type': 'application/json'},
+                body: JSON.stringify({
+                    matcher: document.getElementById('silenceMatcher').value,
+                    duration: parseInt(document.getElementById('silenceDuration').value),
+                    comment: document.getElementById('silenceComment').value,
+                }),
+            })
+                .then(response => response.ok ? response.json() : response.json().then(error => {
+                    throw new Error(error.detail);
+                }))
+                .then(() => {
+                    document.getElementById('silenceForm').reset();
+                    refreshSilences();
+                    refreshAlerts();
+                })
+                .catch(error => alert(`Failed to create the silence: ${error.message}`));
+        });
+
+        refreshSilences();
+        setInterval(refreshSilences, alertsInterval);
+
+        function refreshSessions() {
+            fetch('/api/sessions')
+                .then(response => response.ok ? response.json() : {current: null, sessions: []})
+                .then(data => {
+                    const tableBody = document.getElementById('sessionsTable').querySelector('tbody');
+                    tableBody.innerHTML = '';
+                    data.sessions.forEach(session => {
+                        const row = document.createElement('tr');
+                        // Values are set as text, since the user agent is sent by the browser
+                        [
+                            session.username,
+                            session.ip,
+                            session.user_agent,
+                            new Date(session.created_at * 1000).toLocaleString(),
+                            new Date(session.last_seen * 1000).toLocaleString(),
+                        ].forEach(value => {
+                            const cell = document.createElement('td');
+                            cell.textContent = value;
+                            row.appendChild(cell);
+                        });
+                        const action = document.createElement('td');
+                        row.appendChild(action);
+                        tableBody.appendChild(row);
+                        // The current session is ended by logging out
+                        if (session.id === data.current) {
+                            action.textContent = 'This session';
+                            return;
+                        }
+                        const button = document.createElement('button');
+                        button.className = 'tooltip-button';
+                        button.textContent = 'Revoke';
+                        button.addEventListener('click', () => {
+                            fetch(`/api/sessions/${encodeURIComponent(session.id)}`, {method: 'DELETE'})
+                                .then(() => refreshSessions());
+                        });
+                        action.appendChild(button);
+                    });
+                })
+                .catch(error => console.warn('Error fetching sessions:', error));
+        }
+
+        document.getElementById('logoutEverywhere').addEventListener('click', () => {
+            if (confirm('Log out of all sessions, including this one?')) {
+                window.location.href = window.location.origin + "{{ logout }}?everywhere=true";
+            }
+        });
+
+        refreshSessions();
+        setInterval(refreshSessions, alertsInterval);
+
+        function refreshMfa() {
+            fetch('/api/mfa')
+                .then(response => response.ok ? response.json() : {enabled: false, backup_codes: 0})
+                .then(status => {
+                    document.getElementById('mfaStatus').textContent = status.enabled
+                        ? `Enabled since ${new Date(status.enrolled_at * 1000).toLocaleString()}, ${status.backup_codes} backup codes left`
+                        : 'Disabled';
+                    document.getElementById('mfaEnroll').style.display = status.enabled ? 'none' : '';
+                    document.getElementById('mfaDisable').style.display = status.enabled ? '' : 'none';
+                })
+                .catch(error => console.warn('Error fetching two-factor status:', error));
+        }
+
+        function mfaRequest(method, url, code) {
+            return fetch(url, {
+                method: method,
+                headers: {'Content-Type': 'application/json'},
+                body: code === undefined ? undefined : JSON.stringify({code: code}),
+            }).then(response => {
+                if (!response.ok) {
+                    return response.json().then(error => {
+                        throw new Error(error.detail);
+                    });
+                }
+                return response.status === 204 ? null : response.json();
+            });
+        }
+
+        document.getElementById('mfaEnroll').addEventListener('click', () => {
+            mfaRequest('POST', '/api/mfa/enroll')
+                .then(enrollment => {
+                    const qrCode = document.getElementById('mfaQrCode');
+                    qrCode.innerHTML = '';
+                    new QRCode(qrCode, {text: enrollment.uri, width: 192, height: 192});
+                    document.getElementById('mfaSecret').textContent = enrollment.secret;
+                    document.getElementById('mfaEnrollment').style.display = '';
+                    document.getElementById('mfaEnroll').style.display = 'none';
+                })
+                .catch(error => alert(`Failed to enable two-factor authentication: ${error.message}`));
+        });
+
+        document.getElementById('mfaConfirmForm').addEventListener('submit', event => {
+            event.preventDefault();
+            mfaRequest('POST', '/api/mfa/confirm', document.getElementById('mfaConfirmCode').value)
+                .then(data => {
+                    document.getElementById('mfaConfirmForm').reset();
+                    document.getElementById('mfaEnrollment').style.display = 'none';
+                    document.getElementById('mfaBackupCodesList').textContent = data.backup_codes.join('\n');
+                    document.getElementById('mfaBackupCodes').style.display = '';
+                    refreshMfa();
+                })
+                .catch(error => alert(`Failed to confirm two-factor authentication: ${error.message}`));
+        });
+
+        document.getElementById('mfaDisable').addEventListener('click', () => {
+            const code = prompt('Enter a code from the authenticator app, or a backup code, to disable two-factor authentication');
+            if (!code) {
+                return;
+            }
+            mfaRequest('DELETE', '/api/mfa', code)
+                .then(() => {
+                    document.getElementById('mfaBackupCodes').style.display = 'none';
+                    refreshMfa();
+                })
+                .catch(error => alert(`Failed to disable two-factor authentication: ${error.message}`));
+        });
+
+        refreshMfa();
+
+        // Failed logins are only listed for admins, who can unlock the subjects
+        function refreshLockouts() {
+            fetch('/api/lockouts')
+                .then(response => response.ok ? response.json() : [])
+                .then(entries => {
+                    const tableBody = document.getElementById('lockoutsTable').querySelector('tbody');
+                    tableBody.innerHTML = '';
+                    entries.forEach(entry => {
+                        const row = document.createElement('tr');
+                        // Values are set as text, since the usernames are entered on the login page
+                        [
+                            entry.kind,
+                            entry.subject,
+                            entry.failures,
+                            `${new Date(entry.blocked_until * 1000).toLocaleString()}${entry.locked ? ' (locked)' : ''}`,
+                        ].forEach(value => {
+                            const cell = document.createElement('td');
+                            cell.textContent = value;
+                            row.appendChild(cell);
+                        });
+                        const action = document.createElement('td');
+                        const button = document.createElement('button');
+                        button.className = 'tooltip-button';
+                        button.textContent = 'Unlock';
+                        button.addEventListener('click', () => {
+                            fetch(`/api/lockouts/${entry.kind}/${encodeURIComponent(entry.subject)}`, {method: 'DELETE'})
+                                .then(() => refreshLockouts());
+                        });
+                        action.appendChild(button);
+                        row.appendChild(action);
+                        tableBody.appendChild(row);
+                    });
+                })
+                .catch(error => console.warn('Error fetching failed logins:', error));
+        }
+
+        if (document.getElementById('lockouts')) {
+            refreshLockouts();
+            setInterval(refreshLockouts, alertsInterval);
+        }
+
+        // API tokens are only managed by admins, and the token itself is only shown once after it is created
+        function refreshTokens() {
+            fetch('/api/tokens')
+                .then(response => response.ok ? response.json() : [])
+                .then(tokens => {
+                    const tableBody = document.getElementById('tokensTable').querySelector('tbody');
+                    tableBody.innerHTML = '';
+                    const formatTime = timestamp => timestamp ? new Date(timestamp * 1000).toLocaleString() : 'Never';
+                    tokens.forEach(token => {
+                        const row = document.createElement('tr');
+                        [
+                            token.name,
+                            token.role,
+                            token.created_by,
+                            formatTime(token.created_at),
+                            formatTime(token.expires_at),
+                            formatTime(token.last_used),
+                        ].forEach(value => {
+                            const cell = document.createElement('td');
+                            cell.textContent = value;
+                            row.appendChild(cell);
+                        });
+                        const action = document.createElement('td');
+                        const button = document.createElement('button');
+                        button.className = 'tooltip-button';
+                        button.textContent = 'Revoke';
+                        button.addEventListener('click', () => {
+                            if (confirm(`Revoke the API token '${token.name}'?`)) {
+                                fetch(`/api/tokens/${token.id}`, {method: 'DELETE'})
+                                    .then(() => refreshTokens());
+                            }
+                        });
+                        action.appendChild(button);
+                        row.appendChild(action);
+                        tableBody.appendChild(row);
+                    });
+                })
+                .catch(error => console.warn('Error fetching API tokens:', error));
+        }
+
+        if (document.getElementById('tokens')) {
+            document.getElementById('tokenForm').addEventListener('submit', event => {
+                event.preventDefault();
+                const expiry = document.getElementById('tokenExpiry').value;
+                fetch('/api/tokens', {
+                    method: 'POST',
+                    headers: {'Content-Type': 'application/json'},
+                    body: JSON.stringify({
+                        name: document.getElementById('tokenName').value,
+                        role: document.getElementById('tokenRole').value,
+                        expires_in: expiry ? parseInt(expiry) : null,
+                    }),
+                })
+                    .then(response => response.json().then(data => {
+                        if (!response.ok) {
+                            throw new Error(data.detail);
+                        }
+                        document.getElementById('tokenForm').reset();
+                        document.getElementById('tokenValue').textContent = data.token;
+                        document.getElementById('tokenCreated').style.display = '';
+                        refreshTokens();
+                    }))
+                    .catch(error => alert(`Failed to create the API token: ${error.message}`));
+            });
+            refreshTokens();
+            setInterval(refreshTokens, alertsInterval);
+        }
+
         let memoryChartInstance = null;
         let swapChartInstance = null;
         let diskChartInstance = null;
         let loadChartInstance = null;
+        let networkChartInstance = null;
+        let diskIOChartInstance = null;
 
-        ws.onmessage = function (event) {
+        function handleMessage(event) {
             let data;
             try {
                 data = JSON.parse(event.data);
@@ -402,6 +1029,93 @@ pub fn get_content() -> String {
                 logOut();
                 return;
             }
+            if (data.type === 'ack') {
+                console.log(`Subscription updated [${data.action}]:`, data);
+                return;
+            }
+            if (data.type === 'error') {
+                console.warn('Command rejected by the server:', data.detail);
+                return;
+            }
+            if (data.type === 'history') {
+                backfillCharts(data.metrics);
+                return;
+            }
+
+            const diskIOStatsJSON = data.disk_io;
+            // Check if diskIOStatsJSON is valid
+            if (diskIOStatsJSON && diskIOStatsJSON.length > 0) {
+                // Show the container and the table
+                const statsDiskIO = document.getElementById("disk-io-stats");
+                statsDiskIO.style.display = "flex";
+                const table = document.getElementById("diskIOStatsTable");
+                table.style.display = "table";
+                // Get reference to the table body
+                const tableBody = document.querySelector('#diskIOStatsTable tbody');
+                // Clear the existing table rows
+                tableBody.innerHTML = '';
+                const throughput = {};
+                // Loop through the JSON data and populate the table
+                diskIOStatsJSON.forEach(device => {
+                    const row = document.createElement('tr');
+                    row.innerHTML = `
+                        <td>${device.name}</td>
+                        <td>${formatBytes(device.read_bytes)}/s</td>
+                        <td>${formatBytes(device.write_bytes)}/s</td>
+                        <td>${device.read_iops.toFixed(1)} / ${device.write_iops.toFixed(1)}</td>
+                        <td>${device.await_ms.toFixed(2)} ms</td>
+                        <td>${device.utilization.toFixed(1)}%</td>
+                    `;
+                    tableBody.appendChild(row);
+                    throughput[`${device.name} read`] = device.read_bytes;
+                    throughput[`${device.name} write`] = device.write_bytes;
+                });
+                if (!diskIOChartInstance) {
+                    diskIOChartInstance = createLineChart('diskIOChart', rateFormatter);
+                }
+                appendToLineChart(diskIOChartInstance, new Date().toLocaleTimeString(), throughput);
+            } else {
+                // Hide the container if no data is available
+                document.getElementById("disk-io-stats").style.display = "none";
+            }
+
+            const networkStatsJSON = data.network_stats;
+            // Check if networkStatsJSON is valid
+            if (networkStatsJSON && networkStatsJSON.length > 0) {
+                // Show the container and the table
+                const statsNetwork = document.getElementById("network-stats");
+                statsNetwork.style.display = "flex";
+                const table = document.getElementById("networkStatsTable");
+                table.style.display = "table";
+                // Get reference to the table body
+                const tableBody = document.querySelector('#networkStatsTable tbody');
+                // Clear the existing table rows
+                tableBody.innerHTML = '';
+                const throughput = {};
+                // Loop through the JSON data and populate the table
+                networkStatsJSON.forEach(iface => {
+                    const row = document.createElement('tr');
+                    row.innerHTML = `
+                        <td>${iface.name}</td>
+                        <td>${iface.state}</td>
+                        <td>${formatBytes(iface.rates.rx_bytes)}/s</td>
+                        <td>${formatBytes(iface.rates.tx_bytes)}/s</td>
+                        <td>${iface.rates.rx_packets.toFixed(1)}/s / ${iface.rates.tx_packets.toFixed(1)}/s</td>
+                        <td>${iface.totals.rx_errors} / ${iface.totals.tx_errors}</td>
+                        <td>${iface.totals.rx_dropped} / ${iface.totals.tx_dropped}</td>
+                    `;
+                    tableBody.appendChild(row);
+                    throughput[`${iface.name} rx`] = iface.rates.rx_bytes;
+                    throughput[`${iface.name} tx`] = iface.rates.tx_bytes;
+                });
+                if (!networkChartInstance) {
+                    networkChartInstance = createLineChart('networkChart', rateFormatter);
+                }
+                appendToLineChart(networkChartInstance, new Date().toLocaleTimeString(), throughput);
+            } else {
+                // Hide the container if no data is available
+                document.getElementById("network-stats").style.display = "none";
+            }
 
             const dockerStatsJSON = data.docker_stats;
             // Check if dockerStatsJSON is valid
@@ -503,26 +1217,30 @@ pub fn get_content() -> String {
 
             // Update CPU usage
             const cpuUsage = data.cpu_usage;
-            const cpuContainer = document.getElementById('cpuUsageContainer');
-            cpuContainer.innerHTML = ''; // Clear previous content
-            cpuUsage.forEach((usage, index) => {
-                const cpuDiv = document.createElement('div');
-                cpuDiv.innerHTML = `
-                        <strong>CPU ${index + 1}:</strong> ${usage}%
-                        <div class="progress">
-                            <div id="cpu${index}" class="progress-bar"></div>
-                        </div>
-                    `;
-                cpuContainer.appendChild(cpuDiv);
-                updateProgressBar(`cpu${index}`, usage);
-            });
+            if (cpuUsage) {
+                const cpuContainer = document.getElementById('cpuUsageContainer');
+                cpuContainer.innerHTML = ''; // Clear previous content
+                cpuUsage.forEach((usage, index) => {
+                    const cpuDiv = document.createElement('div');
+                    cpuDiv.innerHTML = `
+                            <strong>CPU ${index + 1}:</strong> ${usage}%
+                            <div class="progress">
+                                <div id="cpu${index}" class="progress-bar"></div>
+                            </div>
+                        `;
+                    cpuContainer.appendChild(cpuDiv);
+                    updateProgressBar(`cpu${index}`, usage);
+                });
+            }
 
             // Memory Usage Progress Bar
             const memoryInfo = data.memory_info;
-            const memoryUsage = (memoryInfo.used / memoryInfo.total) * 100;
-            document.getElementById('memoryUsage').style.width = memoryUsage.toFixed(2) + '%';
-            document.getElementById('memoryUsageText').innerText = `Memory: ${memoryUsage.toFixed(2)}%`;
-            updateProgressBar('memoryUsage', memoryUsage);
+            if (memoryInfo) {
+                const memoryUsage = (memoryInfo.used / memoryInfo.total) * 100;
+                document.getElementById('memoryUsage').style.width = memoryUsage.toFixed(2) + '%';
+                document.getElementById('memoryUsageText').innerText = `Memory: ${memoryUsage.toFixed(2)}%`;
+                updateProgressBar('memoryUsage', memoryUsage);
+            }
 
             // Swap Usage Progress Bar
             const swapInfo = data.swap_info;
@@ -533,120 +1251,134 @@ pub fn get_content() -> String {
                 updateProgressBar('swapUsage', swapUsage);
             }
 
-            // Disk Usage Progress Bar
+            // Disk Usage Progress Bar per mount
             const diskInfo = data.disk_info;
-            const diskUsage = (diskInfo.used / diskInfo.total) * 100;
-            document.getElementById('diskUsage').style.width = diskUsage.toFixed(2) + '%';
-            document.getElementById('diskUsageText').innerText = `Disk: ${diskUsage.toFixed(2)}%`;
-            updateProgressBar('diskUsage', diskUsage);
+            const diskUsageJSON = data.disk_usage;
+            if (diskUsageJSON) {
+                const diskContainer = document.getElementById('diskUsageContainer');
+                diskContainer.innerHTML = ''; // Clear previous content
+                diskUsageJSON.forEach((mount, index) => {
+                    const diskUsage = mount.total > 0 ? (mount.used / mount.total) * 100 : 0;
+                    const diskDiv = document.createElement('div');
+                    diskDiv.innerHTML = `
+                            <strong>${mount.mount_point}</strong> (${mount.file_system}):
+                            ${formatBytes(mount.used)} / ${formatBytes(mount.total)}
+                            <div class="progress">
+                                <div id="disk${index}" class="progress-bar"></div>
+                            </div>
+                            <p>Disk: ${diskUsage.toFixed(2)}%</p>
+                        `;
+                    diskContainer.appendChild(diskDiv);
+                    updateProgressBar(`disk${index}`, diskUsage);
+                });
+            }
 
             // CPU Load Avg Graph
             const loadAverages = data.load_averages;
-            if (loadChartInstance) {
-                loadChartInstance.data.datasets[0].data = [loadAverages["m1"], loadAverages["m5"], loadAverages["m15"]];
-                loadChartInstance.update();
-            } else {
-                const ctx = document.getElementById('loadChart').getContext('2d');
-                loadChartInstance = new Chart(ctx, {
-                    type: 'bar',
-                    data: {
-                        labels: ['1 minute', '5 minutes', '15 minutes'],
-                        datasets: [{
-                            label: 'Load Average',
-                            data: [loadAverages["m1"], loadAverages["m5"], loadAverages["m15"]],
-                            backgroundColor: [
-                                'rgba(75, 192, 192, 0.2)',
-                                'rgba(153, 102, 255, 0.2)',
-                                'rgba(255, 159, 64, 0.2)'
-                            ],
-                            borderColor: [
-                                'rgba(75, 192, 192, 1)',
-                                'rgba(153, 102, 255, 1)',
-                                'rgba(255, 159, 64, 1)'
-                            ],
-                            borderWidth: 1
-                        }]
-                    },
-                    options: {
-                        plugins: {
-                            // Hide the legend
-                            legend: {
-                                display: false
-                            }
+            if (loadAverages) {
+                if (!loadChartInstance) {
+                    loadChartInstance = createLineChart('loadChart', loadFormatter);
+                }
+                appendToLineChart(loadChartInstance, new Date().toLocaleTimeString(), {
+                    [loadLabels['load.m1']]: loadAverages["m1"],
+                    [loadLabels['load.m5']]: loadAverages["m5"],
+                    [loadLabels['load.m15']]: loadAverages["m15"]
+                });
+            }
+
+            // Memory Chart
+            if (memoryInfo) {
+                document.getElementById("memoryTotal").innerText = `Total: ${formatBytes(memoryInfo.total)}`;
+                if (memoryChartInstance) {
+                    memoryChartInstance.data.datasets[0].data = [memoryInfo.used, memoryInfo.total - memoryInfo.used];
+                    memoryChartInstance.update();
+                } else {
+                    const memoryChart = document.getElementById('memoryChart').getContext('2d');
+                    memoryChartInstance = new Chart(memoryChart, {
+                        type: 'pie',
+                        data: {
+                            labels: ['Used', 'Free'],
+                            datasets: [{
+                                label: 'Memory Usage',
+                                data: [memoryInfo.used, memoryInfo.total - memoryInfo.used],
+                                backgroundColor: ['#FF6384', '#36A2EB']
+                            }]
                         },
-                        scales: {
-                            y: {
-                                beginAtZero: true,
-                                title: {
-                                    display: true,
-                                    text: 'Number of Processes'
-                                },
-                                ticks: {
-                                    // Set integer step size
-                                    stepSize: 1,
-                                    callback: function (value) {
-                                        return Number.isInteger(value) ? value : '';
+                        options: {
+                            responsive: true,
+                            plugins: {
+                                tooltip: {
+                                    callbacks: {
+                                        label: function (tooltipItem) {
+                                            const value = tooltipItem.raw;
+                                            const formattedValue = formatBytes(value);
+                                            return `${tooltipItem.label}: ${formattedValue}`;
+                                        }
                                     }
                                 }
                             }
                         }
-                    }
-                });
+                    });
+                }
             }
 
-            // Memory Chart
-            document.getElementById("memoryTotal").innerText = `Total: ${formatBytes(memoryInfo.total)}`;
-            if (memoryChartInstance) {
-                memoryChartInstance.data.datasets[0].data = [memoryInfo.used, memoryInfo.total - memoryInfo.used];
-                memoryChartInstance.update();
-            } else {
-                const memoryChart = document.getElementById('memoryChart').getContext('2d');
-                memoryChartInstance = new Chart(memoryChart, {
-                    type: 'pie',
-                    data: {
-                        labels: ['Used', 'Free'],
-                        datasets: [{
-                            label: 'Memory Usage',
-                            data: [memoryInfo.used, memoryInfo.total - memoryInfo.used],
-                            backgroundColor: ['#FF6384', '#36A2EB']
-                        }]
-                    },
-                    options: {
-                        responsive: true,
-                        plugins: {
-                            tooltip: {
-                                callbacks: {
-                                    label: function (tooltipItem) {
-                                        const value = tooltipItem.raw;
-                                        const formattedValue = formatBytes(value);
-                                        return `${tooltipItem.label}: ${formattedValue}`;
+            // Swap Chart
+            if (swapInfo) {
+                const swapChart = document.getElementById('swapChart');
+                if (swapChart) {
+                    document.getElementById("swapTotal").innerText = `Total: ${formatBytes(swapInfo.total)}`;
+                }
+                if (swapChartInstance) {
+                    swapChartInstance.data.datasets[0].data = [swapInfo.used, swapInfo.total - swapInfo.used];
+                    swapChartInstance.update();
+                } else {
+                    if (swapChart) {
+                        const swapContext = swapChart.getContext('2d')
+                        swapChartInstance = new Chart(swapContext, {
+                            type: 'pie',
+                            data: {
+                                labels: ['Used', 'Free'],
+                                datasets: [{
+                                    label: 'Swap Usage',
+                                    data: [swapInfo.used, swapInfo.total - swapInfo.used],
+                                    backgroundColor: ['#FFCE56', '#E7E9ED']
+                                }]
+                            },
+                            options: {
+                                responsive: true,
+                                plugins: {
+                                    tooltip: {
+                                        callbacks: {
+                                            label: function (tooltipItem) {
+                                                const value = tooltipItem.raw;
+                                                const formattedValue = formatBytes(value);
+                                                return `${tooltipItem.label}: ${formattedValue}`;
+                                            }
+                                        }
                                     }
                                 }
                             }
-                        }
+                        });
                     }
-                });
+                }
             }
 
-            // Swap Chart
-            const swapChart = document.getElementById('swapChart');
-            if (swapChart) {
-                document.getElementById("swapTotal").innerText = `Total: ${formatBytes(swapInfo.total)}`;
-            }
-            if (swapChartInstance) {
-                swapChartInstance.data.datasets[0].data = [swapInfo.used, swapInfo.total - swapInfo.used];
-                swapChartInstance.update();
-            } else {
-                if (swapChart) {
-                    const swapContext = swapChart.getContext('2d')
-                    swapChartInstance = new Chart(swapContext, {
+            // Disk Chart
+            if (diskInfo) {
+                document.getElementById("diskTotal").innerText = `Total: ${formatBytes(diskInfo.total)}`;
+                if (diskChartInstance) {
+                    diskChartInstance.data.datasets[0].data = [diskInfo.used, diskInfo.total - diskInfo.used];
+                    diskChartInstance.update();
+                } else {
+                    const diskChart = document.getElementById('diskChart').getContext('2d');
+                    diskChartInstance = new Chart(diskChart, {
                         type: 'pie',
                         data: {
                             labels: ['Used', 'Free'],
                             datasets: [{
-                                label: 'Swap Usage',
-                                data: [swapInfo.used, swapInfo.total - swapInfo.used],
-                                backgroundColor: ['#FFCE56', '#E7E9ED']
+                                label: 'Disk Usage',
+                                data: [diskInfo.used, diskInfo.total - diskInfo.used],
+                                backgroundColor: ['#63950d', '#ca7b00']
                             }]
                         },
                         options: {
@@ -667,41 +1399,8 @@ pub fn get_content() -> String {
                 }
             }
 
-            // Disk Chart
-            document.getElementById("diskTotal").innerText = `Total: ${formatBytes(diskInfo.total)}`;
-            if (diskChartInstance) {
-                diskChartInstance.data.datasets[0].data = [diskInfo.used, diskInfo.total - diskInfo.used];
-                diskChartInstance.update();
-            } else {
-                const diskChart = document.getElementById('diskChart').getContext('2d');
-                diskChartInstance = new Chart(diskChart, {
-                    type: 'pie',
-                    data: {
-                        labels: ['Used', 'Free'],
-                        datasets: [{
-                            label: 'Disk Usage',
-                            data: [diskInfo.used, diskInfo.total - diskInfo.used],
-                            backgroundColor: ['#63950d', '#ca7b00']
-                        }]
-                    },
-                    options: {
-                        responsive: true,
-                        plugins: {
-                            tooltip: {
-                                callbacks: {
-                                    label: function (tooltipItem) {
-                                        const value = tooltipItem.raw;
-                                        const formattedValue = formatBytes(value);
-                                        return `${tooltipItem.label}: ${formattedValue}`;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                });
-            }
-
-        };
+        }
+        ws.onmessage = handleMessage;
 
         function updateProgressBar(id, percentage) {
             const bar = document.getElementById(id);
@@ -722,6 +1421,120 @@ pub fn get_content() -> String {
             }
         }
 
+        // Number of data points retained in the line charts
+        const chartWindow = 60;
+        const loadFormatter = value => Number(value).toFixed(2);
+        const rateFormatter = value => `${formatBytes(value)}/s`;
+        // Dataset labels for the load averages in history
+        const loadLabels = {'load.m1': '1 minute', 'load.m5': '5 minutes', 'load.m15': '15 minutes'};
+
+        function backfillCharts(metrics) {
+            // Group the points by timestamp, so that every chart gets one label per sample
+            const timeline = {};
+            Object.entries(metrics).forEach(([metric, points]) => {
+                // Interface and device names can contain dots, so the suffix is split from the end
+                const suffix = metric.substring(metric.lastIndexOf('.') + 1);
+                const name = metric.substring(metric.indexOf('.') + 1, metric.lastIndexOf('.'));
+                let chart = null;
+                let label = null;
+                if (metric in loadLabels) {
+                    chart = 'load';
+                    label = loadLabels[metric];
+                } else if (metric.startsWith('network.')) {
+                    chart = 'network';
+                    label = `${name} ${suffix}`;
+                } else if (metric.startsWith('disk_io.') && suffix !== 'util') {
+                    chart = 'diskIO';
+                    label = `${name} ${suffix}`;
+                }
+                if (!chart) {
+                    return;
+                }
+                points.forEach(([timestamp, value]) => {
+                    timeline[timestamp] = timeline[timestamp] || {load: {}, network: {}, diskIO: {}};
+                    timeline[timestamp][chart][label] = value;
+                });
+            });
+            Object.keys(timeline).sort((a, b) => a - b).forEach(timestamp => {
+                const label = new Date(timestamp * 1000).toLocaleTimeString();
+                const values = timeline[timestamp];
+                if (Object.keys(values.load).length > 0) {
+                    loadChartInstance = loadChartInstance || createLineChart('loadChart', loadFormatter);
+                    appendToLineChart(loadChartInstance, label, values.load);
+                }
+                if (Object.keys(values.network).length > 0) {
+                    networkChartInstance = networkChartInstance || createLineChart('networkChart', rateFormatter);
+                    appendToLineChart(networkChartInstance, label, values.network);
+                }
+                if (Object.keys(values.diskIO).length > 0) {
+                    diskIOChartInstance = diskIOChartInstance || createLineChart('diskIOChart', rateFormatter);
+                    appendToLineChart(diskIOChartInstance, label, values.diskIO);
+                }
+            });
+        }
+
+        function createLineChart(canvasId, formatter) {
+            const ctx = document.getElementById(canvasId).getContext('2d');
+            return new Chart(ctx, {
+                type: 'line',
+                data: {
+                    labels: [],
+                    datasets: []
+                },
+                options: {
+                    animation: false,
+                    responsive: true,
+                    maintainAspectRatio: false,
+                    scales: {
+                        y: {
+                            beginAtZero: true,
+                            ticks: {
+                                callback: formatter
+                            }
+                        }
+                    },
+                    plugins: {
+                        tooltip: {
+                            callbacks: {
+                                label: function (tooltipItem) {
+                                    return `${tooltipItem.dataset.label}: ${formatter(tooltipItem.raw)}`;
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        }
+
+        function appendToLineChart(chart, label, values) {
+            chart.data.labels.push(label);
+            Object.entries(values).forEach(([name, value]) => {
+                let dataset = chart.data.datasets.find(dataset => dataset.label === name);
+                if (!dataset) {
+                    // Pad the new dataset, so that it lines up with the existing labels
+                    dataset = {
+                        label: name,
+                        data: new Array(chart.data.labels.length - 1).fill(null),
+                        pointRadius: 0,
+                        borderWidth: 1
+                    };
+                    chart.data.datasets.push(dataset);
+                }
+                dataset.data.push(value);
+            });
+            // Datasets that went missing in this sample are padded with a gap
+            chart.data.datasets.forEach(dataset => {
+                while (dataset.data.length < chart.data.labels.length) {
+                    dataset.data.push(null);
+                }
+            });
+            if (chart.data.labels.length > chartWindow) {
+                chart.data.labels.shift();
+                chart.data.datasets.forEach(dataset => dataset.data.shift());
+            }
+            chart.update();
+        }
+
         function formatBytes(bytes) {
             const units = ['bytes', 'KB', 'MB', 'GB', 'TB', 'PB', 'EB', 'ZB', 'YB'];
             let unitIndex = 0;
@@ -737,6 +1550,8 @@ pub fn get_content() -> String {
     function logOut() {
         window.location.href = window.location.origin + "{{ logout }}";
     }
+
+    document.getElementById('logout').addEventListener('click', logOut);
 </script>
 <footer>
     Generated by <a href="https://github.com/thevickypedia/SysMonk/releases/tag/v{{ version }}">SysMonk - v{{ version }}</a>
@@ -67,7 +67,7 @@ pub fn get_content() -> String {
                 Please refer <a href="https://www.enable-javascript.com/">enable-javascript</a> for how to.
             </h2>
             <form>
-                <button type="submit" onClick="<meta httpEquiv='refresh' content='0'>">RETRY</button>
+                <button type="submit">RETRY</button>
             </form>
         </div>
     </noscript>
@@ -76,19 +76,32 @@ pub fn get_content() -> String {
 <h2 style="margin-top:5%">LOGIN FAILED</h2>
 <h3>USER ERROR - REPLACE USER</h3>
 <p>
-    <img src="https://thevickypedia.github.io/open-source/images/gif/lockscape.gif"
-         onerror="this.src='https://vigneshrao.com/open-source/images/gif/lockscape.gif'"
+    <img id="image" src="https://thevickypedia.github.io/open-source/images/gif/lockscape.gif"
+         data-fallback="https://vigneshrao.com/open-source/images/gif/lockscape.gif"
          width="200" height="170" alt="Image" class="center">
 </p>
-<button style="text-align:center" onClick="window.location.href = '/monitor';">LOGIN</button>
+<button id="navigate" style="text-align:center">LOGIN</button>
 <br>
-<button style="text-align:center" onClick="alert('Forgot Password?\n\nRelax and try to remember your password.');">HELP
+<button id="help" style="text-align:center">HELP
 </button>
 <h4>Click <a href="https://vigneshrao.com/contact">HERE</a> to reach out.</h4>
 </body>
 <footer>
     Generated by <a href="https://github.com/thevickypedia/SysMonk/releases/tag/v{{ version }}">SysMonk - v{{ version }}</a>
 </footer>
+<!-- handlers are attached here, since the content security policy doesn't allow inline handlers -->
+<script nonce="{{ nonce }}">
+    const image = document.getElementById('image');
+    const fallback = () => image.src = image.dataset.fallback;
+    // Image may have failed to load before this script runs
+    if (image.complete && image.naturalWidth === 0) {
+        fallback();
+    } else {
+        image.addEventListener('error', fallback, {once: true});
+    }
+    document.getElementById('navigate').addEventListener('click', () => window.location.href = '/monitor');
+    document.getElementById('help').addEventListener('click', () => alert('Forgot Password?\n\nRelax and try to remember your password.'));
+</script>
 </html>
 "###.to_string()
 }